@@ -1,112 +1,262 @@
-struct Camera {
-    position: vec4<f32>,
-    view_projection: mat4x4<f32>,
-};
-
-struct Light {
-    position: vec3<f32>,
-    color: vec3<f32>,
-}
-
-@group(0) @binding(0) var t_diffuse: texture_2d<f32>;
-@group(0) @binding(1) var s_diffuse: sampler;
-@group(0) @binding(2) var t_normal: texture_2d<f32>;
-@group(0) @binding(3) var s_normal: sampler;
-@group(1) @binding(0) var<uniform> u_camera: Camera;
-@group(2) @binding(0) var<uniform> u_light: Light;
-
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) normal: vec3<f32>,
-    @location(3) tangent: vec3<f32>,
-    @location(4) bitangent: vec3<f32>,
-}
-
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
-    @location(9) normal_matrix_0: vec3<f32>,
-    @location(10) normal_matrix_1: vec3<f32>,
-    @location(11) normal_matrix_2: vec3<f32>,
-};
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>,
-    @location(1) tangent_position: vec3<f32>,
-    @location(2) tangent_light_position: vec3<f32>,
-    @location(3) tangent_view_position: vec3<f32>,
-}
-
-struct FragmentOutput {
-    @location(0) color: vec4<f32>,
-}
-
-@vertex
-fn vs_main(
-    model: VertexInput,
-    instance: InstanceInput,
-) -> VertexOutput {
-    let model_matrix = mat4x4<f32>(
-        instance.model_matrix_0,
-        instance.model_matrix_1,
-        instance.model_matrix_2,
-        instance.model_matrix_3,
-    );
-    let normal_matrix = mat3x3<f32>(
-        instance.normal_matrix_0,
-        instance.normal_matrix_1,
-        instance.normal_matrix_2,
-    );
-
-    let world_normal = normalize(normal_matrix * model.normal);
-    let world_tangent = normalize(normal_matrix * model.tangent);
-    let world_bitangent = normalize(normal_matrix * model.bitangent);
-    let tangent_matrix = transpose(mat3x3<f32>(
-        world_tangent,
-        world_bitangent,
-        world_normal,
-    ));
-
-    let world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
-
-    var out: VertexOutput;
-
-    out.clip_position = u_camera.view_projection * world_position;
-    out.tex_coords = model.tex_coords;
-    out.tangent_position = tangent_matrix * world_position.xyz;
-    out.tangent_view_position = tangent_matrix * u_camera.position.xyz;
-    out.tangent_light_position = tangent_matrix * u_light.position;
-
-    return out;
-}
-
-@fragment
-fn fs_main(in: VertexOutput) -> FragmentOutput {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    let object_normal: vec4<f32> = textureSample(t_normal, s_normal, in.tex_coords);
-
-    let tangent_normal = object_normal.xyz * 2.0 - 1.0;
-    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
-    let view_dir = normalize(in.tangent_view_position - in.tangent_position);
-    let half_dir = normalize(view_dir + light_dir);
-
-    let ambient_strength = 0.10;
-    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
-    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 32.0);
-
-    let ambient_color = ambient_strength * u_light.color;
-    let diffuse_color = diffuse_strength * u_light.color;
-    let specular_color = specular_strength * u_light.color;
-
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-
-    var out: FragmentOutput;
-
-    out.color = vec4<f32>(result, object_color.a);
-
-    return out;
-}
\ No newline at end of file
+struct Camera {
+    position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    inverse_sky_view_projection: mat4x4<f32>,
+};
+
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+
+struct Material {
+    alpha_cutoff: f32,
+    dissolve: f32,
+}
+
+@group(0) @binding(0) var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1) var s_diffuse: sampler;
+@group(0) @binding(2) var t_normal: texture_2d<f32>;
+@group(0) @binding(3) var s_normal: sampler;
+@group(0) @binding(4) var<uniform> u_material: Material;
+@group(0) @binding(5) var t_emissive: texture_2d<f32>;
+@group(0) @binding(6) var s_emissive: sampler;
+// Occlusion, roughness and metallic in the red, green and blue channels.
+@group(0) @binding(7) var t_orm: texture_2d<f32>;
+@group(0) @binding(8) var s_orm: sampler;
+// White at the surface and black at the deepest point.
+@group(0) @binding(9) var t_height: texture_2d<f32>;
+@group(0) @binding(10) var s_height: sampler;
+@group(1) @binding(0) var<uniform> u_camera: Camera;
+@group(2) @binding(0) var<uniform> u_light: Light;
+@group(3) @binding(1) var t_irradiance: texture_cube<f32>;
+@group(3) @binding(2) var t_prefiltered: texture_cube<f32>;
+@group(3) @binding(3) var t_brdf: texture_2d<f32>;
+@group(3) @binding(4) var s_environment: sampler;
+
+// Reflectance at normal incidence of common dielectrics.
+const F0: f32 = 0.04;
+// Matches `Camera::Z_FAR`.
+const FAR: f32 = 100.0;
+// How deep the black parts of height maps are, in texture coordinates.
+const PARALLAX_DEPTH: f32 = 0.04;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tangent_position: vec3<f32>,
+    @location(2) tangent_light_position: vec3<f32>,
+    @location(3) tangent_view_position: vec3<f32>,
+    @location(4) world_position: vec3<f32>,
+    @location(5) world_normal: vec3<f32>,
+    @location(6) world_tangent: vec3<f32>,
+    @location(7) world_bitangent: vec3<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    let world_normal = normalize(normal_matrix * model.normal);
+    let world_tangent = normalize(normal_matrix * model.tangent);
+    let world_bitangent = normalize(normal_matrix * model.bitangent);
+    let tangent_matrix = transpose(mat3x3<f32>(
+        world_tangent,
+        world_bitangent,
+        world_normal,
+    ));
+
+    let world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+
+    out.clip_position = u_camera.view_projection * world_position;
+    out.tex_coords = model.tex_coords;
+    out.tangent_position = tangent_matrix * world_position.xyz;
+    out.tangent_view_position = tangent_matrix * u_camera.position.xyz;
+    out.tangent_light_position = tangent_matrix * u_light.position;
+    out.world_position = world_position.xyz;
+    out.world_normal = world_normal;
+    out.world_tangent = world_tangent;
+    out.world_bitangent = world_bitangent;
+
+    return out;
+}
+
+fn sample_tangent_normal(tex_coords: vec2<f32>) -> vec3<f32> {
+    return textureSample(t_normal, s_normal, tex_coords).xyz * 2.0 - 1.0;
+}
+
+// Parallax offset mapping: moves the texture coordinates to where the view
+// ray would hit the height map, assuming the height there is the same.
+fn parallax(tex_coords: vec2<f32>, view_dir: vec3<f32>) -> vec2<f32> {
+    let depth = (1.0 - textureSample(t_height, s_height, tex_coords).r) * PARALLAX_DEPTH;
+
+    return tex_coords - view_dir.xy / max(view_dir.z, 0.1) * depth;
+}
+
+fn to_world_normal(in: VertexOutput, tangent_normal: vec3<f32>) -> vec3<f32> {
+    return normalize(mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    ) * tangent_normal);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let view_dir = normalize(in.tangent_view_position - in.tangent_position);
+    let tex_coords = parallax(in.tex_coords, view_dir);
+
+    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, tex_coords);
+    let orm = textureSample(t_orm, s_orm, tex_coords).rgb;
+    let occlusion = orm.r;
+    let roughness = orm.g;
+    let metallic = orm.b;
+
+    let tangent_normal = sample_tangent_normal(tex_coords);
+    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
+    let half_dir = normalize(view_dir + light_dir);
+
+    // The Blinn-Phong exponent that roughly matches the roughness.
+    let shininess = max(2.0 / max(roughness * roughness, 0.0001) - 2.0, 1.0);
+    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
+    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), shininess);
+
+    let diffuse_color = diffuse_strength * u_light.color;
+    let specular_color = specular_strength * u_light.color;
+
+    // Image based ambient light, using the split sum approximation for the
+    // specular part.
+    let world_normal = to_world_normal(in, tangent_normal);
+    let world_view_dir = normalize(u_camera.position.xyz - in.world_position);
+    let n_dot_v = max(dot(world_normal, world_view_dir), 0.0);
+    // Metals reflect in the color of their surface, and have no diffuse part.
+    let f0 = mix(vec3<f32>(F0), object_color.rgb, metallic);
+    let fresnel = f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+
+    let irradiance = textureSample(t_irradiance, s_environment, world_normal).rgb;
+    let max_lod = f32(textureNumLevels(t_prefiltered) - 1);
+    let prefiltered = textureSampleLevel(
+        t_prefiltered,
+        s_environment,
+        reflect(-world_view_dir, world_normal),
+        roughness * max_lod,
+    ).rgb;
+    let brdf = textureSample(t_brdf, s_environment, vec2<f32>(n_dot_v, roughness)).rg;
+
+    let ambient_color = ((1.0 - fresnel) * (1.0 - metallic) * irradiance * object_color.xyz
+        + prefiltered * (fresnel * brdf.x + brdf.y)) * occlusion;
+    let emissive = textureSample(t_emissive, s_emissive, tex_coords).rgb;
+
+    let result = (diffuse_color * (1.0 - metallic) + specular_color) * object_color.xyz
+        + ambient_color
+        + emissive;
+
+    // Only cutout materials have a cutoff above 0. Discarding comes after
+    // every implicit lod sample, which needs uniform control flow.
+    let alpha = object_color.a * u_material.dissolve;
+
+    if alpha < u_material.alpha_cutoff {
+        discard;
+    }
+
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(result, alpha);
+
+    return out;
+}
+
+// Debug views, picked with `debug.view` in settings.toml. Directions are
+// mapped from -1 to 1 onto colors from 0 to 1.
+
+@fragment
+fn fs_diffuse(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(textureSample(t_diffuse, s_diffuse, in.tex_coords).rgb, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_depth(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(vec3<f32>(distance(in.world_position, u_camera.position.xyz) / FAR), 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_normal_map(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(sample_tangent_normal(in.tex_coords) * 0.5 + 0.5, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_uvs(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(fract(in.tex_coords), 0.0, 1.0);
+
+    return out;
+}
+
+@fragment
+fn fs_world_normals(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(to_world_normal(in, sample_tangent_normal(in.tex_coords)) * 0.5 + 0.5, 1.0);
+
+    return out;
+}
+
+// The edges of the triangles, drawn over the scene.
+@fragment
+fn fs_wireframe(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(0.0, 1.0, 0.0, 1.0);
+
+    return out;
+}
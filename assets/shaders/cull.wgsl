@@ -0,0 +1,62 @@
+struct Params {
+    // Normals point inside the frustum.
+    planes: array<vec4<f32>, 6>,
+    // The center and radius of the mesh, in model space.
+    bounding_sphere: vec4<f32>,
+    instance_count: u32,
+    instance_floats: u32,
+}
+
+// Matches the layout `draw_indexed_indirect` reads.
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<uniform> u_params: Params;
+// Instances start with their model matrix, the rest is copied as is.
+@group(0) @binding(1) var<storage, read> instances: array<f32>;
+@group(0) @binding(2) var<storage, read_write> visible: array<f32>;
+@group(0) @binding(3) var<storage, read_write> draw: DrawIndexedIndirect;
+
+fn column(base: u32, index: u32) -> vec4<f32> {
+    let offset = base + index * 4u;
+
+    return vec4<f32>(
+        instances[offset],
+        instances[offset + 1u],
+        instances[offset + 2u],
+        instances[offset + 3u],
+    );
+}
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= u_params.instance_count {
+        return;
+    }
+
+    let base = id.x * u_params.instance_floats;
+    let model = mat4x4<f32>(column(base, 0u), column(base, 1u), column(base, 2u), column(base, 3u));
+
+    let center = (model * vec4<f32>(u_params.bounding_sphere.xyz, 1.0)).xyz;
+    let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+    let radius = u_params.bounding_sphere.w * scale;
+
+    for (var i = 0; i < 6; i += 1) {
+        let plane = u_params.planes[i];
+
+        if dot(plane.xyz, center) + plane.w < -radius {
+            return;
+        }
+    }
+
+    let output = atomicAdd(&draw.instance_count, 1u) * u_params.instance_floats;
+
+    for (var i = 0u; i < u_params.instance_floats; i += 1u) {
+        visible[output + i] = instances[base + i];
+    }
+}
@@ -0,0 +1,41 @@
+struct Camera {
+    position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    inverse_sky_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> u_camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = u_camera.view_projection * vec4<f32>(in.position, 1.0);
+    out.color = in.color;
+
+    return out;
+}
+
+// Unlit, so the lines keep their color whatever they are drawn over.
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = in.color;
+
+    return out;
+}
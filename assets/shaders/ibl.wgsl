@@ -0,0 +1,211 @@
+// Precomputes the maps used for image based lighting. Every entry point uses
+// its own bindings, so that each compute pipeline gets a layout that only
+// contains what it needs.
+
+struct Params {
+    lod: f32,
+    roughness: f32,
+}
+
+@group(0) @binding(0) var s_source: sampler;
+@group(0) @binding(1) var t_equirectangular: texture_2d<f32>;
+@group(0) @binding(2) var t_environment: texture_cube<f32>;
+@group(0) @binding(3) var t_output: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(4) var<uniform> u_params: Params;
+@group(0) @binding(5) var t_brdf: texture_storage_2d<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+const SAMPLE_COUNT: u32 = 1024u;
+
+// Direction through the center of a texel of a cube face, with the faces in
+// the +X, -X, +Y, -Y, +Z, -Z order of the array layers.
+fn cube_direction(id: vec3<u32>, size: u32) -> vec3<f32> {
+    let uv = (vec2<f32>(id.xy) + 0.5) / f32(size) * 2.0 - 1.0;
+
+    var direction: vec3<f32>;
+
+    switch id.z {
+        case 0u: {
+            direction = vec3<f32>(1.0, -uv.y, -uv.x);
+        }
+        case 1u: {
+            direction = vec3<f32>(-1.0, -uv.y, uv.x);
+        }
+        case 2u: {
+            direction = vec3<f32>(uv.x, 1.0, uv.y);
+        }
+        case 3u: {
+            direction = vec3<f32>(uv.x, -1.0, -uv.y);
+        }
+        case 4u: {
+            direction = vec3<f32>(uv.x, -uv.y, 1.0);
+        }
+        default: {
+            direction = vec3<f32>(-uv.x, -uv.y, -1.0);
+        }
+    }
+
+    return normalize(direction);
+}
+
+fn tangent_to_world(tangent: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+
+    if abs(normal.y) > 0.999 {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+
+    let right = normalize(cross(up, normal));
+    let forward = cross(normal, right);
+
+    return tangent.x * right + tangent.y * forward + tangent.z * normal;
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness * roughness * roughness * roughness;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+
+    return a2 / (PI * d * d);
+}
+
+// Samples a half vector around the z axis, distributed by the GGX lobe.
+fn importance_sample_ggx(xi: vec2<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+fn geometry_schlick_ggx(n_dot: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+
+    return n_dot / (n_dot * (1.0 - k) + k);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn equirectangular_to_cube(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = u32(textureDimensions(t_output).x);
+
+    if id.x >= size || id.y >= size {
+        return;
+    }
+
+    let direction = cube_direction(id, size);
+    let uv = vec2<f32>(atan2(direction.z, direction.x) / (2.0 * PI) + 0.5, acos(direction.y) / PI);
+    let color = textureSampleLevel(t_equirectangular, s_source, uv, u_params.lod);
+
+    textureStore(t_output, vec2<i32>(id.xy), i32(id.z), vec4<f32>(color.rgb, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn irradiance(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = u32(textureDimensions(t_output).x);
+
+    if id.x >= size || id.y >= size {
+        return;
+    }
+
+    let normal = cube_direction(id, size);
+
+    // The integral is smooth, so a low resolution mip of the environment
+    // avoids aliasing without needing more samples.
+    let lod = max(log2(f32(textureDimensions(t_environment).x) / 32.0), 0.0);
+    let step = 0.05;
+
+    var sum = vec3<f32>(0.0);
+    var count = 0.0;
+
+    for (var phi = 0.0; phi < 2.0 * PI; phi += step) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += step) {
+            let tangent = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = tangent_to_world(tangent, normal);
+
+            sum += textureSampleLevel(t_environment, s_source, direction, lod).rgb * cos(theta) * sin(theta);
+            count += 1.0;
+        }
+    }
+
+    textureStore(t_output, vec2<i32>(id.xy), i32(id.z), vec4<f32>(PI * sum / count, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn prefilter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = u32(textureDimensions(t_output).x);
+
+    if id.x >= size || id.y >= size {
+        return;
+    }
+
+    // The view and reflection directions are assumed to equal the normal.
+    let normal = cube_direction(id, size);
+    let roughness = u_params.roughness;
+    let environment_size = f32(textureDimensions(t_environment).x);
+    let texel_solid_angle = 4.0 * PI / (6.0 * environment_size * environment_size);
+
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let half_dir = tangent_to_world(importance_sample_ggx(hammersley(i, SAMPLE_COUNT), roughness), normal);
+        let light_dir = normalize(2.0 * dot(normal, half_dir) * half_dir - normal);
+        let n_dot_l = dot(normal, light_dir);
+
+        if n_dot_l > 0.0 {
+            // Sampling a mip that matches the solid angle of each sample
+            // removes the fireflies of bright, small light sources.
+            let n_dot_h = max(dot(normal, half_dir), 0.0);
+            let pdf = distribution_ggx(n_dot_h, roughness) / 4.0 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf + 0.0001);
+            let lod = select(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, u_params.lod, roughness == 0.0);
+
+            sum += textureSampleLevel(t_environment, s_source, light_dir, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    textureStore(t_output, vec2<i32>(id.xy), i32(id.z), vec4<f32>(sum / max(weight, 0.0001), 1.0));
+}
+
+// Scale and bias to F0 of the split sum approximation, indexed by n·v and
+// roughness.
+@compute @workgroup_size(8, 8, 1)
+fn brdf(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<u32>(textureDimensions(t_brdf));
+
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    let n_dot_v = (f32(id.x) + 0.5) / f32(size.x);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+    let view_dir = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+    var scale = 0.0;
+    var bias = 0.0;
+
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let half_dir = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), roughness);
+        let light_dir = normalize(2.0 * dot(view_dir, half_dir) * half_dir - view_dir);
+
+        let n_dot_l = max(light_dir.z, 0.0);
+        let n_dot_h = max(half_dir.z, 0.0);
+        let v_dot_h = max(dot(view_dir, half_dir), 0.0);
+
+        if n_dot_l > 0.0 {
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    textureStore(t_brdf, vec2<i32>(id.xy), vec4<f32>(scale, bias, 0.0, 0.0) / f32(SAMPLE_COUNT));
+}
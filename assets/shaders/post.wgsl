@@ -0,0 +1,286 @@
+struct Params {
+    bloom_intensity: f32,
+    bloom_threshold: f32,
+    bloom_knee: f32,
+    exposure: f32,
+    tonemapper: u32,
+    encode_srgb: u32,
+}
+
+@group(0) @binding(0) var t_source: texture_2d<f32>;
+@group(0) @binding(1) var s_source: sampler;
+@group(0) @binding(2) var<uniform> u_params: Params;
+@group(0) @binding(3) var t_bloom: texture_2d<f32>;
+
+const TONEMAPPER_ACES: u32 = 0u;
+const TONEMAPPER_REINHARD: u32 = 1u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle covering the screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+
+    return out;
+}
+
+fn sample_offset(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    return textureSample(t_source, s_source, uv + texel * vec2<f32>(x, y)).rgb;
+}
+
+// The 13 tap filter from Call of Duty: Advanced Warfare, which halves the
+// resolution without the flickering of a plain box filter.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    let center = sample_offset(uv, texel, 0.0, 0.0);
+    let inner = sample_offset(uv, texel, -1.0, 1.0)
+        + sample_offset(uv, texel, 1.0, 1.0)
+        + sample_offset(uv, texel, -1.0, -1.0)
+        + sample_offset(uv, texel, 1.0, -1.0);
+    let corners = sample_offset(uv, texel, -2.0, 2.0)
+        + sample_offset(uv, texel, 2.0, 2.0)
+        + sample_offset(uv, texel, -2.0, -2.0)
+        + sample_offset(uv, texel, 2.0, -2.0);
+    let edges = sample_offset(uv, texel, 0.0, 2.0)
+        + sample_offset(uv, texel, -2.0, 0.0)
+        + sample_offset(uv, texel, 2.0, 0.0)
+        + sample_offset(uv, texel, 0.0, -2.0);
+
+    return center * 0.125 + inner * 0.125 + corners * 0.03125 + edges * 0.0625;
+}
+
+// Keeps what is brighter than the threshold, with a soft transition of
+// `bloom_knee` around it.
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = u_params.bloom_knee;
+
+    var soft = clamp(brightness - u_params.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+
+    return color * max(soft, brightness - u_params.bloom_threshold) / max(brightness, 0.0001);
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(threshold(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// A 3x3 tent filter, blended additively onto the next larger level.
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    let color = sample_offset(in.uv, texel, 0.0, 0.0) * 4.0
+        + (sample_offset(in.uv, texel, -1.0, 0.0)
+            + sample_offset(in.uv, texel, 1.0, 0.0)
+            + sample_offset(in.uv, texel, 0.0, -1.0)
+            + sample_offset(in.uv, texel, 0.0, 1.0)) * 2.0
+        + sample_offset(in.uv, texel, -1.0, -1.0)
+        + sample_offset(in.uv, texel, 1.0, -1.0)
+        + sample_offset(in.uv, texel, -1.0, 1.0)
+        + sample_offset(in.uv, texel, 1.0, 1.0);
+
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    return clamp(
+        (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14),
+        vec3<f32>(0.0),
+        vec3<f32>(1.0),
+    );
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(t_source, s_source, in.uv).rgb
+        + textureSample(t_bloom, s_source, in.uv).rgb * u_params.bloom_intensity;
+    let exposed = hdr * exp2(u_params.exposure);
+
+    var color: vec3<f32>;
+
+    if u_params.tonemapper == TONEMAPPER_ACES {
+        color = aces(exposed);
+    } else if u_params.tonemapper == TONEMAPPER_REINHARD {
+        color = reinhard(exposed);
+    } else {
+        color = clamp(exposed, vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+
+    // Surfaces without an sRGB format do not encode on write.
+    if u_params.encode_srgb != 0u {
+        color = linear_to_srgb(color);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
+
+// Perceptual luma. `t_source` already holds sRGB encoded values unless the
+// surface encodes them on write.
+fn luma(uv: vec2<f32>) -> f32 {
+    let color = textureSampleLevel(t_source, s_source, uv, 0.0).rgb;
+    let l = dot(color, vec3<f32>(0.299, 0.587, 0.114));
+
+    if u_params.encode_srgb != 0u {
+        return l;
+    }
+
+    return sqrt(l);
+}
+
+const FXAA_EDGE_THRESHOLD: f32 = 0.125;
+const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const FXAA_SEARCH_STEPS: i32 = 10;
+const FXAA_SUBPIXEL: f32 = 0.75;
+
+// A simplified FXAA 3.11: finds the direction of the edge through the pixel,
+// searches along it for both ends, and blends across the edge depending on
+// how far the pixel is from the nearer end.
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+    let uv = in.uv;
+
+    let center = luma(uv);
+    let north = luma(uv + vec2<f32>(0.0, -texel.y));
+    let south = luma(uv + vec2<f32>(0.0, texel.y));
+    let west = luma(uv + vec2<f32>(-texel.x, 0.0));
+    let east = luma(uv + vec2<f32>(texel.x, 0.0));
+
+    let lowest = min(center, min(min(north, south), min(west, east)));
+    let highest = max(center, max(max(north, south), max(west, east)));
+    let range = highest - lowest;
+
+    if range < max(FXAA_EDGE_THRESHOLD_MIN, highest * FXAA_EDGE_THRESHOLD) {
+        return textureSampleLevel(t_source, s_source, uv, 0.0);
+    }
+
+    let north_west = luma(uv + vec2<f32>(-texel.x, -texel.y));
+    let north_east = luma(uv + vec2<f32>(texel.x, -texel.y));
+    let south_west = luma(uv + vec2<f32>(-texel.x, texel.y));
+    let south_east = luma(uv + vec2<f32>(texel.x, texel.y));
+
+    let horizontal = abs(north_west + south_west - 2.0 * west)
+        + 2.0 * abs(north + south - 2.0 * center)
+        + abs(north_east + south_east - 2.0 * east);
+    let vertical = abs(north_west + north_east - 2.0 * north)
+        + 2.0 * abs(west + east - 2.0 * center)
+        + abs(south_west + south_east - 2.0 * south);
+    let is_horizontal = horizontal >= vertical;
+
+    // The neighbours across the edge, and the one it has the steeper
+    // gradient towards.
+    var positive = east;
+    var negative = west;
+    var step = texel.x;
+    if is_horizontal {
+        positive = south;
+        negative = north;
+        step = texel.y;
+    }
+
+    let gradient_positive = abs(positive - center);
+    let gradient_negative = abs(negative - center);
+    var gradient = gradient_negative;
+    var edge_luma = (negative + center) * 0.5;
+    if gradient_positive >= gradient_negative {
+        gradient = gradient_positive;
+        edge_luma = (positive + center) * 0.5;
+    } else {
+        step = -step;
+    }
+
+    var edge_uv = uv;
+    var along = vec2<f32>(0.0, texel.y);
+    if is_horizontal {
+        edge_uv.y += step * 0.5;
+        along = vec2<f32>(texel.x, 0.0);
+    } else {
+        edge_uv.x += step * 0.5;
+    }
+
+    let scaled_gradient = gradient * 0.25;
+    var uv_positive = edge_uv + along;
+    var uv_negative = edge_uv - along;
+    var end_positive = luma(uv_positive) - edge_luma;
+    var end_negative = luma(uv_negative) - edge_luma;
+
+    for (var i = 0; i < FXAA_SEARCH_STEPS; i += 1) {
+        let done_positive = abs(end_positive) >= scaled_gradient;
+        let done_negative = abs(end_negative) >= scaled_gradient;
+
+        if done_positive && done_negative {
+            break;
+        }
+        if !done_positive {
+            uv_positive += along;
+            end_positive = luma(uv_positive) - edge_luma;
+        }
+        if !done_negative {
+            uv_negative -= along;
+            end_negative = luma(uv_negative) - edge_luma;
+        }
+    }
+
+    var distance_positive = uv_positive.y - uv.y;
+    var distance_negative = uv.y - uv_negative.y;
+    if is_horizontal {
+        distance_positive = uv_positive.x - uv.x;
+        distance_negative = uv.x - uv_negative.x;
+    }
+
+    // The end that is nearer decides whether the pixel is on the side of the
+    // edge that gets blended.
+    let nearer_negative = distance_negative < distance_positive;
+    var end = end_positive;
+    if nearer_negative {
+        end = end_negative;
+    }
+
+    var edge_offset = 0.0;
+    if (center - edge_luma < 0.0) != (end < 0.0) {
+        edge_offset = 0.5 - min(distance_positive, distance_negative)
+            / (distance_positive + distance_negative);
+    }
+
+    // Blends single pixel features that the edge search misses.
+    let average = (2.0 * (north + south + west + east)
+        + north_west + north_east + south_west + south_east) / 12.0;
+    let subpixel = smoothstep(0.0, 1.0, clamp(abs(average - center) / range, 0.0, 1.0));
+    let subpixel_offset = subpixel * subpixel * FXAA_SUBPIXEL;
+
+    var offset = vec2<f32>(max(edge_offset, subpixel_offset) * step, 0.0);
+    if is_horizontal {
+        offset = vec2<f32>(0.0, max(edge_offset, subpixel_offset) * step);
+    }
+
+    return textureSampleLevel(t_source, s_source, uv + offset, 0.0);
+}
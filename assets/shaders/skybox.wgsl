@@ -0,0 +1,43 @@
+struct Camera {
+    position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    inverse_sky_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var t_environment: texture_cube<f32>;
+@group(0) @binding(4) var s_environment: sampler;
+@group(1) @binding(0) var<uniform> u_camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+// Draws a single triangle that covers the screen on the far plane, so the sky
+// only shows where nothing else has been drawn.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let clip_position = vec4<f32>(uv * 2.0 - 1.0, 1.0, 1.0);
+    let world_position = u_camera.inverse_sky_view_projection * clip_position;
+
+    var out: VertexOutput;
+
+    out.clip_position = clip_position;
+    out.direction = world_position.xyz / world_position.w;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(textureSample(t_environment, s_environment, in.direction).rgb, 1.0);
+
+    return out;
+}
@@ -0,0 +1,51 @@
+struct Sprites {
+    projection: mat4x4<f32>,
+    encode_srgb: u32,
+}
+
+@group(0) @binding(0) var<uniform> u_sprites: Sprites;
+@group(1) @binding(0) var t_sprite: texture_2d<f32>;
+@group(1) @binding(1) var s_sprite: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = u_sprites.projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Sprites are sampled as linear through their sRGB format.
+    let color = in.color * textureSample(t_sprite, s_sprite, in.tex_coords);
+
+    // Surfaces without an sRGB format do not encode on write.
+    if u_sprites.encode_srgb != 0u {
+        return vec4<f32>(linear_to_srgb(color.rgb), color.a);
+    }
+
+    return color;
+}
@@ -0,0 +1,67 @@
+struct Text {
+    size: vec2<f32>,
+    encode_srgb: u32,
+    sdf: u32,
+}
+
+@group(0) @binding(0) var<uniform> u_text: Text;
+@group(0) @binding(1) var t_atlas: texture_2d<f32>;
+@group(0) @binding(2) var s_atlas: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+// Positions are in pixels from the top left corner of the framebuffer.
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(
+        in.position.x / u_text.size.x * 2.0 - 1.0,
+        1.0 - in.position.y / u_text.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let value = textureSample(t_atlas, s_atlas, in.tex_coords).r;
+
+    var coverage = value;
+    if u_text.sdf != 0u {
+        // The edge is at 0.5, antialiased over about a pixel whatever the
+        // scale the glyph is drawn at.
+        let width = max(fwidth(value) * 0.5, 0.0001);
+        coverage = smoothstep(0.5 - width, 0.5 + width, value);
+    }
+
+    var color = in.color.rgb;
+
+    // Surfaces without an sRGB format do not encode on write.
+    if u_text.encode_srgb != 0u {
+        color = linear_to_srgb(color);
+    }
+
+    return vec4<f32>(color, in.color.a * coverage);
+}
@@ -0,0 +1,65 @@
+struct Screen {
+    size: vec2<f32>,
+    encode_srgb: u32,
+}
+
+@group(0) @binding(0) var<uniform> u_screen: Screen;
+@group(1) @binding(0) var t_ui: texture_2d<f32>;
+@group(1) @binding(1) var s_ui: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+// Positions are in points from the top left corner of the screen.
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(
+        in.position.x / u_screen.size.x * 2.0 - 1.0,
+        1.0 - in.position.y / u_screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.tex_coords = in.tex_coords;
+    // Vertex colors are sRGB with premultiplied alpha.
+    out.color = vec4<f32>(srgb_to_linear(in.color.rgb), in.color.a);
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Textures are sampled as linear through their sRGB format.
+    var color = in.color * textureSample(t_ui, s_ui, in.tex_coords);
+
+    // Surfaces without an sRGB format do not encode on write.
+    if u_screen.encode_srgb != 0u {
+        color = vec4<f32>(linear_to_srgb(color.rgb), color.a);
+    }
+
+    return color;
+}
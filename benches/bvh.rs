@@ -0,0 +1,214 @@
+//! Times the BVH with 100, 10k and 100k boxes scattered through a scene, and
+//! box queries against going through every box, which the tests of the `bvh`
+//! module check them against.
+//!
+//! Usage: `cargo bench --bench bvh`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use weng_game::bvh::{Aabb, Bvh};
+
+const COUNTS: [usize; 3] = [100, 10_000, 100_000];
+
+/// The boxes and a tree of them, with the same density of objects whatever
+/// their number.
+struct Scene {
+    boxes: Vec<Aabb>,
+    extent: f32,
+    random: SmallRng,
+    tree: Bvh<usize>,
+}
+
+impl Scene {
+    fn new(count: usize) -> Self {
+        let mut random = SmallRng::seed_from_u64(0);
+        let extent = (count as f32).cbrt() * 4.0;
+        let boxes: Vec<_> = (0..count)
+            .map(|_| random_box(&mut random, extent))
+            .collect();
+        let mut tree = Bvh::new(0.25);
+
+        for (key, aabb) in boxes.iter().enumerate() {
+            tree.insert(key, *aabb);
+        }
+
+        Self {
+            boxes,
+            extent,
+            random,
+            tree,
+        }
+    }
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+
+    for count in COUNTS {
+        let boxes = Scene::new(count).boxes;
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &boxes, |b, boxes| {
+            b.iter(|| {
+                let mut tree = Bvh::new(0.25);
+
+                for (key, aabb) in boxes.iter().enumerate() {
+                    tree.insert(key, *aabb);
+                }
+
+                tree
+            })
+        });
+    }
+}
+
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+
+    for count in COUNTS {
+        let mut scene = Scene::new(count);
+
+        // Most objects only drift, and a few jump across the scene.
+        let moved: Vec<_> = scene
+            .boxes
+            .iter()
+            .enumerate()
+            .map(|(index, aabb)| {
+                let offset = if index % 10 == 0 {
+                    random_point(&mut scene.random, scene.extent)
+                } else {
+                    random_point(&mut scene.random, 0.1)
+                };
+
+                Aabb {
+                    max: aabb.max + offset,
+                    min: aabb.min + offset,
+                }
+            })
+            .collect();
+
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter_batched_ref(
+                || Scene::new(count).tree,
+                |tree| {
+                    for (key, aabb) in moved.iter().enumerate() {
+                        tree.update(key, *aabb);
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+fn remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove half");
+
+    for count in COUNTS {
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter_batched_ref(
+                || Scene::new(count).tree,
+                |tree| {
+                    for key in (0..count).step_by(2) {
+                        tree.remove(key);
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+fn queries(c: &mut Criterion) {
+    for count in COUNTS {
+        let mut scene = Scene::new(count);
+        let extent = scene.extent;
+        let mut group = c.benchmark_group(format!("query {count}"));
+
+        let query = random_box(&mut scene.random, extent);
+        group.bench_function("aabb", |b| {
+            b.iter(|| {
+                let mut found = 0;
+
+                scene.tree.query_aabb(&query, |_| found += 1);
+
+                found
+            })
+        });
+        group.bench_function("aabb brute force", |b| {
+            b.iter(|| {
+                scene
+                    .boxes
+                    .iter()
+                    .filter(|aabb| aabb.intersects(&query))
+                    .count()
+            })
+        });
+
+        let center = random_point(&mut scene.random, extent);
+        group.bench_function("sphere", |b| {
+            b.iter(|| {
+                let mut found = 0;
+
+                scene.tree.query_sphere(center, 4.0, |_| found += 1);
+
+                found
+            })
+        });
+
+        let origin = random_point(&mut scene.random, extent);
+        let direction = random_point(&mut scene.random, 1.0).normalize_or_zero();
+        group.bench_function("ray", |b| {
+            b.iter(|| {
+                let mut found = 0;
+
+                scene
+                    .tree
+                    .query_ray(origin, direction, extent, |_, _| found += 1);
+
+                found
+            })
+        });
+
+        let view = glam::Mat4::look_to_lh(
+            random_point(&mut scene.random, extent),
+            random_point(&mut scene.random, 1.0),
+            glam::Vec3::Y,
+        );
+        let projection = glam::Mat4::perspective_lh(1.1, 16.0 / 9.0, 0.1, extent / 2.0);
+        let planes = weng_game::culling::frustum_planes(projection * view);
+        group.bench_function("frustum", |b| {
+            b.iter(|| {
+                let mut found = 0;
+
+                scene.tree.query_frustum(&planes, |_| found += 1);
+
+                found
+            })
+        });
+    }
+}
+
+fn random_box(random: &mut impl Rng, extent: f32) -> Aabb {
+    let center = random_point(random, extent);
+    let half = glam::Vec3::new(
+        random.gen_range(0.1..1.0),
+        random.gen_range(0.1..1.0),
+        random.gen_range(0.1..1.0),
+    );
+
+    Aabb {
+        max: center + half,
+        min: center - half,
+    }
+}
+
+fn random_point(random: &mut impl Rng, extent: f32) -> glam::Vec3 {
+    glam::Vec3::new(
+        random.gen_range(-extent..extent),
+        random.gen_range(-extent..extent),
+        random.gen_range(-extent..extent),
+    )
+}
+
+criterion_group!(benches, insert, update, remove, queries);
+criterion_main!(benches);
@@ -0,0 +1,176 @@
+use super::Backend;
+use crate::{
+    data::{
+        self,
+        materials::Properties,
+        models::Vertex,
+        textures::{Image, Sampling, Texture},
+    },
+    renderer,
+};
+use std::path::Path;
+
+pub struct Buffers {
+    /// The center in `xyz` and the radius in `w`, in model space.
+    pub bounding_sphere: glam::Vec4,
+    pub index_buffer: weng::wgpu::Buffer,
+    pub index_count: u32,
+    /// The triangles, kept on the CPU for raycasts.
+    pub indices: Vec<u32>,
+    pub positions: Vec<glam::Vec3>,
+    pub vertex_buffer: weng::wgpu::Buffer,
+    /// Kept for the gizmos to draw their normals and tangents.
+    #[cfg(feature = "gizmos")]
+    pub vertices: Vec<Vertex>,
+}
+
+impl Buffers {
+    /// The corners of every triangle, in model space.
+    pub fn triangles(&self) -> impl Iterator<Item = [glam::Vec3; 3]> + '_ {
+        self.indices.chunks_exact(3).map(|triangle| {
+            [
+                self.positions[triangle[0] as usize],
+                self.positions[triangle[1] as usize],
+                self.positions[triangle[2] as usize],
+            ]
+        })
+    }
+
+    /// Empty unless the gizmos are compiled in.
+    pub fn vertices(&self) -> &[Vertex] {
+        #[cfg(feature = "gizmos")]
+        return &self.vertices;
+        #[cfg(not(feature = "gizmos"))]
+        return &[];
+    }
+}
+
+impl Backend for weng::graphics::Context {
+    type Material = weng::wgpu::BindGroup;
+    type MaterialLayout = weng::wgpu::BindGroupLayout;
+    type Mesh = Buffers;
+    type Shader = weng::wgpu::ShaderModule;
+    type Texture = Texture;
+
+    fn create_material(
+        &self,
+        layout: &Self::MaterialLayout,
+        textures: [&Self::Texture; 5],
+        properties: &Properties,
+    ) -> Self::Material {
+        // The bind group keeps the buffer alive.
+        let uniform = renderer::create_buffer(
+            self,
+            &[properties.uniform()],
+            weng::wgpu::BufferUsages::UNIFORM,
+        );
+        let [diffuse, normal, emissive, orm, height] = textures;
+
+        self.device()
+            .create_bind_group(&weng::wgpu::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &[
+                    weng::wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: weng::wgpu::BindingResource::TextureView(&diffuse.view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: weng::wgpu::BindingResource::Sampler(&diffuse.sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: weng::wgpu::BindingResource::TextureView(&normal.view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: weng::wgpu::BindingResource::Sampler(&normal.sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: uniform.as_entire_binding(),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: weng::wgpu::BindingResource::TextureView(&emissive.view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: weng::wgpu::BindingResource::Sampler(&emissive.sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 7,
+                        resource: weng::wgpu::BindingResource::TextureView(&orm.view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 8,
+                        resource: weng::wgpu::BindingResource::Sampler(&orm.sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 9,
+                        resource: weng::wgpu::BindingResource::TextureView(&height.view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 10,
+                        resource: weng::wgpu::BindingResource::Sampler(&height.sampler),
+                    },
+                ],
+            })
+    }
+
+    fn create_mesh(&self, vertices: &[Vertex], indices: &[u32]) -> Self::Mesh {
+        Buffers {
+            bounding_sphere: bounding_sphere(vertices),
+            index_buffer: renderer::create_buffer(self, indices, weng::wgpu::BufferUsages::INDEX),
+            index_count: indices.len() as u32,
+            indices: indices.to_vec(),
+            positions: vertices
+                .iter()
+                .map(|vertex| glam::Vec3::from_array(vertex.position))
+                .collect(),
+            vertex_buffer: renderer::create_buffer(
+                self,
+                vertices,
+                weng::wgpu::BufferUsages::VERTEX,
+            ),
+            #[cfg(feature = "gizmos")]
+            vertices: vertices.to_vec(),
+        }
+    }
+
+    fn create_shader(&self, path: &Path) -> anyhow::Result<Self::Shader> {
+        data::shaders::validate(path, self.device().features())?;
+
+        Ok(renderer::validated(self.device(), || {
+            self.load_shader(path)
+        })??)
+    }
+
+    fn create_texture(
+        &self,
+        image: &Image,
+        format: weng::wgpu::TextureFormat,
+        sampling: Sampling,
+    ) -> anyhow::Result<Self::Texture> {
+        data::textures::create(self, image, format, sampling)
+    }
+}
+
+/// Centered on the bounding box, which is tight enough for culling.
+fn bounding_sphere(vertices: &[Vertex]) -> glam::Vec4 {
+    let positions = vertices
+        .iter()
+        .map(|vertex| glam::Vec3::from_array(vertex.position));
+
+    let (min, max) = positions.clone().fold(
+        (glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN)),
+        |(min, max), position| (min.min(position), max.max(position)),
+    );
+    let center = (min + max) / 2.0;
+    let radius = positions
+        .map(|position| position.distance(center))
+        .fold(0.0, f32::max);
+
+    center.extend(radius)
+}
@@ -0,0 +1,192 @@
+use super::Dependents;
+use crate::data::{
+    self,
+    materials::Maps,
+    models::ModelData,
+    textures::{Image, Source},
+};
+use anyhow::Context;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+
+/// A model and every texture its materials reference, decoded on the CPU.
+pub struct LoadedModel {
+    pub data: ModelData,
+    pub images: HashMap<Source, Image>,
+}
+
+impl LoadedModel {
+    pub fn decode(path: &Path) -> anyhow::Result<Self> {
+        let model = data::models::load(path)?;
+        let mut images = HashMap::new();
+
+        for info in &model.materials {
+            for source in Maps::from_mtl(info).sources() {
+                if let Entry::Vacant(entry) = images.entry(source.clone()) {
+                    let image = source
+                        .decode()
+                        .with_context(|| format!("failed to decode {source}"))?;
+
+                    entry.insert(image);
+                }
+            }
+        }
+
+        Ok(Self {
+            data: model,
+            images,
+        })
+    }
+}
+
+pub enum Loaded {
+    /// A model requested with [`Loader::load_model`], or why it could not be
+    /// decoded, so that whoever asked for it can stop waiting.
+    Model {
+        model: anyhow::Result<LoadedModel>,
+        path: PathBuf,
+    },
+    /// A model decoded again because a file it was built from changed.
+    ReloadedModel { model: LoadedModel, path: PathBuf },
+    /// A texture decoded again because a file it was built from changed.
+    ReloadedTexture { image: Image, source: Source },
+}
+
+enum Request {
+    Model(PathBuf),
+    ReloadedModel(PathBuf),
+    ReloadedTexture(Source),
+}
+
+fn decode(request: Request) -> anyhow::Result<Loaded> {
+    match request {
+        Request::Model(path) => Ok(Loaded::Model {
+            model: LoadedModel::decode(&path),
+            path,
+        }),
+        Request::ReloadedModel(path) => LoadedModel::decode(&path)
+            .with_context(|| format!("failed to reload {}", path.display()))
+            .map(|model| Loaded::ReloadedModel { model, path }),
+        Request::ReloadedTexture(source) => source
+            .decode()
+            .with_context(|| format!("failed to reload {source}"))
+            .map(|image| Loaded::ReloadedTexture { image, source }),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub finished: usize,
+    pub requested: usize,
+}
+
+impl Progress {
+    pub fn done(&self) -> bool {
+        self.finished == self.requested
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.requested == 0 {
+            1.0
+        } else {
+            self.finished as f32 / self.requested as f32
+        }
+    }
+}
+
+/// Decodes models and their textures on worker threads. The results only contain CPU-side data and
+/// are handed to [`super::Assets`] on the main thread for uploading.
+pub struct Loader {
+    progress: Progress,
+    requests: Option<crossbeam_channel::Sender<Request>>,
+    results: crossbeam_channel::Receiver<anyhow::Result<Loaded>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Loader {
+    pub fn load_model(&mut self, path: impl Into<PathBuf>) {
+        self.request(Request::Model(path.into()));
+    }
+
+    pub fn new() -> Self {
+        let worker_count = std::thread::available_parallelism().map_or(1, |count| count.get());
+
+        let (request_sender, request_receiver) = crossbeam_channel::unbounded::<Request>();
+        let (result_sender, result_receiver) = crossbeam_channel::unbounded();
+
+        let workers = (0..worker_count)
+            .map(|_| {
+                let requests = request_receiver.clone();
+                let results = result_sender.clone();
+
+                std::thread::spawn(move || {
+                    for request in requests {
+                        if results.send(decode(request)).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            progress: Progress {
+                finished: 0,
+                requested: 0,
+            },
+            requests: Some(request_sender),
+            results: result_receiver,
+            workers,
+        }
+    }
+
+    /// Returns everything that finished decoding since the last call without
+    /// blocking.
+    pub fn poll(&mut self) -> Vec<anyhow::Result<Loaded>> {
+        let results = self.results.try_iter().collect::<Vec<_>>();
+
+        self.progress.finished += results.len();
+
+        results
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    /// Decodes the models and textures that [`super::Assets::dependents`]
+    /// found for a changed file, to be swapped in with
+    /// [`super::Assets::replace_model`] and [`super::Assets::replace_texture`].
+    pub fn reload(&mut self, dependents: Dependents) {
+        for path in dependents.models {
+            self.request(Request::ReloadedModel(path));
+        }
+        for source in dependents.textures {
+            self.request(Request::ReloadedTexture(source));
+        }
+    }
+
+    fn request(&mut self, request: Request) {
+        self.progress.requested += 1;
+
+        self.requests
+            .as_ref()
+            .unwrap()
+            .send(request)
+            .expect("asset loader workers exited");
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        // Closing the channel lets the workers run out of requests and exit.
+        self.requests = None;
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
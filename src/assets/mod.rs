@@ -0,0 +1,918 @@
+mod gpu;
+mod loader;
+mod storage;
+
+pub use loader::{Loaded, LoadedModel, Loader, Progress};
+pub use storage::{Handle, Storage};
+
+use crate::data::{
+    self,
+    materials::{Maps, Properties},
+    models::Vertex,
+    textures::{Image, Sampling, Source, Type},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Creates the GPU side of assets. Everything that touches files goes through
+/// [`Assets`], so a backend that only records calls is enough to exercise
+/// deduplication and reference counting.
+pub trait Backend {
+    type Material;
+    type MaterialLayout;
+    type Mesh;
+    type Shader;
+    type Texture;
+
+    /// `textures` are in the order of [`Maps::sources`].
+    fn create_material(
+        &self,
+        layout: &Self::MaterialLayout,
+        textures: [&Self::Texture; 5],
+        properties: &Properties,
+    ) -> Self::Material;
+    fn create_mesh(&self, vertices: &[Vertex], indices: &[u32]) -> Self::Mesh;
+    fn create_shader(&self, path: &Path) -> anyhow::Result<Self::Shader>;
+    fn create_texture(
+        &self,
+        image: &Image,
+        format: weng::wgpu::TextureFormat,
+        sampling: Sampling,
+    ) -> anyhow::Result<Self::Texture>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureKey {
+    pub format: weng::wgpu::TextureFormat,
+    pub sampling: Sampling,
+    pub source: Source,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialKey {
+    pub maps: Maps,
+    pub properties: Properties,
+}
+
+pub struct Material<B: Backend> {
+    pub bind_group: B::Material,
+    pub diffuse: Handle<B::Texture>,
+    pub emissive: Handle<B::Texture>,
+    pub height: Handle<B::Texture>,
+    pub normal: Handle<B::Texture>,
+    pub orm: Handle<B::Texture>,
+    pub properties: Properties,
+}
+
+impl<B: Backend> Material<B> {
+    /// In the order of [`Maps::sources`].
+    pub fn textures(&self) -> [Handle<B::Texture>; 5] {
+        [
+            self.diffuse,
+            self.normal,
+            self.emissive,
+            self.orm,
+            self.height,
+        ]
+    }
+}
+
+pub struct Mesh<B: Backend> {
+    pub buffers: B::Mesh,
+    pub material: Handle<Material<B>>,
+}
+
+pub struct Model<B: Backend> {
+    pub materials: Vec<Handle<Material<B>>>,
+    pub meshes: Vec<Mesh<B>>,
+    /// The obj and mtl files the model was built from.
+    pub sources: Vec<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryReport {
+    pub models: usize,
+    pub shaders: usize,
+    pub textures: usize,
+}
+
+impl MemoryReport {
+    pub fn total(&self) -> usize {
+        self.models + self.shaders + self.textures
+    }
+}
+
+/// The loaded assets built from a file, which have to be decoded again when
+/// it changes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dependents {
+    /// Obj files of the models that use the file as their obj or mtl.
+    pub models: Vec<PathBuf>,
+    /// The loaded textures built from the file.
+    pub textures: Vec<Source>,
+}
+
+type LoadTexture<B> = fn(
+    &mut Assets<B>,
+    &B,
+    &Source,
+    &HashMap<Source, Image>,
+) -> anyhow::Result<Handle<<B as Backend>::Texture>>;
+
+pub struct Assets<B: Backend> {
+    material_layout: B::MaterialLayout,
+    materials: Storage<MaterialKey, Material<B>>,
+    models: Storage<PathBuf, Model<B>>,
+    shaders: Storage<PathBuf, B::Shader>,
+    textures: Storage<TextureKey, B::Texture>,
+}
+
+impl<B: Backend> Assets<B> {
+    /// Every file on disk that a loaded asset was built from.
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.models
+            .iter()
+            .flat_map(|(_, _, model)| model.sources.iter().cloned())
+            .chain(self.shaders.iter().map(|(_, path, _)| path.clone()))
+            .chain(
+                self.textures
+                    .iter()
+                    .flat_map(|(_, key, _)| key.source.files().into_iter().map(Path::to_path_buf)),
+            )
+            .collect()
+    }
+
+    /// What has to be decoded again with [`Loader::reload`] after `path`
+    /// changed.
+    pub fn dependents(&self, path: &Path) -> Dependents {
+        Dependents {
+            models: self
+                .models
+                .iter()
+                .filter(|(_, _, model)| model.sources.iter().any(|source| source == path))
+                .map(|(_, key, _)| key.clone())
+                .collect(),
+            textures: self
+                .textures
+                .iter()
+                .filter(|(_, key, _)| key.source.files().contains(&path))
+                .map(|(_, key, _)| key.source.clone())
+                .collect(),
+        }
+    }
+
+    /// Uploads a model decoded by the [`Loader`].
+    pub fn insert_model(
+        &mut self,
+        backend: &B,
+        path: &Path,
+        model: LoadedModel,
+    ) -> anyhow::Result<Handle<Model<B>>> {
+        let key = path.to_path_buf();
+
+        if let Some(handle) = self.models.acquire(&key) {
+            return Ok(handle);
+        }
+
+        let (model, bytes) = self.create_model(backend, model)?;
+
+        Ok(self.models.insert(key, model, bytes))
+    }
+
+    pub fn load_shader(&mut self, backend: &B, path: &Path) -> anyhow::Result<Handle<B::Shader>> {
+        let key = path.to_path_buf();
+
+        if let Some(handle) = self.shaders.acquire(&key) {
+            return Ok(handle);
+        }
+
+        let shader = backend.create_shader(path)?;
+        let bytes = std::fs::metadata(path)?.len() as usize;
+
+        Ok(self.shaders.insert(key, shader, bytes))
+    }
+
+    pub fn material(&self, handle: Handle<Material<B>>) -> &Material<B> {
+        self.materials.get(handle).expect("stale material handle")
+    }
+
+    pub fn material_layout(&self) -> &B::MaterialLayout {
+        &self.material_layout
+    }
+
+    pub fn memory(&self) -> MemoryReport {
+        MemoryReport {
+            models: self.models.bytes(),
+            shaders: self.shaders.bytes(),
+            textures: self.textures.bytes(),
+        }
+    }
+
+    pub fn model(&self, handle: Handle<Model<B>>) -> &Model<B> {
+        self.models.get(handle).expect("stale model handle")
+    }
+
+    pub fn new(material_layout: B::MaterialLayout) -> Self {
+        Self {
+            material_layout,
+            materials: Storage::new(),
+            models: Storage::new(),
+            shaders: Storage::new(),
+            textures: Storage::new(),
+        }
+    }
+
+    pub fn release_model(&mut self, handle: Handle<Model<B>>) {
+        self.models.release(handle);
+    }
+
+    /// Recreates the shader loaded from `path`, if there is one. On failure
+    /// the old shader is kept.
+    pub fn reload_shader(
+        &mut self,
+        backend: &B,
+        path: &Path,
+    ) -> anyhow::Result<Option<Handle<B::Shader>>> {
+        let handle = match self.shaders.handles_where(|key, _| key == path).first() {
+            Some(&handle) => handle,
+            None => return Ok(None),
+        };
+        let shader = backend.create_shader(path)?;
+        let bytes = std::fs::metadata(path)?.len() as usize;
+
+        self.shaders.replace(handle, shader, bytes);
+
+        Ok(Some(handle))
+    }
+
+    /// Rebuilds the model loaded from `path` in place, so its handle stays
+    /// valid, and returns it. Assets only the old model used are left for
+    /// [`Assets::unload_unused`]. On failure the old model is kept.
+    pub fn replace_model(
+        &mut self,
+        backend: &B,
+        path: &Path,
+        model: LoadedModel,
+    ) -> anyhow::Result<Option<Handle<Model<B>>>> {
+        let handle = match self.models.handles_where(|key, _| key == path).first() {
+            Some(&handle) => handle,
+            None => return Ok(None),
+        };
+        let (model, bytes) = self.create_model(backend, model)?;
+
+        if let Some(old) = self.models.replace(handle, model, bytes) {
+            self.release_model_dependencies(old);
+        }
+
+        Ok(Some(handle))
+    }
+
+    /// Recreates every texture built from `source` in place, and the
+    /// materials that use them, and returns the textures. On failure the old
+    /// textures are kept.
+    pub fn replace_texture(
+        &mut self,
+        backend: &B,
+        source: &Source,
+        image: &Image,
+    ) -> anyhow::Result<Vec<Handle<B::Texture>>> {
+        let textures = self.textures.handles_where(|key, _| key.source == *source);
+        let mut created = Vec::with_capacity(textures.len());
+
+        for &handle in &textures {
+            let key = self.textures.key(handle).unwrap();
+
+            created.push((
+                backend.create_texture(image, key.format, key.sampling)?,
+                image.bytes(key.format),
+            ));
+        }
+        for (&handle, (texture, bytes)) in textures.iter().zip(created) {
+            self.textures.replace(handle, texture, bytes);
+        }
+
+        for handle in self.materials.handles_where(|_, material| {
+            material
+                .textures()
+                .iter()
+                .any(|texture| textures.contains(texture))
+        }) {
+            let material = self.materials.get(handle).unwrap();
+            let bind_group =
+                self.create_bind_group(backend, material.textures(), &material.properties);
+
+            self.materials.get_mut(handle).unwrap().bind_group = bind_group;
+        }
+
+        Ok(textures)
+    }
+
+    pub fn shader(&self, handle: Handle<B::Shader>) -> &B::Shader {
+        self.shaders.get(handle).expect("stale shader handle")
+    }
+
+    /// Frees every asset that is no longer referenced, including assets that
+    /// were only kept alive by the ones being freed.
+    pub fn unload_unused(&mut self) {
+        for model in self.models.remove_unused() {
+            self.release_model_dependencies(model);
+        }
+        for material in self.materials.remove_unused() {
+            for texture in material.textures() {
+                self.textures.release(texture);
+            }
+        }
+        self.shaders.remove_unused();
+        self.textures.remove_unused();
+    }
+
+    fn create_model(
+        &mut self,
+        backend: &B,
+        model: LoadedModel,
+    ) -> anyhow::Result<(Model<B>, usize)> {
+        let LoadedModel {
+            data: model_data,
+            images,
+        } = model;
+
+        // Meshes without a material of their own, as in an obj without an
+        // mtl, share a default one after the others.
+        let default = tobj::Material::default();
+        let missing = model_data
+            .meshes
+            .iter()
+            .any(|mesh| mesh.material_indice >= model_data.materials.len());
+        let mut materials = Vec::with_capacity(model_data.materials.len() + 1);
+
+        for info in model_data
+            .materials
+            .iter()
+            .chain(missing.then_some(&default))
+        {
+            match self.load_material(backend, info, &images) {
+                Ok(handle) => materials.push(handle),
+                Err(e) => {
+                    for handle in materials {
+                        self.materials.release(handle);
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        let bytes = model_data.meshes.iter().map(|mesh| mesh.bytes()).sum();
+
+        let meshes = model_data
+            .meshes
+            .iter()
+            .map(|mesh| Mesh {
+                buffers: backend.create_mesh(&mesh.vertices, &mesh.indices),
+                material: materials[mesh.material_indice.min(materials.len() - 1)],
+            })
+            .collect();
+
+        Ok((
+            Model {
+                materials,
+                meshes,
+                sources: model_data.sources,
+            },
+            bytes,
+        ))
+    }
+
+    fn create_bind_group(
+        &self,
+        backend: &B,
+        textures: [Handle<B::Texture>; 5],
+        properties: &Properties,
+    ) -> B::Material {
+        backend.create_material(
+            &self.material_layout,
+            textures.map(|texture| self.textures.get(texture).unwrap()),
+            properties,
+        )
+    }
+
+    fn load_material(
+        &mut self,
+        backend: &B,
+        info: &tobj::Material,
+        images: &HashMap<Source, Image>,
+    ) -> anyhow::Result<Handle<Material<B>>> {
+        let key = MaterialKey {
+            maps: Maps::from_mtl(info),
+            properties: Properties::from_mtl(info),
+        };
+
+        if let Some(handle) = self.materials.acquire(&key) {
+            return Ok(handle);
+        }
+
+        let loads: [LoadTexture<B>; 5] = [
+            Self::load_texture_with::<data::textures::Diffuse>,
+            Self::load_texture_with::<data::textures::Normal>,
+            Self::load_texture_with::<data::textures::Emissive>,
+            Self::load_texture_with::<data::textures::Orm>,
+            Self::load_texture_with::<data::textures::Height>,
+        ];
+        let mut textures = Vec::with_capacity(loads.len());
+
+        for (load, source) in loads.into_iter().zip(key.maps.sources()) {
+            match load(self, backend, source, images) {
+                Ok(texture) => textures.push(texture),
+                Err(e) => {
+                    for texture in textures {
+                        self.textures.release(texture);
+                    }
+
+                    return Err(e);
+                }
+            }
+        }
+
+        let [diffuse, normal, emissive, orm, height] = [0, 1, 2, 3, 4].map(|i| textures[i]);
+        let bind_group = self.create_bind_group(
+            backend,
+            [diffuse, normal, emissive, orm, height],
+            &key.properties,
+        );
+        let properties = key.properties;
+
+        Ok(self.materials.insert(
+            key,
+            Material {
+                bind_group,
+                diffuse,
+                emissive,
+                height,
+                normal,
+                orm,
+                properties,
+            },
+            0,
+        ))
+    }
+
+    /// Uses the already decoded image from `images` if there is one.
+    fn load_texture_with<T: Type>(
+        &mut self,
+        backend: &B,
+        source: &Source,
+        images: &HashMap<Source, Image>,
+    ) -> anyhow::Result<Handle<B::Texture>> {
+        let key = TextureKey {
+            format: T::FORMAT,
+            sampling: T::SAMPLING,
+            source: source.clone(),
+        };
+
+        if let Some(handle) = self.textures.acquire(&key) {
+            return Ok(handle);
+        }
+
+        match images.get(source) {
+            Some(image) => self.upload_texture(backend, key, image),
+            None => self.upload_texture(backend, key, &source.decode()?),
+        }
+    }
+
+    fn release_model_dependencies(&mut self, model: Model<B>) {
+        for material in model.materials {
+            self.materials.release(material);
+        }
+    }
+
+    fn upload_texture(
+        &mut self,
+        backend: &B,
+        key: TextureKey,
+        image: &Image,
+    ) -> anyhow::Result<Handle<B::Texture>> {
+        let texture = backend.create_texture(image, key.format, key.sampling)?;
+        let bytes = image.bytes(key.format);
+
+        Ok(self.textures.insert(key, texture, bytes))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Numbers everything it creates, counting up from one per kind, so that
+    /// tests can tell how many times each kind of asset was built.
+    #[derive(Default)]
+    pub(crate) struct Recorder {
+        materials: Cell<usize>,
+        meshes: Cell<usize>,
+        shaders: Cell<usize>,
+        textures: Cell<usize>,
+    }
+
+    fn next(counter: &Cell<usize>) -> usize {
+        counter.set(counter.get() + 1);
+        counter.get()
+    }
+
+    impl Backend for Recorder {
+        type Material = usize;
+        type MaterialLayout = ();
+        type Mesh = usize;
+        type Shader = usize;
+        type Texture = usize;
+
+        fn create_material(&self, _: &(), _: [&usize; 5], _: &Properties) -> usize {
+            next(&self.materials)
+        }
+
+        fn create_mesh(&self, _: &[Vertex], _: &[u32]) -> usize {
+            next(&self.meshes)
+        }
+
+        fn create_shader(&self, _: &Path) -> anyhow::Result<usize> {
+            Ok(next(&self.shaders))
+        }
+
+        fn create_texture(
+            &self,
+            _: &Image,
+            _: weng::wgpu::TextureFormat,
+            _: Sampling,
+        ) -> anyhow::Result<usize> {
+            Ok(next(&self.textures))
+        }
+    }
+
+    /// Two one triangle models sharing a material with a diffuse, normal,
+    /// roughness and height map, in a directory of their own that is removed on drop.
+    pub(crate) struct Files {
+        dir: PathBuf,
+    }
+
+    impl Files {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("weng-assets-{name}-{}", std::process::id()));
+            let files = Self { dir };
+
+            std::fs::create_dir_all(&files.dir).unwrap();
+            files.write_texture("diffuse.png", [255, 255, 255, 255]);
+            files.write_texture("normal.png", [128, 128, 255, 255]);
+            files.write_texture("roughness.png", [64, 64, 64, 255]);
+            files.write_texture("height.png", [192, 192, 192, 255]);
+            std::fs::write(
+                files.path("material.mtl"),
+                format!(
+                    "newmtl material\nd 1.0\nmap_Kd {}\nmap_Bump {}\nmap_Pr {}\ndisp {}\n",
+                    files.path("diffuse.png").display(),
+                    files.path("normal.png").display(),
+                    files.path("roughness.png").display(),
+                    files.path("height.png").display(),
+                ),
+            )
+            .unwrap();
+            for model in ["first.obj", "second.obj"] {
+                std::fs::write(
+                    files.path(model),
+                    format!(
+                        "mtllib {}\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n\
+                         usemtl material\nf 1/1/1 2/2/1 3/3/1\n",
+                        files.path("material.mtl").display(),
+                    ),
+                )
+                .unwrap();
+            }
+
+            files
+        }
+
+        pub(crate) fn path(&self, name: &str) -> PathBuf {
+            self.dir.join(name)
+        }
+
+        fn write_texture(&self, name: &str, pixel: [u8; 4]) {
+            image::RgbaImage::from_pixel(2, 2, image::Rgba(pixel))
+                .save(self.path(name))
+                .unwrap();
+        }
+    }
+
+    impl Drop for Files {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Decodes and uploads `path` on this thread, as the [`Loader`] and
+    /// [`Assets::insert_model`] do between them.
+    pub(crate) fn load_model<B: Backend>(
+        assets: &mut Assets<B>,
+        backend: &B,
+        path: &Path,
+    ) -> anyhow::Result<Handle<Model<B>>> {
+        assets.insert_model(backend, path, LoadedModel::decode(path)?)
+    }
+
+    fn load_texture<T: Type, B: Backend>(
+        assets: &mut Assets<B>,
+        backend: &B,
+        path: &Path,
+    ) -> anyhow::Result<Handle<B::Texture>> {
+        assets.load_texture_with::<T>(backend, &Source::File(path.to_path_buf()), &HashMap::new())
+    }
+
+    #[test]
+    fn loading_twice_shares_assets() {
+        let files = Files::new("dedup");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        let first = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let again = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let second = load_model(&mut assets, &backend, &files.path("second.obj")).unwrap();
+
+        assert_eq!(first, again);
+        assert_ne!(first, second);
+        assert_eq!(assets.models.references(first), 2);
+        assert_eq!(backend.meshes.get(), 2);
+        // Both models use the same material, and so the same textures: the
+        // diffuse, normal and height maps, the default emissive color and the
+        // packed roughness map.
+        assert_eq!(backend.materials.get(), 1);
+        assert_eq!(backend.textures.get(), 5);
+        assert_eq!(
+            assets.model(first).materials,
+            assets.model(second).materials
+        );
+        assert_eq!(
+            assets.dependents(&files.path("height.png")).textures,
+            [Source::File(files.path("height.png"))]
+        );
+        assert_eq!(
+            load_texture::<data::textures::Diffuse, _>(
+                &mut assets,
+                &backend,
+                &files.path("diffuse.png")
+            )
+            .unwrap(),
+            assets.material(assets.model(first).materials[0]).diffuse,
+        );
+        assert_eq!(backend.textures.get(), 5);
+    }
+
+    #[test]
+    fn released_assets_stay_until_unloaded() {
+        let files = Files::new("release");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        let model = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let material = assets.model(model).materials[0];
+
+        assets.release_model(model);
+        assets.unload_unused();
+        assert_eq!(assets.models.references(model), 1);
+
+        assets.release_model(model);
+        assert!(assets.models.get(model).is_some());
+
+        assets.unload_unused();
+        assert!(assets.models.get(model).is_none());
+        assert!(assets.materials.get(material).is_none());
+        assert!(assets.dependencies().is_empty());
+        assert_eq!(assets.memory().total(), 0);
+
+        // A new load gets a new handle rather than the stale one.
+        let reloaded = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+
+        assert_ne!(reloaded, model);
+        assert!(assets.models.get(model).is_none());
+        assert_eq!(backend.meshes.get(), 2);
+    }
+
+    #[test]
+    fn unload_unused_keeps_referenced_dependencies() {
+        let files = Files::new("unload");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        let first = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let second = load_model(&mut assets, &backend, &files.path("second.obj")).unwrap();
+        let material = assets.model(first).materials[0];
+        let Material {
+            diffuse, normal, ..
+        } = *assets.material(material);
+        let texture = load_texture::<data::textures::Normal, _>(
+            &mut assets,
+            &backend,
+            &files.path("normal.png"),
+        )
+        .unwrap();
+
+        assets.release_model(first);
+        assets.unload_unused();
+        assert!(assets.models.get(first).is_none());
+        assert!(assets.models.get(second).is_some());
+        assert_eq!(assets.materials.references(material), 1);
+
+        assets.release_model(second);
+        assets.unload_unused();
+        assert!(assets.materials.get(material).is_none());
+        assert!(assets.textures.get(diffuse).is_none());
+        // Still loaded on its own account.
+        assert_eq!(texture, normal);
+        assert_eq!(assets.textures.references(normal), 1);
+
+        assets.textures.release(texture);
+        assets.unload_unused();
+        assert!(assets.textures.get(normal).is_none());
+    }
+
+    #[test]
+    fn replacing_a_texture_rebuilds_its_materials() {
+        let files = Files::new("reload");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        let first = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let second = load_model(&mut assets, &backend, &files.path("second.obj")).unwrap();
+        let material = assets.model(first).materials[0];
+        let diffuse = assets.material(material).diffuse;
+        let bind_group = assets.material(material).bind_group;
+
+        assert_eq!(
+            assets.dependents(&files.path("unrelated.png")),
+            Dependents::default()
+        );
+        assert_eq!(
+            assets.dependents(&files.path("diffuse.png")),
+            Dependents {
+                models: Vec::new(),
+                textures: vec![Source::File(files.path("diffuse.png"))],
+            }
+        );
+
+        files.write_texture("diffuse.png", [255, 0, 0, 255]);
+        let image = data::textures::decode(&files.path("diffuse.png")).unwrap();
+        let reloaded = assets
+            .replace_texture(&backend, &Source::File(files.path("diffuse.png")), &image)
+            .unwrap();
+
+        assert_eq!(reloaded, [diffuse]);
+        assert_eq!(backend.textures.get(), 6);
+        assert_eq!(assets.textures.get(diffuse), Some(&6));
+        // The material is rebuilt in place, so the models keep their handle to
+        // it and need nothing rebuilt themselves.
+        assert_ne!(assets.material(material).bind_group, bind_group);
+        assert_eq!(assets.model(first).materials[0], material);
+        assert_eq!(assets.model(second).materials[0], material);
+        assert_eq!(backend.meshes.get(), 2);
+    }
+
+    #[test]
+    fn an_mtl_change_rebuilds_its_models() {
+        let files = Files::new("mtl");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        let model = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let dependents = assets.dependents(&files.path("material.mtl"));
+
+        assert_eq!(dependents.models, [files.path("first.obj")]);
+        assert!(dependents.textures.is_empty());
+
+        let reloaded = assets
+            .replace_model(
+                &backend,
+                &dependents.models[0],
+                LoadedModel::decode(&dependents.models[0]).unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(reloaded, Some(model));
+        assert_eq!(backend.meshes.get(), 2);
+        // The rebuilt model takes the existing material again and the old one
+        // lets go of it, so nothing is duplicated or leaked.
+        assert_eq!(backend.materials.get(), 1);
+        assert_eq!(
+            assets
+                .materials
+                .references(assets.model(model).materials[0]),
+            1
+        );
+    }
+
+    #[test]
+    fn the_loader_decodes_what_a_change_affects() {
+        let files = Files::new("loader");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+        let mut loader = Loader::new();
+
+        let model = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+
+        loader.reload(assets.dependents(&files.path("material.mtl")));
+        loader.reload(assets.dependents(&files.path("diffuse.png")));
+        assert_eq!(loader.progress().requested, 2);
+
+        let mut results = Vec::new();
+        while !loader.progress().done() {
+            results.extend(loader.poll());
+            std::thread::yield_now();
+        }
+
+        for result in results {
+            match result.unwrap() {
+                Loaded::Model { .. } => panic!("nothing new was requested"),
+                Loaded::ReloadedModel {
+                    model: decoded,
+                    path,
+                } => assert_eq!(
+                    assets.replace_model(&backend, &path, decoded).unwrap(),
+                    Some(model)
+                ),
+                Loaded::ReloadedTexture { image, source } => assert_eq!(
+                    assets
+                        .replace_texture(&backend, &source, &image)
+                        .unwrap()
+                        .len(),
+                    1
+                ),
+            }
+        }
+        assert_eq!(backend.meshes.get(), 2);
+        assert_eq!(backend.textures.get(), 6);
+    }
+
+    #[test]
+    fn a_roughness_change_repacks_the_orm_texture() {
+        let files = Files::new("orm");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        let model = load_model(&mut assets, &backend, &files.path("first.obj")).unwrap();
+        let material = assets.model(model).materials[0];
+        let orm = assets.material(material).orm;
+        let source = Source::Orm {
+            metallic: None,
+            occlusion: None,
+            roughness: Some(files.path("roughness.png")),
+        };
+
+        assert_eq!(
+            assets.dependents(&files.path("roughness.png")),
+            Dependents {
+                models: Vec::new(),
+                textures: vec![source.clone()],
+            }
+        );
+
+        files.write_texture("roughness.png", [200, 200, 200, 255]);
+        let image = source.decode().unwrap();
+
+        match &image {
+            Image::Uncompressed(pixels) => {
+                assert_eq!(pixels.to_rgba8().get_pixel(0, 0).0, [255, 200, 0, 255])
+            }
+            Image::Compressed(_) => panic!("packed orm maps are uncompressed"),
+        }
+        assert_eq!(
+            assets.replace_texture(&backend, &source, &image).unwrap(),
+            [orm]
+        );
+        assert_eq!(backend.materials.get(), 2);
+    }
+
+    #[test]
+    fn a_model_without_an_mtl_gets_the_default_material() {
+        let files = Files::new("bare");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+
+        std::fs::write(
+            files.path("bare.obj"),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n\
+             f 1/1/1 2/2/1 3/3/1\n",
+        )
+        .unwrap();
+
+        let model = load_model(&mut assets, &backend, &files.path("bare.obj")).unwrap();
+        let material = assets.model(model).materials[0];
+
+        assert_eq!(assets.model(model).materials.len(), 1);
+        assert_eq!(assets.model(model).meshes[0].material, material);
+        // Every map falls back to a color, so only the obj is on disk.
+        assert_eq!(assets.dependencies(), [files.path("bare.obj")]);
+        assert_eq!(
+            assets.material(material).properties.alpha_mode,
+            data::materials::AlphaMode::Opaque
+        );
+    }
+}
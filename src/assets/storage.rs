@@ -0,0 +1,245 @@
+use std::{collections::HashMap, hash::Hash, marker::PhantomData};
+
+/// A typed reference to an asset. The generation makes handles to unloaded
+/// assets stale instead of silently pointing at whatever reuses the slot.
+pub struct Handle<T> {
+    generation: u32,
+    index: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle")
+            .field("generation", &self.generation)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.generation == other.generation && self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Ord for Handle<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.index, self.generation).cmp(&(other.index, other.generation))
+    }
+}
+
+impl<T> PartialOrd for Handle<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.generation.hash(state);
+        self.index.hash(state);
+    }
+}
+
+struct Entry<K, T> {
+    bytes: usize,
+    key: K,
+    references: usize,
+    value: T,
+}
+
+struct Slot<K, T> {
+    entry: Option<Entry<K, T>>,
+    generation: u32,
+}
+
+/// Reference counted assets, deduplicated by key.
+pub struct Storage<K, T> {
+    free: Vec<u32>,
+    lookup: HashMap<K, Handle<T>>,
+    slots: Vec<Slot<K, T>>,
+}
+
+impl<K: Clone + Eq + Hash, T> Storage<K, T> {
+    /// Returns the handle of an already loaded asset and takes a reference to it.
+    pub fn acquire(&mut self, key: &K) -> Option<Handle<T>> {
+        let handle = *self.lookup.get(key)?;
+
+        self.entry_mut(handle)?.references += 1;
+
+        Some(handle)
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.entries().map(|(_, entry)| entry.bytes).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &K, &T)> {
+        self.entries()
+            .map(|(handle, entry)| (handle, &entry.key, &entry.value))
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (Handle<T>, &Entry<K, T>)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.entry.as_ref().map(|entry| {
+                (
+                    Handle {
+                        generation: slot.generation,
+                        index: index as u32,
+                        _marker: PhantomData,
+                    },
+                    entry,
+                )
+            })
+        })
+    }
+
+    fn entry(&self, handle: Handle<T>) -> Option<&Entry<K, T>> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.entry.as_ref())
+    }
+
+    fn entry_mut(&mut self, handle: Handle<T>) -> Option<&mut Entry<K, T>> {
+        self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.entry.as_mut())
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.entry(handle).map(|entry| &entry.value)
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.entry_mut(handle).map(|entry| &mut entry.value)
+    }
+
+    /// Handles of every loaded asset whose key matches `predicate`.
+    pub fn handles_where(&self, mut predicate: impl FnMut(&K, &T) -> bool) -> Vec<Handle<T>> {
+        self.iter()
+            .filter(|(_, key, value)| predicate(key, value))
+            .map(|(handle, _, _)| handle)
+            .collect()
+    }
+
+    /// Stores a new asset with a single reference.
+    pub fn insert(&mut self, key: K, value: T, bytes: usize) -> Handle<T> {
+        let entry = Entry {
+            bytes,
+            key: key.clone(),
+            references: 1,
+            value,
+        };
+
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize].entry = Some(entry);
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    entry: Some(entry),
+                    generation: 0,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+
+        let handle = Handle {
+            generation: self.slots[index as usize].generation,
+            index,
+            _marker: PhantomData,
+        };
+
+        self.lookup.insert(key, handle);
+
+        handle
+    }
+
+    pub fn key(&self, handle: Handle<T>) -> Option<&K> {
+        self.entry(handle).map(|entry| &entry.key)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            free: Vec::new(),
+            lookup: HashMap::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Drops a reference. The asset stays loaded until
+    /// [`Storage::remove_unused`] is called.
+    pub fn release(&mut self, handle: Handle<T>) {
+        if let Some(entry) = self.entry_mut(handle) {
+            entry.references = entry.references.saturating_sub(1);
+        }
+    }
+
+    /// Removes every asset without references and returns them, so that the
+    /// caller can release whatever they depend on.
+    pub fn remove_unused(&mut self) -> Vec<T> {
+        let mut removed = Vec::new();
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if !matches!(&slot.entry, Some(entry) if entry.references == 0) {
+                continue;
+            }
+
+            let entry = slot.entry.take().unwrap();
+
+            self.lookup.remove(&entry.key);
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(index as u32);
+
+            removed.push(entry.value);
+        }
+
+        removed
+    }
+
+    /// Swaps the value behind a handle, keeping its key and references.
+    /// Returns the old value.
+    pub fn replace(&mut self, handle: Handle<T>, value: T, bytes: usize) -> Option<T> {
+        let entry = self.entry_mut(handle)?;
+
+        entry.bytes = bytes;
+
+        Some(std::mem::replace(&mut entry.value, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl<T> Handle<T> {
+        /// A handle no storage gave out, for code that only compares handles.
+        pub(crate) fn test(index: u32) -> Self {
+            Self {
+                generation: 0,
+                index,
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    impl<K: Clone + Eq + Hash, T> Storage<K, T> {
+        pub(crate) fn references(&self, handle: Handle<T>) -> usize {
+            self.entry(handle).map_or(0, |entry| entry.references)
+        }
+    }
+}
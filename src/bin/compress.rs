@@ -0,0 +1,350 @@
+//! Converts PNG and JPG textures into block compressed DDS or KTX2 files with a
+//! full mip chain, so that the game can upload them without decoding.
+//!
+//! Usage: `compress <input> <output.dds|output.ktx2> [bc1|bc3|bc4|bc5|bc7|astc<W>x<H>] [--srgb]`
+//!
+//! The format defaults to BC3 for images with transparency and BC1 otherwise.
+//! ASTC is only written to KTX2, since DDS has no ASTC formats. Images that
+//! are not a whole number of blocks are padded by repeating their last row and
+//! column.
+
+use anyhow::bail;
+use weng_game::codecs::{astc, bc, bc7};
+
+const USAGE: &str = "usage: compress <input> <output.dds|output.ktx2> \
+                     [bc1|bc3|bc4|bc5|bc7|astc<W>x<H>] [--srgb]";
+
+/// The ASTC block sizes in the order Vulkan numbers their formats.
+const ASTC_BLOCKS: [(u32, u32); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Astc(u32, u32),
+    Bc(bc::Format),
+    Bc7,
+}
+
+impl Format {
+    fn parse(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "bc1" => Format::Bc(bc::Format::Bc1),
+            "bc3" => Format::Bc(bc::Format::Bc3),
+            "bc4" => Format::Bc(bc::Format::Bc4),
+            "bc5" => Format::Bc(bc::Format::Bc5),
+            "bc7" => Format::Bc7,
+            _ => match name
+                .strip_prefix("astc")
+                .and_then(|size| size.split_once('x'))
+                .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            {
+                Some(size) if ASTC_BLOCKS.contains(&size) => Format::Astc(size.0, size.1),
+                _ => bail!("unknown format {name}"),
+            },
+        })
+    }
+
+    fn block_dimensions(self) -> (u32, u32) {
+        match self {
+            Format::Astc(width, height) => (width, height),
+            Format::Bc(_) | Format::Bc7 => (4, 4),
+        }
+    }
+
+    fn block_bytes(self) -> usize {
+        match self {
+            Format::Bc(format) => format.block_bytes(),
+            Format::Astc(..) | Format::Bc7 => 16,
+        }
+    }
+
+    fn compress(self, width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+        match self {
+            Format::Astc(block_width, block_height) => {
+                astc::compress(block_width, block_height, width, height, rgba)
+            }
+            Format::Bc(format) => bc::compress(format, width, height, rgba),
+            Format::Bc7 => bc7::compress(width, height, rgba),
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let (input, output) = match args.as_slice() {
+        [input, output, ..] => (input, output),
+        _ => bail!(USAGE),
+    };
+    let options = &args[2..];
+    let srgb = options.iter().any(|option| option == "--srgb");
+
+    let mut image = image::open(input)?.to_rgba8();
+
+    let format = match options.iter().find(|option| !option.starts_with("--")) {
+        Some(name) => Format::parse(name)?,
+        None if image.pixels().any(|pixel| pixel[3] < 255) => Format::Bc(bc::Format::Bc3),
+        None => Format::Bc(bc::Format::Bc1),
+    };
+
+    if srgb && matches!(format, Format::Bc(bc::Format::Bc4 | bc::Format::Bc5)) {
+        bail!("{format:?} has no sRGB variant");
+    }
+
+    // The base level of compressed textures has to consist of whole blocks.
+    let (block_width, block_height) = format.block_dimensions();
+    let width = (image.width() + block_width - 1) / block_width * block_width;
+    let height = (image.height() + block_height - 1) / block_height * block_height;
+
+    if (width, height) != image.dimensions() {
+        println!(
+            "padding {}x{} to {width}x{height}",
+            image.width(),
+            image.height()
+        );
+
+        image = image::RgbaImage::from_fn(width, height, |x, y| {
+            *image.get_pixel(x.min(image.width() - 1), y.min(image.height() - 1))
+        });
+    }
+
+    let mut levels = vec![image];
+
+    while let Some(previous) = levels
+        .last()
+        .filter(|level| level.width() > 1 || level.height() > 1)
+    {
+        let next = image::imageops::resize(
+            previous,
+            (previous.width() / 2).max(1),
+            (previous.height() / 2).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+
+        levels.push(next);
+    }
+
+    let levels = levels
+        .iter()
+        .map(|level| format.compress(level.width(), level.height(), level.as_raw()))
+        .collect::<Vec<_>>();
+
+    match std::path::Path::new(output)
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("dds") => write_dds(output, format, srgb, width, height, &levels)?,
+        Some("ktx2") => std::fs::write(output, ktx2(format, srgb, width, height, &levels))?,
+        _ => bail!("{output} is neither a .dds nor a .ktx2 file"),
+    }
+
+    println!(
+        "wrote {output}: {format:?}, {} mip levels, {} bytes",
+        levels.len(),
+        levels.iter().map(Vec::len).sum::<usize>()
+    );
+
+    Ok(())
+}
+
+fn write_dds(
+    output: &str,
+    format: Format,
+    srgb: bool,
+    width: u32,
+    height: u32,
+    levels: &[Vec<u8>],
+) -> anyhow::Result<()> {
+    use ddsfile::DxgiFormat;
+
+    let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+        height,
+        width,
+        depth: None,
+        format: match (format, srgb) {
+            (Format::Bc(bc::Format::Bc1), false) => DxgiFormat::BC1_UNorm,
+            (Format::Bc(bc::Format::Bc1), true) => DxgiFormat::BC1_UNorm_sRGB,
+            (Format::Bc(bc::Format::Bc3), false) => DxgiFormat::BC3_UNorm,
+            (Format::Bc(bc::Format::Bc3), true) => DxgiFormat::BC3_UNorm_sRGB,
+            (Format::Bc(bc::Format::Bc4), _) => DxgiFormat::BC4_UNorm,
+            (Format::Bc(bc::Format::Bc5), _) => DxgiFormat::BC5_UNorm,
+            (Format::Bc7, false) => DxgiFormat::BC7_UNorm,
+            (Format::Bc7, true) => DxgiFormat::BC7_UNorm_sRGB,
+            (Format::Astc(..), _) => bail!("DDS files cannot hold ASTC, write a .ktx2 file"),
+        },
+        mipmap_levels: Some(levels.len() as u32),
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+        alpha_mode: ddsfile::AlphaMode::Unknown,
+    })?;
+
+    let data = levels.concat();
+    let dds_data = dds.get_mut_data(0)?;
+
+    if dds_data.len() < data.len() {
+        bail!(
+            "dds layout holds {} bytes, compressed data is {} bytes",
+            dds_data.len(),
+            data.len()
+        );
+    }
+    dds_data[..data.len()].copy_from_slice(&data);
+
+    dds.write(&mut std::io::BufWriter::new(std::fs::File::create(output)?))?;
+
+    Ok(())
+}
+
+/// A KTX2 file without supercompression or key/value data. The levels are
+/// stored from the smallest to the largest, each aligned to a block.
+fn ktx2(format: Format, srgb: bool, width: u32, height: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+    use ktx2::{ColorModel, Format as VkFormat};
+
+    let (vk_format, color_model, samples): (_, _, &[(u32, u32)]) = match (format, srgb) {
+        (Format::Bc(bc::Format::Bc1), false) => {
+            (VkFormat::BC1_RGBA_UNORM_BLOCK, ColorModel::BC1A, &[(0, 1)])
+        }
+        (Format::Bc(bc::Format::Bc1), true) => {
+            (VkFormat::BC1_RGBA_SRGB_BLOCK, ColorModel::BC1A, &[(0, 1)])
+        }
+        (Format::Bc(bc::Format::Bc3), false) => (
+            VkFormat::BC3_UNORM_BLOCK,
+            ColorModel::BC3,
+            &[(0, 15), (64, 0)],
+        ),
+        (Format::Bc(bc::Format::Bc3), true) => (
+            VkFormat::BC3_SRGB_BLOCK,
+            ColorModel::BC3,
+            &[(0, 15), (64, 0)],
+        ),
+        (Format::Bc(bc::Format::Bc4), _) => (VkFormat::BC4_UNORM_BLOCK, ColorModel::BC4, &[(0, 0)]),
+        (Format::Bc(bc::Format::Bc5), _) => (
+            VkFormat::BC5_UNORM_BLOCK,
+            ColorModel::BC5,
+            &[(0, 0), (64, 1)],
+        ),
+        (Format::Bc7, false) => (VkFormat::BC7_UNORM_BLOCK, ColorModel::BC7, &[(0, 0)]),
+        (Format::Bc7, true) => (VkFormat::BC7_SRGB_BLOCK, ColorModel::BC7, &[(0, 0)]),
+        (Format::Astc(block_width, block_height), srgb) => {
+            let index = ASTC_BLOCKS
+                .iter()
+                .position(|&size| size == (block_width, block_height))
+                .unwrap() as u32;
+
+            (
+                VkFormat(
+                    (VkFormat::ASTC_4x4_UNORM_BLOCK.0.get() + index * 2 + srgb as u32)
+                        .try_into()
+                        .unwrap(),
+                ),
+                ColorModel::ASTC,
+                &[(0, 0)],
+            )
+        }
+    };
+
+    let (block_width, block_height) = format.block_dimensions();
+    let block_bytes = format.block_bytes();
+    let sample_bits = block_bytes as u32 * 8 / samples.len() as u32;
+
+    // The data format descriptor: its total size, then one basic descriptor
+    // block with a sample per compressed channel.
+    let mut dfd = Vec::new();
+    let block_size = 24 + 16 * samples.len() as u32;
+
+    dfd.extend((4 + block_size).to_le_bytes());
+    dfd.extend(0u32.to_le_bytes());
+    dfd.extend((2 | block_size << 16).to_le_bytes());
+    dfd.extend([
+        color_model.0.get() as u8,
+        1, // BT.709 primaries
+        if srgb { 2 } else { 1 },
+        0, // straight alpha
+        block_width as u8 - 1,
+        block_height as u8 - 1,
+        0,
+        0,
+        block_bytes as u8,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    for &(bit_offset, channel) in samples {
+        dfd.extend((bit_offset | (sample_bits - 1) << 16 | channel << 24).to_le_bytes());
+        dfd.extend(0u32.to_le_bytes());
+        dfd.extend(0u32.to_le_bytes());
+        dfd.extend(u32::MAX.to_le_bytes());
+    }
+
+    let header_bytes = 80 + 24 * levels.len();
+    let align = |offset: usize| (offset + block_bytes - 1) / block_bytes * block_bytes;
+
+    let mut offsets = vec![0; levels.len()];
+    let mut end = header_bytes + dfd.len();
+
+    for (level, data) in levels.iter().enumerate().rev() {
+        offsets[level] = align(end);
+        end = offsets[level] + data.len();
+    }
+
+    let mut file = vec![
+        0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+    ];
+
+    for value in [
+        vk_format.0.get(),
+        1, // type size
+        width,
+        height,
+        0, // depth
+        0, // layers
+        1, // faces
+        levels.len() as u32,
+        0, // supercompression
+        header_bytes as u32,
+        dfd.len() as u32,
+        0, // key/value data offset
+        0, // key/value data length
+    ] {
+        file.extend(value.to_le_bytes());
+    }
+
+    // No supercompression global data.
+    file.extend([0; 16]);
+
+    for (offset, data) in offsets.iter().zip(levels) {
+        for value in [*offset, data.len(), data.len()] {
+            file.extend((value as u64).to_le_bytes());
+        }
+    }
+
+    file.extend(dfd);
+
+    for (offset, data) in offsets.iter().zip(levels).rev() {
+        file.resize(*offset, 0);
+        file.extend(data);
+    }
+
+    file
+}
@@ -0,0 +1,173 @@
+//! Culls a grid of instances on a software adapter, reads the indirect draw
+//! arguments back and compares them with the same culling done on the CPU, so
+//! `cull.wgsl` can be checked without a window or a GPU.
+//!
+//! Usage: `cull_check`
+
+use anyhow::{bail, Context};
+use weng_game::culling::{self, Culling, DrawIndexedIndirect};
+
+/// Laid out like the instances of the game, which start with the model matrix.
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Instance {
+    model: [f32; 16],
+    normal: [f32; 9],
+}
+
+fn main() -> anyhow::Result<()> {
+    let instance = weng::wgpu::Instance::new(weng::wgpu::InstanceDescriptor::default());
+    let adapter = block_on(
+        instance.request_adapter(&weng::wgpu::RequestAdapterOptions {
+            power_preference: weng::wgpu::PowerPreference::default(),
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        }),
+    )
+    .context("no software adapter")?;
+
+    println!("adapter: {}", adapter.get_info().name);
+
+    let (device, queue) = block_on(adapter.request_device(
+        &weng::wgpu::DeviceDescriptor {
+            label: None,
+            features: weng::wgpu::Features::empty(),
+            limits: adapter.limits(),
+        },
+        None,
+    ))?;
+
+    let shader = device.create_shader_module(weng::wgpu::ShaderModuleDescriptor {
+        label: Some("cull.wgsl"),
+        source: weng::wgpu::ShaderSource::Wgsl(
+            include_str!("../../assets/shaders/cull.wgsl").into(),
+        ),
+    });
+
+    let mut instances = Vec::new();
+    for x in -50..50i32 {
+        for z in -50..50 {
+            let scale = 0.5 + (x + z).rem_euclid(4) as f32;
+
+            instances.push(Instance {
+                model: glam::Mat4::from_scale_rotation_translation(
+                    glam::Vec3::splat(scale),
+                    glam::Quat::IDENTITY,
+                    glam::Vec3::new(x as f32 * 4.0, (x - z) as f32 * 0.5, z as f32 * 4.0),
+                )
+                .to_cols_array(),
+                normal: glam::Mat3::IDENTITY.to_cols_array(),
+            });
+        }
+    }
+
+    // A unit cube, like `cube.obj`.
+    let bounding_sphere = glam::Vec4::new(0.0, 0.0, 0.0, 3f32.sqrt());
+    let index_count = 36;
+
+    let view_projection = glam::Mat4::perspective_lh(1.0, 16.0 / 9.0, 0.1, 100.0)
+        * glam::Mat4::look_at_lh(
+            glam::Vec3::new(0.0, 20.0, -60.0),
+            glam::Vec3::ZERO,
+            glam::Vec3::Y,
+        );
+
+    let culling = Culling::new(&device, &shader, &instances);
+
+    let mut encoder =
+        device.create_command_encoder(&weng::wgpu::CommandEncoderDescriptor { label: None });
+    culling.cull(
+        &queue,
+        &mut encoder,
+        view_projection,
+        bounding_sphere,
+        index_count,
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let draw = read_draw(&device, &queue, &culling);
+
+    let planes = culling::frustum_planes(view_projection);
+    let expected = instances
+        .iter()
+        .filter(|instance| {
+            culling::instance_visible(
+                &planes,
+                glam::Mat4::from_cols_array(&instance.model),
+                bounding_sphere,
+            )
+        })
+        .count() as u32;
+
+    println!(
+        "visible: {} of {} on the GPU, {expected} on the CPU",
+        draw.instance_count,
+        culling.instance_count(),
+    );
+
+    if draw.index_count != index_count {
+        bail!("expected {index_count} indices, got {}", draw.index_count);
+    }
+    if draw.instance_count != expected {
+        bail!("the GPU and CPU disagree on the visible instances");
+    }
+
+    Ok(())
+}
+
+/// Copies the indirect arguments back from the GPU, waiting for every
+/// submitted culling pass to finish.
+fn read_draw(
+    device: &weng::wgpu::Device,
+    queue: &weng::wgpu::Queue,
+    culling: &Culling,
+) -> DrawIndexedIndirect {
+    let size = std::mem::size_of::<DrawIndexedIndirect>() as weng::wgpu::BufferAddress;
+    let readback = device.create_buffer(&weng::wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: weng::wgpu::BufferUsages::MAP_READ | weng::wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&weng::wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(culling.draw_buffer(), 0, &readback, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback.slice(..);
+    slice.map_async(weng::wgpu::MapMode::Read, |_| ());
+    device.poll(weng::wgpu::Maintain::Wait);
+
+    let draw = *bytemuck::from_bytes(&slice.get_mapped_range());
+    readback.unmap();
+
+    draw
+}
+
+/// Runs a future to completion on this thread, which is all the futures of
+/// native wgpu backends need.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> std::task::RawWaker {
+        fn clone(_: *const ()) -> std::task::RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: std::task::RawWakerVTable =
+            std::task::RawWakerVTable::new(clone, noop, noop, noop);
+
+        std::task::RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    // SAFETY: the vtable functions do nothing with the data pointer.
+    let waker = unsafe { std::task::Waker::from_raw(noop_raw_waker()) };
+    let mut context = std::task::Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
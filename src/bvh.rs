@@ -0,0 +1,659 @@
+//! A dynamic bounding volume hierarchy: a binary tree of boxes over the scene,
+//! for finding what is near a point, a ray or the camera without going
+//! through every object.
+//!
+//! Leaves are stored with a margin around the box they were given, so that an
+//! object moving a little stays in its leaf and [`Bvh::update`] costs nothing.
+//! When it does leave, it is taken out and inserted again next to whichever
+//! subtree grows the least, and the boxes above it are refitted.
+
+use std::{collections::HashMap, hash::Hash};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub max: glam::Vec3,
+    pub min: glam::Vec3,
+}
+
+impl Aabb {
+    /// Nothing is inside, and merging it with a box gives that box.
+    pub const EMPTY: Self = Self {
+        max: glam::Vec3::splat(f32::MIN),
+        min: glam::Vec3::splat(f32::MAX),
+    };
+
+    pub fn center(&self) -> glam::Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+
+    pub fn expanded(&self, margin: f32) -> Self {
+        Self {
+            max: self.max + margin,
+            min: self.min - margin,
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = glam::Vec3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| Self {
+            max: aabb.max.max(point),
+            min: aabb.min.min(point),
+        })
+    }
+
+    pub fn from_sphere(center: glam::Vec3, radius: f32) -> Self {
+        Self {
+            max: center + radius,
+            min: center - radius,
+        }
+    }
+
+    /// Whether the box is at least partly inside the planes of
+    /// [`frustum_planes`](crate::culling::frustum_planes).
+    pub fn in_frustum(&self, planes: &[glam::Vec4; 6]) -> bool {
+        planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // The corner furthest along the normal.
+            let corner = glam::Vec3::select(normal.cmpge(glam::Vec3::ZERO), self.max, self.min);
+
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    pub fn intersects_sphere(&self, center: glam::Vec3, radius: f32) -> bool {
+        center.clamp(self.min, self.max).distance_squared(center) <= radius * radius
+    }
+
+    pub fn merged(&self, other: &Aabb) -> Self {
+        Self {
+            max: self.max.max(other.max),
+            min: self.min.min(other.min),
+        }
+    }
+
+    /// The distance along the ray to where it enters the box, or zero if it
+    /// starts inside. `inverse_direction` is one over every component of the
+    /// direction.
+    pub fn ray(
+        &self,
+        origin: glam::Vec3,
+        inverse_direction: glam::Vec3,
+        max_distance: f32,
+    ) -> Option<f32> {
+        let to_min = (self.min - origin) * inverse_direction;
+        let to_max = (self.max - origin) * inverse_direction;
+        let enter = to_min.min(to_max).max_element().max(0.0);
+        let exit = to_min.max(to_max).min_element().min(max_distance);
+
+        // A zero component of the direction gives infinite distances on its
+        // axis, which compare as they should.
+        (enter <= exit).then_some(enter)
+    }
+
+    /// Half the area of the surface, which is what inserting compares.
+    pub fn surface_area(&self) -> f32 {
+        let size = (self.max - self.min).max(glam::Vec3::ZERO);
+
+        size.x * size.y + size.y * size.z + size.z * size.x
+    }
+
+    /// The box around this one once `matrix` moves it.
+    pub fn transformed(&self, matrix: glam::Mat4) -> Self {
+        let center = matrix.transform_point3(self.center());
+        let half = (self.max - self.min) / 2.0;
+        let extent = matrix.x_axis.truncate().abs() * half.x
+            + matrix.y_axis.truncate().abs() * half.y
+            + matrix.z_axis.truncate().abs() * half.z;
+
+        Self {
+            max: center + extent,
+            min: center - extent,
+        }
+    }
+}
+
+enum Kind<K> {
+    Branch([usize; 2]),
+    Leaf(K),
+}
+
+struct Node<K> {
+    /// Around both children of a branch, or the box of a leaf with the
+    /// margin.
+    aabb: Aabb,
+    kind: Kind<K>,
+    parent: Option<usize>,
+}
+
+/// Boxes keyed by `K`, such as an entity.
+pub struct Bvh<K> {
+    /// The node of every key.
+    leaves: HashMap<K, usize>,
+    margin: f32,
+    nodes: Vec<Node<K>>,
+    /// Nodes that were removed, to reuse.
+    free: Vec<usize>,
+    root: Option<usize>,
+}
+
+impl<K: Copy + Eq + Hash> Bvh<K> {
+    /// The box `key` was inserted with, and the margin.
+    pub fn aabb(&self, key: K) -> Option<Aabb> {
+        self.leaves.get(&key).map(|&node| self.nodes[node].aabb)
+    }
+
+    pub fn clear(&mut self) {
+        self.leaves.clear();
+        self.nodes.clear();
+        self.free.clear();
+        self.root = None;
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.leaves.contains_key(&key)
+    }
+
+    /// How many branches there are on the longest way from the root to a
+    /// leaf.
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut stack: Vec<_> = self.root.map(|root| (root, 0)).into_iter().collect();
+
+        while let Some((node, depth)) = stack.pop() {
+            match self.nodes[node].kind {
+                Kind::Branch(children) => stack.extend(children.map(|child| (child, depth + 1))),
+                Kind::Leaf(_) => deepest = deepest.max(depth),
+            }
+        }
+
+        deepest
+    }
+
+    /// Adds `key` with `aabb`, or moves it there if it is already in.
+    pub fn insert(&mut self, key: K, aabb: Aabb) {
+        if self.contains(key) {
+            self.update(key, aabb);
+
+            return;
+        }
+
+        let leaf = self.allocate(Node {
+            aabb: aabb.expanded(self.margin),
+            kind: Kind::Leaf(key),
+            parent: None,
+        });
+
+        self.leaves.insert(key, leaf);
+        self.insert_leaf(leaf);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = K> + '_ {
+        self.leaves.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// `margin` is how far an object can move before its leaf has to.
+    pub fn new(margin: f32) -> Self {
+        Self {
+            leaves: HashMap::new(),
+            margin,
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+        }
+    }
+
+    /// Calls `visit` with every key whose box overlaps `aabb`.
+    pub fn query_aabb(&self, aabb: &Aabb, visit: impl FnMut(K)) {
+        self.query(|node| node.intersects(aabb), visit);
+    }
+
+    /// Calls `visit` with every key whose box is at least partly inside the
+    /// planes of [`frustum_planes`](crate::culling::frustum_planes).
+    pub fn query_frustum(&self, planes: &[glam::Vec4; 6], visit: impl FnMut(K)) {
+        self.query(|node| node.in_frustum(planes), visit);
+    }
+
+    /// Calls `visit` with every key whose box the ray passes through within
+    /// `max_distance`, and the distance to where it enters the box. The keys
+    /// come in no particular order.
+    pub fn query_ray(
+        &self,
+        origin: glam::Vec3,
+        direction: glam::Vec3,
+        max_distance: f32,
+        mut visit: impl FnMut(K, f32),
+    ) {
+        let inverse_direction = direction.recip();
+        let mut stack: Vec<_> = self.root.into_iter().collect();
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let distance = match node.aabb.ray(origin, inverse_direction, max_distance) {
+                Some(distance) => distance,
+                None => continue,
+            };
+
+            match node.kind {
+                Kind::Branch(children) => stack.extend(children),
+                Kind::Leaf(key) => visit(key, distance),
+            }
+        }
+    }
+
+    /// Calls `visit` with every key whose box overlaps the sphere.
+    pub fn query_sphere(&self, center: glam::Vec3, radius: f32, visit: impl FnMut(K)) {
+        self.query(|node| node.intersects_sphere(center, radius), visit);
+    }
+
+    /// Returns whether `key` was in.
+    pub fn remove(&mut self, key: K) -> bool {
+        match self.leaves.remove(&key) {
+            Some(leaf) => {
+                self.remove_leaf(leaf);
+                self.free.push(leaf);
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves `key` to `aabb`. The tree only changes when the new box leaves
+    /// the margin of the old one, which this returns. Does nothing if `key`
+    /// is not in.
+    pub fn update(&mut self, key: K, aabb: Aabb) -> bool {
+        let leaf = match self.leaves.get(&key) {
+            Some(&leaf) => leaf,
+            None => return false,
+        };
+
+        if self.nodes[leaf].aabb.contains(&aabb) {
+            return false;
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].aabb = aabb.expanded(self.margin);
+        self.insert_leaf(leaf);
+
+        true
+    }
+
+    fn allocate(&mut self, node: Node<K>) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+
+                index
+            }
+            None => {
+                self.nodes.push(node);
+
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Pairs `leaf` with the node that makes the tree grow the least, going
+    /// down from the root while a child is cheaper than the node itself.
+    fn insert_leaf(&mut self, leaf: usize) {
+        let aabb = self.nodes[leaf].aabb;
+        let mut sibling = match self.root {
+            Some(root) => root,
+            None => {
+                self.nodes[leaf].parent = None;
+                self.root = Some(leaf);
+
+                return;
+            }
+        };
+
+        while let Kind::Branch(children) = self.nodes[sibling].kind {
+            let area = self.nodes[sibling].aabb.surface_area();
+            let merged_area = self.nodes[sibling].aabb.merged(&aabb).surface_area();
+            // Pairing here makes a new branch around both, and every branch
+            // above grows by what the leaf adds.
+            let here = 2.0 * merged_area;
+            let inherited = 2.0 * (merged_area - area);
+
+            let cost = |child: usize| {
+                let child = &self.nodes[child];
+                let merged = child.aabb.merged(&aabb).surface_area();
+
+                match child.kind {
+                    Kind::Branch(_) => merged - child.aabb.surface_area() + inherited,
+                    Kind::Leaf(_) => merged + inherited,
+                }
+            };
+            let costs = children.map(cost);
+
+            if here < costs[0] && here < costs[1] {
+                break;
+            }
+
+            sibling = if costs[0] <= costs[1] {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+
+        let parent = self.nodes[sibling].parent;
+        let branch = self.allocate(Node {
+            aabb: self.nodes[sibling].aabb.merged(&aabb),
+            kind: Kind::Branch([sibling, leaf]),
+            parent,
+        });
+
+        self.nodes[sibling].parent = Some(branch);
+        self.nodes[leaf].parent = Some(branch);
+        self.replace_child(parent, sibling, branch);
+        self.refit(parent);
+    }
+
+    fn query(&self, mut overlaps: impl FnMut(&Aabb) -> bool, mut visit: impl FnMut(K)) {
+        let mut stack: Vec<_> = self.root.into_iter().collect();
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            if !overlaps(&node.aabb) {
+                continue;
+            }
+
+            match node.kind {
+                Kind::Branch(children) => stack.extend(children),
+                Kind::Leaf(key) => visit(key),
+            }
+        }
+    }
+
+    /// Grows or shrinks the boxes from `node` up to the root around their
+    /// children.
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(index) = node {
+            if let Kind::Branch([a, b]) = self.nodes[index].kind {
+                self.nodes[index].aabb = self.nodes[a].aabb.merged(&self.nodes[b].aabb);
+            }
+
+            node = self.nodes[index].parent;
+        }
+    }
+
+    /// Takes `leaf` out of the tree, putting its sibling in place of their
+    /// parent. The leaf node itself is kept.
+    fn remove_leaf(&mut self, leaf: usize) {
+        let parent = match self.nodes[leaf].parent {
+            Some(parent) => parent,
+            None => {
+                self.root = None;
+
+                return;
+            }
+        };
+        let sibling = match self.nodes[parent].kind {
+            Kind::Branch([a, b]) if a == leaf => b,
+            Kind::Branch([a, _]) => a,
+            Kind::Leaf(_) => unreachable!("the parent of a node is a branch"),
+        };
+        let grandparent = self.nodes[parent].parent;
+
+        self.nodes[sibling].parent = grandparent;
+        self.replace_child(grandparent, parent, sibling);
+        self.free.push(parent);
+        self.refit(grandparent);
+    }
+
+    /// Points `parent`, or the root if there is none, at `new` instead of
+    /// `old`.
+    fn replace_child(&mut self, parent: Option<usize>, old: usize, new: usize) {
+        match parent {
+            Some(parent) => {
+                if let Kind::Branch(children) = &mut self.nodes[parent].kind {
+                    for child in children {
+                        if *child == old {
+                            *child = new;
+                        }
+                    }
+                }
+            }
+            None => self.root = Some(new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::culling::frustum_planes;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    const EXTENT: f32 = 20.0;
+
+    fn random_box(random: &mut impl Rng, extent: f32) -> Aabb {
+        let center = random_point(random, extent);
+        let half = glam::Vec3::new(
+            random.gen_range(0.1..1.0),
+            random.gen_range(0.1..1.0),
+            random.gen_range(0.1..1.0),
+        );
+
+        Aabb {
+            max: center + half,
+            min: center - half,
+        }
+    }
+
+    fn random_point(random: &mut impl Rng, extent: f32) -> glam::Vec3 {
+        glam::Vec3::new(
+            random.gen_range(-extent..extent),
+            random.gen_range(-extent..extent),
+            random.gen_range(-extent..extent),
+        )
+    }
+
+    /// Every branch is around both of its children and their parent, and
+    /// every key in `boxes` is in a leaf of its own around its box.
+    fn check_tree(tree: &Bvh<usize>, boxes: &HashMap<usize, Aabb>) {
+        let mut stack: Vec<_> = tree.root.into_iter().collect();
+        let mut leaves = 0;
+
+        assert_eq!(tree.root.and_then(|root| tree.nodes[root].parent), None);
+
+        while let Some(node) = stack.pop() {
+            match tree.nodes[node].kind {
+                Kind::Branch(children) => {
+                    for child in children {
+                        assert_eq!(tree.nodes[child].parent, Some(node));
+                        assert!(tree.nodes[node].aabb.contains(&tree.nodes[child].aabb));
+                    }
+
+                    stack.extend(children);
+                }
+                Kind::Leaf(key) => {
+                    assert_eq!(tree.leaves.get(&key), Some(&node));
+                    assert!(tree.nodes[node].aabb.contains(&boxes[&key]));
+
+                    leaves += 1;
+                }
+            }
+        }
+
+        assert_eq!(leaves, boxes.len());
+        assert_eq!(tree.len(), boxes.len());
+    }
+
+    /// Sorted, to compare with going through every box.
+    fn found(query: impl FnOnce(&mut dyn FnMut(usize))) -> Vec<usize> {
+        let mut found = Vec::new();
+
+        query(&mut |key| found.push(key));
+        found.sort_unstable();
+
+        found
+    }
+
+    /// The keys whose leaf box `hits`, which is what the queries go by.
+    fn expected(
+        tree: &Bvh<usize>,
+        boxes: &HashMap<usize, Aabb>,
+        hits: impl Fn(&Aabb) -> bool,
+    ) -> Vec<usize> {
+        let mut expected: Vec<_> = boxes
+            .keys()
+            .copied()
+            .filter(|&key| hits(&tree.aabb(key).unwrap()))
+            .collect();
+
+        expected.sort_unstable();
+
+        expected
+    }
+
+    /// Runs every kind of query from random places, and compares what they
+    /// find with going through all of the boxes.
+    fn check_queries(tree: &Bvh<usize>, boxes: &HashMap<usize, Aabb>, random: &mut impl Rng) {
+        for _ in 0..50 {
+            let query = random_box(random, EXTENT);
+
+            assert_eq!(
+                found(|visit| tree.query_aabb(&query, visit)),
+                expected(tree, boxes, |aabb| aabb.intersects(&query)),
+            );
+
+            let center = random_point(random, EXTENT);
+            let radius = random.gen_range(0.5..8.0);
+
+            assert_eq!(
+                found(|visit| tree.query_sphere(center, radius, visit)),
+                expected(tree, boxes, |aabb| aabb.intersects_sphere(center, radius)),
+            );
+
+            let origin = random_point(random, EXTENT);
+            let direction = random_point(random, 1.0).normalize_or_zero();
+            let inverse_direction = direction.recip();
+            let mut distances = HashMap::new();
+
+            assert_eq!(
+                found(
+                    |visit| tree.query_ray(origin, direction, EXTENT, |key, distance| {
+                        distances.insert(key, distance);
+                        visit(key);
+                    })
+                ),
+                expected(tree, boxes, |aabb| {
+                    aabb.ray(origin, inverse_direction, EXTENT).is_some()
+                }),
+            );
+            for (key, distance) in distances {
+                assert_eq!(
+                    tree.aabb(key)
+                        .unwrap()
+                        .ray(origin, inverse_direction, EXTENT),
+                    Some(distance)
+                );
+            }
+
+            let view = glam::Mat4::look_to_lh(
+                random_point(random, EXTENT),
+                random_point(random, 1.0),
+                glam::Vec3::Y,
+            );
+            let projection = glam::Mat4::perspective_lh(1.1, 16.0 / 9.0, 0.1, EXTENT);
+            let planes = frustum_planes(projection * view);
+
+            assert_eq!(
+                found(|visit| tree.query_frustum(&planes, visit)),
+                expected(tree, boxes, |aabb| aabb.in_frustum(&planes)),
+            );
+        }
+    }
+
+    #[test]
+    fn queries_match_brute_force_through_inserts_updates_and_removes() {
+        let mut random = SmallRng::seed_from_u64(0);
+        let mut tree = Bvh::new(0.25);
+        let mut boxes = HashMap::new();
+
+        for key in 0..500 {
+            let aabb = random_box(&mut random, EXTENT);
+
+            tree.insert(key, aabb);
+            boxes.insert(key, aabb);
+        }
+        check_tree(&tree, &boxes);
+        check_queries(&tree, &boxes, &mut random);
+
+        // Most drift within their margin, and a few jump across the scene.
+        for (&key, aabb) in &mut boxes {
+            let offset = if key % 10 == 0 {
+                random_point(&mut random, EXTENT)
+            } else {
+                random_point(&mut random, 0.1)
+            };
+
+            aabb.min += offset;
+            aabb.max += offset;
+            tree.update(key, *aabb);
+        }
+        check_tree(&tree, &boxes);
+        check_queries(&tree, &boxes, &mut random);
+
+        for key in (0..500).step_by(2) {
+            assert!(tree.remove(key));
+            boxes.remove(&key);
+        }
+        assert!(!tree.remove(0));
+        check_tree(&tree, &boxes);
+        check_queries(&tree, &boxes, &mut random);
+
+        // Into the nodes the removes freed.
+        for key in (0..500).step_by(4) {
+            let aabb = random_box(&mut random, EXTENT);
+
+            tree.insert(key, aabb);
+            boxes.insert(key, aabb);
+        }
+        check_tree(&tree, &boxes);
+        check_queries(&tree, &boxes, &mut random);
+    }
+
+    #[test]
+    fn updates_only_move_leaves_out_of_their_margin() {
+        let mut tree = Bvh::new(0.5);
+        let aabb = Aabb {
+            max: glam::Vec3::ONE,
+            min: glam::Vec3::ZERO,
+        };
+        let moved = |offset: glam::Vec3| Aabb {
+            max: aabb.max + offset,
+            min: aabb.min + offset,
+        };
+
+        tree.insert(0, aabb);
+        tree.insert(1, moved(glam::Vec3::splat(10.0)));
+
+        assert!(!tree.update(0, moved(glam::Vec3::splat(0.25))));
+        assert_eq!(tree.aabb(0), Some(aabb.expanded(0.5)));
+
+        assert!(tree.update(0, moved(glam::Vec3::X)));
+        assert_eq!(tree.aabb(0), Some(moved(glam::Vec3::X).expanded(0.5)));
+
+        assert!(!tree.update(2, aabb));
+        assert!(!tree.contains(2));
+    }
+}
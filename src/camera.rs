@@ -16,6 +16,7 @@ impl Type {
     }
 }
 
+#[derive(Clone)]
 pub struct Camera {
     dir: glam::Vec3,
     yaw: f32,
@@ -35,6 +36,16 @@ impl Camera {
         self.projection * glam::Mat4::look_to_lh(self.pos, self.dir, Self::UP)
     }
 
+    /// Like [`Self::build_matrix`], but from the origin so that only the
+    /// rotation of the camera applies.
+    pub fn build_sky_matrix(&self) -> glam::Mat4 {
+        self.projection * glam::Mat4::look_to_lh(glam::Vec3::ZERO, self.dir, Self::UP)
+    }
+
+    pub fn direction(&self) -> glam::Vec3 {
+        self.dir
+    }
+
     pub fn new(surface_width: u32, surface_height: u32) -> Self {
         let mut camera = Self {
             pos: glam::Vec3::new(0.0, 0.0, -1.5),
@@ -54,6 +65,10 @@ impl Camera {
         self.pos
     }
 
+    pub fn right(&self) -> glam::Vec3 {
+        -self.dir.cross(Self::UP).normalize()
+    }
+
     pub fn resize(&mut self, new_width: u32, new_height: u32) {
         self.projection = glam::Mat4::perspective_lh(
             Self::FOVY,
@@ -63,7 +78,8 @@ impl Camera {
         );
     }
 
-    pub fn update(&mut self, input: &Input) {
+    /// Switches the type and turns with the cursor, without moving.
+    pub fn look(&mut self, input: &Input) {
         if input.flying_camera() {
             self.type_ = Type::Flying;
         }
@@ -83,7 +99,7 @@ impl Camera {
                 .clamp(f32::to_radians(-89.0), f32::to_radians(89.0));
         }
 
-        let right = -self.dir.cross(Self::UP);
+        let right = self.right();
 
         let orientation = {
             let pitch = glam::Quat::from_axis_angle(right, -self.pitch);
@@ -92,13 +108,30 @@ impl Camera {
         };
 
         self.dir = orientation.mul_vec3(glam::Vec3::Z);
+    }
 
-        let move_speed = 0.1;
-
+    /// The unit direction the movement keys point to, flattened for the FPS
+    /// camera.
+    pub fn movement(&self, input: &Input) -> glam::Vec3 {
         let movement_dir = self.type_.transform_dir_for_movement(self.dir);
 
-        self.pos += (movement_dir * input.movement().z + right * input.movement().x)
-            .normalize_or_zero()
-            * move_speed;
+        (movement_dir * input.movement().z + self.right() * input.movement().x).normalize_or_zero()
+    }
+
+    pub fn set_position(&mut self, position: glam::Vec3) {
+        self.pos = position;
+    }
+
+    pub fn type_(&self) -> Type {
+        self.type_
+    }
+
+    /// Looks, then moves freely, for a camera that no character carries.
+    pub fn update(&mut self, input: &Input) {
+        self.look(input);
+
+        let move_speed = 0.1;
+
+        self.pos += self.movement(input) * move_speed;
     }
 }
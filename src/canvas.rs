@@ -0,0 +1,324 @@
+//! Immediate mode 2D drawing for the HUD. Quads are added during a frame, in
+//! framebuffer pixels from the top left corner, and drawn over everything else
+//! by `renderer::sprites`.
+
+use crate::data::shaders::sprite::Vertex;
+
+/// A texture of the sprite renderer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId(pub(crate) usize);
+
+impl TextureId {
+    /// A single white texel, which leaves colored quads as they are.
+    pub const WHITE: Self = Self(0);
+}
+
+/// A region of a texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    /// The top left corner, in texels.
+    pub origin: glam::UVec2,
+    /// In texels.
+    pub size: glam::UVec2,
+    pub texture: TextureId,
+    pub texture_size: glam::UVec2,
+}
+
+impl Sprite {
+    /// The top left and bottom right texture coordinates of `min` and `max`,
+    /// which are in texels within the sprite.
+    fn tex_coords(&self, min: glam::Vec2, max: glam::Vec2) -> (glam::Vec2, glam::Vec2) {
+        let origin = self.origin.as_vec2();
+        let texture_size = self.texture_size.as_vec2();
+
+        ((origin + min) / texture_size, (origin + max) / texture_size)
+    }
+
+    /// The whole of a texture.
+    pub fn whole(texture: TextureId, texture_size: glam::UVec2) -> Self {
+        Self {
+            origin: glam::UVec2::ZERO,
+            size: texture_size,
+            texture,
+            texture_size,
+        }
+    }
+}
+
+/// A texture cut into a grid of sprites of the same size, numbered row by row
+/// from the top left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteSheet {
+    /// In texels.
+    pub cell: glam::UVec2,
+    pub texture: Sprite,
+}
+
+impl SpriteSheet {
+    pub fn columns(&self) -> u32 {
+        self.texture.size.x / self.cell.x
+    }
+
+    pub fn len(&self) -> u32 {
+        self.columns() * (self.texture.size.y / self.cell.y)
+    }
+
+    pub fn sprite(&self, index: u32) -> Sprite {
+        debug_assert!(index < self.len(), "sprite {index} is not in the sheet");
+
+        let cell = glam::UVec2::new(index % self.columns(), index / self.columns());
+
+        Sprite {
+            origin: self.texture.origin + cell * self.cell,
+            size: self.cell,
+            ..self.texture
+        }
+    }
+}
+
+/// Quads drawn one after another with the same texture, so they can be drawn
+/// at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Batch {
+    pub texture: TextureId,
+    pub vertices: std::ops::Range<u32>,
+}
+
+/// The 2D shapes of the current frame.
+pub struct Canvas {
+    batches: Vec<Batch>,
+    vertices: Vec<Vertex>,
+}
+
+impl Canvas {
+    /// A horizontal bar filled from the left to `fraction`, over `background`,
+    /// such as a health bar.
+    pub fn bar(
+        &mut self,
+        min: glam::Vec2,
+        size: glam::Vec2,
+        fraction: f32,
+        color: glam::Vec4,
+        background: glam::Vec4,
+    ) {
+        let fraction = fraction.clamp(0.0, 1.0);
+
+        self.rect(min, size, background);
+        self.rect(min, glam::Vec2::new(size.x * fraction, size.y), color);
+    }
+
+    pub fn batches(&self) -> &[Batch] {
+        &self.batches
+    }
+
+    /// Clears the shapes of the last frame.
+    pub fn begin(&mut self) {
+        self.batches.clear();
+        self.vertices.clear();
+    }
+
+    /// Four lines around `center`, leaving a gap of `gap` pixels from it.
+    pub fn crosshair(
+        &mut self,
+        center: glam::Vec2,
+        length: f32,
+        gap: f32,
+        thickness: f32,
+        color: glam::Vec4,
+    ) {
+        let half = thickness / 2.0;
+
+        for (min, size) in [
+            (
+                glam::Vec2::new(-gap - length, -half),
+                glam::Vec2::new(length, thickness),
+            ),
+            (
+                glam::Vec2::new(gap, -half),
+                glam::Vec2::new(length, thickness),
+            ),
+            (
+                glam::Vec2::new(-half, -gap - length),
+                glam::Vec2::new(thickness, length),
+            ),
+            (
+                glam::Vec2::new(-half, gap),
+                glam::Vec2::new(thickness, length),
+            ),
+        ] {
+            self.rect(center + min, size, color);
+        }
+    }
+
+    pub fn new() -> Self {
+        Self {
+            batches: Vec::new(),
+            vertices: Vec::new(),
+        }
+    }
+
+    /// A panel that stretches `sprite` to `size` but keeps its corners, the
+    /// `border` texels in from each edge, at their size in texels.
+    pub fn nine_slice(
+        &mut self,
+        sprite: &Sprite,
+        border: f32,
+        min: glam::Vec2,
+        size: glam::Vec2,
+        color: glam::Vec4,
+    ) {
+        let sprite_size = sprite.size.as_vec2();
+        // Panels smaller than their corners squash them.
+        let border = glam::Vec2::splat(border).min(size / 2.0);
+
+        let xs = [0.0, border.x, size.x - border.x, size.x];
+        let ys = [0.0, border.y, size.y - border.y, size.y];
+        let us = [0.0, border.x, sprite_size.x - border.x, sprite_size.x];
+        let vs = [0.0, border.y, sprite_size.y - border.y, sprite_size.y];
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let (uv_min, uv_max) = sprite.tex_coords(
+                    glam::Vec2::new(us[column], vs[row]),
+                    glam::Vec2::new(us[column + 1], vs[row + 1]),
+                );
+
+                self.quad(
+                    sprite.texture,
+                    min + glam::Vec2::new(xs[column], ys[row]),
+                    min + glam::Vec2::new(xs[column + 1], ys[row + 1]),
+                    uv_min,
+                    uv_max,
+                    color,
+                );
+            }
+        }
+    }
+
+    /// A solid quad.
+    pub fn rect(&mut self, min: glam::Vec2, size: glam::Vec2, color: glam::Vec4) {
+        self.quad(
+            TextureId::WHITE,
+            min,
+            min + size,
+            glam::Vec2::ZERO,
+            glam::Vec2::ONE,
+            color,
+        );
+    }
+
+    /// `sprite` stretched to `size`, tinted by `color`.
+    pub fn sprite(
+        &mut self,
+        sprite: &Sprite,
+        min: glam::Vec2,
+        size: glam::Vec2,
+        color: glam::Vec4,
+    ) {
+        let (uv_min, uv_max) = sprite.tex_coords(glam::Vec2::ZERO, sprite.size.as_vec2());
+
+        self.quad(sprite.texture, min, min + size, uv_min, uv_max, color);
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    /// Adds two triangles, extending the last batch when it has the same
+    /// texture. Batches are never reordered, so later quads stay on top.
+    fn quad(
+        &mut self,
+        texture: TextureId,
+        min: glam::Vec2,
+        max: glam::Vec2,
+        uv_min: glam::Vec2,
+        uv_max: glam::Vec2,
+        color: glam::Vec4,
+    ) {
+        let color = color.to_array();
+        let corner = |x: bool, y: bool| Vertex {
+            position: [if x { max.x } else { min.x }, if y { max.y } else { min.y }],
+            tex_coords: [
+                if x { uv_max.x } else { uv_min.x },
+                if y { uv_max.y } else { uv_min.y },
+            ],
+            color,
+        };
+
+        let start = self.vertices.len() as u32;
+
+        self.vertices.extend([
+            corner(false, false),
+            corner(false, true),
+            corner(true, false),
+            corner(true, false),
+            corner(false, true),
+            corner(true, true),
+        ]);
+
+        let end = self.vertices.len() as u32;
+
+        match self.batches.last_mut() {
+            Some(batch) if batch.texture == texture => batch.vertices.end = end,
+            _ => self.batches.push(Batch {
+                texture,
+                vertices: start..end,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_cells_are_numbered_row_by_row() {
+        // A 2 by 2 sheet of 16 texel cells, in the bottom right of a texture.
+        let sheet = SpriteSheet {
+            cell: glam::UVec2::splat(16),
+            texture: Sprite {
+                origin: glam::UVec2::new(32, 64),
+                size: glam::UVec2::splat(32),
+                texture: TextureId(1),
+                texture_size: glam::UVec2::new(64, 128),
+            },
+        };
+
+        assert_eq!(sheet.len(), 4);
+
+        let mut canvas = Canvas::new();
+
+        for index in 0..sheet.len() {
+            canvas.sprite(
+                &sheet.sprite(index),
+                glam::Vec2::ZERO,
+                glam::Vec2::ONE,
+                glam::Vec4::ONE,
+            );
+        }
+
+        let corners = canvas
+            .vertices()
+            .chunks(6)
+            .map(|quad| (quad[0].tex_coords, quad[5].tex_coords))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            corners,
+            [
+                ([0.5, 0.5], [0.75, 0.625]),
+                ([0.75, 0.5], [1.0, 0.625]),
+                ([0.5, 0.625], [0.75, 0.75]),
+                ([0.75, 0.625], [1.0, 0.75]),
+            ]
+        );
+        assert_eq!(
+            canvas.batches(),
+            [Batch {
+                texture: TextureId(1),
+                vertices: 0..24,
+            }]
+        );
+    }
+}
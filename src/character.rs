@@ -0,0 +1,422 @@
+//! A capsule that walks through the physics world instead of flying through
+//! it, carrying the FPS camera. The capsule is not a body itself, so dynamic
+//! bodies block it but are not pushed.
+//!
+//! [`CharacterController::update`] only needs the [`Physics`] resource, so a
+//! controller can be moved around a few fixed colliders without a window.
+
+use crate::{
+    camera,
+    components::{Camera, Transform},
+    ecs::World,
+    gizmos::{self, Gizmos},
+    input::Input,
+    physics::{self, Physics, Shape},
+    systems::ActiveCamera,
+    time::Time,
+};
+
+pub struct CharacterController {
+    /// How far below the top of the capsule the eyes are.
+    pub eye_offset: f32,
+    /// From the bottom of the capsule to the top.
+    pub height: f32,
+    /// The upward speed of a jump, in units per second.
+    pub jump_speed: f32,
+    /// The steepest slope that can be walked up, in radians. Anything steeper
+    /// is slid down.
+    pub max_slope: f32,
+    pub radius: f32,
+    /// In units per second.
+    pub speed: f32,
+    /// The highest ledge that is walked onto without jumping.
+    pub step_height: f32,
+    grounded: bool,
+    vertical_speed: f32,
+}
+
+/// What the character is asked to do for a tick.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Control {
+    /// Where to walk, as a unit vector or zero. Only the horizontal part is
+    /// used.
+    pub direction: glam::Vec3,
+    pub jump: bool,
+}
+
+impl CharacterController {
+    /// From the center of the capsule up to the eyes.
+    pub fn eye_height(&self) -> f32 {
+        self.height / 2.0 - self.eye_offset
+    }
+
+    /// Whether the character stood on something at the end of its last
+    /// update.
+    pub fn grounded(&self) -> bool {
+        self.grounded
+    }
+
+    pub fn new() -> Self {
+        Self {
+            eye_offset: 0.1,
+            height: 1.8,
+            jump_speed: 5.0,
+            max_slope: 45f32.to_radians(),
+            radius: 0.4,
+            speed: 5.0,
+            step_height: 0.4,
+            grounded: false,
+            vertical_speed: 0.0,
+        }
+    }
+
+    /// Forgets the fall or jump in progress, for when the character is put
+    /// somewhere else.
+    pub fn stop(&mut self) {
+        self.grounded = false;
+        self.vertical_speed = 0.0;
+    }
+
+    /// Moves the character, with its capsule centered on `position`, by a
+    /// tick of `control` and gravity, and returns where the center ends up.
+    pub fn update(
+        &mut self,
+        physics: &Physics,
+        position: glam::Vec3,
+        control: &Control,
+    ) -> glam::Vec3 {
+        let delta = Time::TICK_RATE.as_secs_f32();
+
+        if self.grounded && control.jump {
+            self.vertical_speed = self.jump_speed;
+        }
+        self.vertical_speed += physics::GRAVITY.y * delta;
+
+        let walk =
+            glam::Vec3::new(control.direction.x, 0.0, control.direction.z).normalize_or_zero();
+        let translation = (walk * self.speed + glam::Vec3::Y * self.vertical_speed) * delta;
+        let (moved, grounded) = physics.move_shape(
+            self.shape(),
+            position,
+            translation,
+            self.max_slope,
+            self.step_height,
+        );
+
+        self.grounded = grounded;
+        // Landing ends the fall, and bumping a ceiling ends the jump.
+        if grounded && self.vertical_speed < 0.0 {
+            self.vertical_speed = 0.0;
+        }
+        if self.vertical_speed > 0.0 && moved.y < translation.y * 0.5 {
+            self.vertical_speed = 0.0;
+        }
+
+        position + moved
+    }
+
+    fn shape(&self) -> Shape {
+        let radius = self.radius.min(self.height / 2.0);
+
+        Shape::Capsule {
+            half_height: self.height / 2.0 - radius,
+            radius,
+        }
+    }
+}
+
+/// Draws the capsule of every character but the one carrying the active
+/// camera, green while it stands on the ground and yellow in the air.
+pub fn draw_gizmos(world: &World, gizmos: &mut Gizmos) {
+    let active = world.resource::<ActiveCamera>().0;
+    let transforms = world.components::<Transform>();
+
+    for (entity, character) in world.components::<CharacterController>().iter() {
+        let transform = match transforms.get(entity) {
+            Some(transform) if entity != active => transform,
+            _ => continue,
+        };
+
+        if let Shape::Capsule {
+            half_height,
+            radius,
+        } = character.shape()
+        {
+            let axis = glam::Vec3::Y * half_height;
+            let color = if character.grounded() {
+                gizmos::GREEN
+            } else {
+                gizmos::YELLOW
+            };
+
+            gizmos.capsule(
+                transform.translation - axis,
+                transform.translation + axis,
+                radius,
+                color,
+            );
+        }
+    }
+}
+
+/// Walks the characters whose FPS camera is in use with the input of the
+/// tick, and puts the camera at their eyes. A flying camera carries its
+/// character along instead, so that switching back lands where it flew.
+pub fn step(world: &mut World) {
+    let input = world.resource::<Input>();
+    let physics = world.resource::<Physics>();
+    let mut cameras = world.components_mut::<Camera>();
+    let mut transforms = world.components_mut::<Transform>();
+
+    for (entity, character) in world.components_mut::<CharacterController>().iter_mut() {
+        let transform = match transforms.get_mut(entity) {
+            Some(transform) => transform,
+            None => continue,
+        };
+        let camera = cameras.get_mut(entity);
+
+        let control = match &camera {
+            Some(camera) if matches!(camera.type_(), camera::Type::Flying) => {
+                transform.translation = camera.position() - glam::Vec3::Y * character.eye_height();
+                character.stop();
+
+                continue;
+            }
+            Some(camera) => Control {
+                direction: camera.movement(&input),
+                jump: input.jump(),
+            },
+            None => Control::default(),
+        };
+
+        transform.translation = character.update(&physics, transform.translation, &control);
+
+        if let Some(camera) = camera {
+            camera.set_position(transform.translation + glam::Vec3::Y * character.eye_height());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{Collider, RigidBody};
+
+    /// A world with a fixed box for every `(center, half_extents, rotation)`,
+    /// already in the simulation.
+    fn world(boxes: &[(glam::Vec3, glam::Vec3, glam::Quat)]) -> World {
+        let mut world = World::new();
+
+        world.insert_resource(Physics::new());
+
+        for &(center, half_extents, rotation) in boxes {
+            world
+                .spawn()
+                .with(Transform {
+                    rotation,
+                    ..Transform::from_translation(center)
+                })
+                .with(RigidBody::fixed())
+                .with(Collider::new(Shape::Box { half_extents }));
+        }
+
+        physics::step(&mut world);
+
+        world
+    }
+
+    /// With its top at zero.
+    fn floor() -> (glam::Vec3, glam::Vec3, glam::Quat) {
+        (
+            glam::Vec3::new(0.0, -0.5, 0.0),
+            glam::Vec3::new(20.0, 0.5, 20.0),
+            glam::Quat::IDENTITY,
+        )
+    }
+
+    /// Updates `character` for `ticks` and returns where it went through.
+    fn walk(
+        character: &mut CharacterController,
+        world: &World,
+        mut position: glam::Vec3,
+        control: Control,
+        ticks: usize,
+    ) -> Vec<glam::Vec3> {
+        let physics = world.resource::<Physics>();
+
+        (0..ticks)
+            .map(|_| {
+                position = character.update(&physics, position, &control);
+
+                position
+            })
+            .collect()
+    }
+
+    /// The height of the center of a character standing on the floor.
+    fn standing(character: &CharacterController) -> f32 {
+        character.height / 2.0
+    }
+
+    #[test]
+    fn gravity_pulls_down_until_the_ground() {
+        let world = world(&[floor()]);
+        let mut character = CharacterController::new();
+        let start = glam::Vec3::new(0.0, 5.0, 0.0);
+
+        let path = walk(&mut character, &world, start, Control::default(), 120);
+
+        // Falling from rest covers half of g t² in t seconds.
+        let after_half_a_second = path[29].y;
+        let expected = start.y + physics::GRAVITY.y * 0.5 * 0.5 * 0.5;
+
+        assert!(
+            (after_half_a_second - expected).abs() < 0.1,
+            "{after_half_a_second} after half a second, expected {expected}"
+        );
+
+        let end = *path.last().unwrap();
+
+        assert!((end.y - standing(&character)).abs() < 0.05, "{end}");
+        assert_eq!(end.x, start.x);
+        assert_eq!(end.z, start.z);
+    }
+
+    #[test]
+    fn ground_is_detected_only_when_standing() {
+        let world = world(&[floor()]);
+        let mut character = CharacterController::new();
+
+        walk(
+            &mut character,
+            &world,
+            glam::Vec3::new(0.0, 3.0, 0.0),
+            Control::default(),
+            1,
+        );
+        assert!(!character.grounded());
+
+        let start = glam::Vec3::new(0.0, standing(&character) + 0.01, 0.0);
+
+        walk(&mut character, &world, start, Control::default(), 1);
+        assert!(character.grounded());
+    }
+
+    #[test]
+    fn jumps_only_from_the_ground() {
+        let world = world(&[floor()]);
+        let mut character = CharacterController::new();
+        let start = glam::Vec3::new(0.0, standing(&character), 0.0);
+        let jump = Control {
+            jump: true,
+            ..Control::default()
+        };
+
+        // Lands first, so that the jump starts from the ground.
+        walk(&mut character, &world, start, Control::default(), 1);
+        assert!(character.grounded());
+
+        let path = walk(&mut character, &world, start, jump, 1);
+        let path = walk(&mut character, &world, path[0], Control::default(), 120);
+        let peak = path
+            .iter()
+            .map(|position| position.y)
+            .fold(f32::MIN, f32::max);
+        // v² / 2g above the start.
+        let expected = start.y + character.jump_speed.powi(2) / (-2.0 * physics::GRAVITY.y);
+
+        assert!(
+            (peak - expected).abs() < 0.1,
+            "peaked at {peak}, expected {expected}"
+        );
+        assert!((path.last().unwrap().y - start.y).abs() < 0.05);
+        assert!(character.grounded());
+
+        // Holding jump in the air does not jump again.
+        let mut character = CharacterController::new();
+        let path = walk(
+            &mut character,
+            &world,
+            glam::Vec3::new(0.0, 3.0, 0.0),
+            jump,
+            10,
+        );
+
+        assert!(path.windows(2).all(|pair| pair[1].y < pair[0].y));
+    }
+
+    #[test]
+    fn steps_up_ledges_up_to_the_step_height() {
+        let character = CharacterController::new();
+        let forward = Control {
+            direction: glam::Vec3::X,
+            ..Control::default()
+        };
+
+        for (height, climbed) in [
+            (character.step_height * 0.75, true),
+            (character.step_height * 1.5, false),
+        ] {
+            // A ledge from x = 2 on.
+            let ledge = (
+                glam::Vec3::new(7.0, height / 2.0, 0.0),
+                glam::Vec3::new(5.0, height / 2.0, 5.0),
+                glam::Quat::IDENTITY,
+            );
+            let world = world(&[floor(), ledge]);
+            let mut character = CharacterController::new();
+            let start = glam::Vec3::new(0.0, standing(&character), 0.0);
+
+            let end = *walk(&mut character, &world, start, forward, 60)
+                .last()
+                .unwrap();
+
+            if climbed {
+                assert!(end.x > 3.0, "stopped at {end} below a {height} ledge");
+                assert!((end.y - standing(&character) - height).abs() < 0.05);
+            } else {
+                assert!(end.x < 2.0, "went to {end} over a {height} ledge");
+                assert!((end.y - standing(&character)).abs() < 0.05);
+            }
+        }
+    }
+
+    #[test]
+    fn slides_down_slopes_steeper_than_the_max_slope() {
+        for (degrees, slides) in [(20f32, false), (60f32, true)] {
+            // Rising towards +x.
+            let ramp = (
+                glam::Vec3::ZERO,
+                glam::Vec3::new(10.0, 0.5, 5.0),
+                glam::Quat::from_rotation_z(degrees.to_radians()),
+            );
+            let world = world(&[ramp]);
+            let mut character = CharacterController::new();
+
+            // Lands on the ramp, then stands still for a second.
+            let landed = *walk(
+                &mut character,
+                &world,
+                glam::Vec3::new(0.0, 3.0, 0.0),
+                Control::default(),
+                60,
+            )
+            .last()
+            .unwrap();
+            let end = *walk(&mut character, &world, landed, Control::default(), 60)
+                .last()
+                .unwrap();
+
+            if slides {
+                assert!(end.y < landed.y - 0.5, "held at {end} on {degrees}°");
+                assert!(end.x < landed.x);
+            } else {
+                assert!(character.grounded());
+                assert!(
+                    end.distance(landed) < 0.05,
+                    "slid from {landed} to {end} on {degrees}°"
+                );
+            }
+        }
+    }
+}
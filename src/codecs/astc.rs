@@ -0,0 +1,1226 @@
+//! CPU decoding of 2D ASTC blocks, for adapters without ASTC texture
+//! compression, and encoding for the offline compress tool. Only the LDR
+//! profile is supported: blocks using HDR endpoint modes decode to the error
+//! color, as they do on LDR hardware.
+
+const BLOCK_BYTES: usize = 16;
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// Color endpoint ranges, from the most to the least precise. Blocks use the
+/// most precise one their endpoints fit into.
+const COLOR_RANGES: [u32; 17] = [
+    256, 192, 160, 128, 96, 80, 64, 48, 40, 32, 24, 20, 16, 12, 10, 8, 6,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Packing {
+    Bits,
+    Quints,
+    Trits,
+}
+
+struct BlockMode {
+    dual_plane: bool,
+    grid_height: u32,
+    grid_width: u32,
+    weight_range: u32,
+}
+
+/// The unquantized weights of a block, interleaved by plane.
+struct Weights<'a> {
+    mode: &'a BlockMode,
+    planes: u32,
+    values: Vec<u32>,
+}
+
+/// Chooses the block mode of every block of one size and endpoint mode, and
+/// encodes blocks with it.
+struct Encoder {
+    block_mode: u32,
+    color_range: u32,
+    endpoint_mode: u32,
+    /// How much each weight of the grid contributes to each texel, out of 16.
+    factors: Vec<Vec<u32>>,
+    mode: BlockMode,
+    /// The value that unquantizes closest to each 8 bit color.
+    quantized_colors: Vec<u32>,
+    /// The packed bits of every combination of five trits and three quints.
+    trits: Vec<u32>,
+    quints: Vec<u32>,
+}
+
+impl Encoder {
+    /// Picks the densest grid of weights with at least four levels that
+    /// leaves room for endpoints with at least 32 levels, preferring grids
+    /// shaped like the block.
+    fn new(block_width: u32, block_height: u32, endpoint_mode: u32) -> Self {
+        let color_count = ((endpoint_mode >> 2) + 1) * 2;
+        let (block_mode, mode, color_range) = (0..2048)
+            .filter_map(|bits| {
+                let mode = block_mode(bits)?;
+                let weight_count = mode.grid_width * mode.grid_height;
+                let weight_bits = ise_bits(weight_count, mode.weight_range);
+
+                if mode.dual_plane
+                    || weight_count > 64
+                    || !(24..=96).contains(&weight_bits)
+                    || mode.grid_width > block_width
+                    || mode.grid_height > block_height
+                    || mode.weight_range < 4
+                {
+                    return None;
+                }
+
+                let color_range = *COLOR_RANGES
+                    .iter()
+                    .find(|&&range| ise_bits(color_count, range) <= 128 - 17 - weight_bits)?;
+
+                (color_range >= 32).then_some((bits, mode, color_range))
+            })
+            .max_by_key(|(_, mode, _)| {
+                let shape = mode.grid_width * block_height;
+                let block_shape = mode.grid_height * block_width;
+
+                (
+                    mode.grid_width * mode.grid_height,
+                    mode.weight_range,
+                    std::cmp::Reverse(shape.abs_diff(block_shape)),
+                )
+            })
+            .expect("every block size fits a grid of weights and direct endpoints");
+
+        let texel_count = (block_width * block_height) as usize;
+        let factors = (0..(mode.grid_width * mode.grid_height) as usize)
+            .map(|point| {
+                let mut values = vec![0; (mode.grid_width * mode.grid_height) as usize];
+                values[point] = 16;
+
+                let weights = Weights {
+                    mode: &mode,
+                    planes: 1,
+                    values,
+                };
+
+                (0..texel_count as u32)
+                    .map(|texel| {
+                        weights.infill(
+                            0,
+                            block_width,
+                            block_height,
+                            texel % block_width,
+                            texel / block_width,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let nearest = |range: u32, target: u32, unquantize: fn(u32, u32) -> u32| {
+            (0..range)
+                .min_by_key(|&value| unquantize(value, range).abs_diff(target))
+                .unwrap()
+        };
+        let inverse = |codes: u32, digits: fn(u32) -> Vec<u32>, base: u32| {
+            let mut packed = vec![0; base.pow(digits(0).len() as u32) as usize];
+
+            // Counting down leaves the lowest code of the digits, which is the
+            // one whose bits past a truncated last group are zero.
+            for code in (0..codes).rev() {
+                let index = digits(code)
+                    .iter()
+                    .rev()
+                    .fold(0, |index, &digit| index * base + digit);
+
+                packed[index as usize] = code;
+            }
+
+            packed
+        };
+
+        Self {
+            block_mode,
+            color_range,
+            endpoint_mode,
+            factors,
+            quantized_colors: (0..256)
+                .map(|color| nearest(color_range, color, unquantize_color))
+                .collect(),
+            trits: inverse(256, |code| decode_trits(code).to_vec(), 3),
+            quints: inverse(128, |code| decode_quints(code).to_vec(), 5),
+            mode,
+        }
+    }
+
+    fn encode_block(&self, texels: &[[u8; 4]]) -> u128 {
+        if texels.iter().all(|texel| *texel == texels[0]) {
+            return void_extent_block(texels[0]);
+        }
+
+        let channels = if self.endpoint_mode == 8 { 3 } else { 4 };
+        let ends = super::principal_endpoints(texels);
+        let mut values: [[u32; 4]; 2] = ends.map(|end| {
+            end.to_array()
+                .map(|c| self.quantized_colors[c.round() as usize])
+        });
+
+        // Endpoints whose first color is brighter ask for blue contraction.
+        let sum = |values: &[u32; 4]| {
+            values[..3]
+                .iter()
+                .map(|&value| unquantize_color(value, self.color_range))
+                .sum::<u32>()
+        };
+
+        if sum(&values[1]) < sum(&values[0]) {
+            values.swap(0, 1);
+        }
+
+        let [e0, e1] = values
+            .map(|values| values.map(|value| unquantize_color(value, self.color_range) as f32));
+        let length = (0..channels).map(|c| (e1[c] - e0[c]).powi(2)).sum::<f32>();
+        let ideal: Vec<f32> = texels
+            .iter()
+            .map(|texel| {
+                let along = (0..channels)
+                    .map(|c| (texel[c] as f32 - e0[c]) * (e1[c] - e0[c]))
+                    .sum::<f32>();
+
+                if length > 0.0 {
+                    (along / length * 64.0).clamp(0.0, 64.0)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        // Each weight of the grid is the average of the texels it reaches.
+        let range = self.mode.weight_range;
+        let weights: Vec<u32> = self
+            .factors
+            .iter()
+            .map(|factors| {
+                let total = factors.iter().sum::<u32>().max(1) as f32;
+                let weight = factors
+                    .iter()
+                    .zip(&ideal)
+                    .map(|(&factor, &ideal)| factor as f32 * ideal)
+                    .sum::<f32>()
+                    / total;
+
+                (0..range)
+                    .min_by(|&a, &b| {
+                        let distance =
+                            |value| (unquantize_weight(value, range) as f32 - weight).abs();
+
+                        distance(a).total_cmp(&distance(b))
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        let colors: Vec<u32> = (0..channels)
+            .flat_map(|c| [values[0][c], values[1][c]])
+            .collect();
+
+        self.block_mode as u128
+            | (self.endpoint_mode as u128) << 13
+            | self.encode_ise(&colors, self.color_range) << 17
+            | self.encode_ise(&weights, range).reverse_bits()
+    }
+
+    /// Packs integers in `0..range` the way [`decode_ise`] reads them, from
+    /// bit 0 up.
+    fn encode_ise(&self, values: &[u32], range: u32) -> u128 {
+        let (packing, bits) = encoding(range);
+        let end = ise_bits(values.len() as u32, range);
+        let mut block = 0;
+        let mut position = 0;
+        let mut write = |value: u32, count: u32| {
+            // The padding of a truncated last group may run past the block.
+            if position < 128 {
+                block |= ((value & ((1 << count) - 1)) as u128) << position;
+            }
+
+            position += count;
+        };
+        let (group, base) = match packing {
+            Packing::Bits => (1, 1),
+            Packing::Quints => (3, 5),
+            Packing::Trits => (5, 3),
+        };
+
+        for values in values.chunks(group) {
+            let digits = values
+                .iter()
+                .rev()
+                .fold(0, |digits, &value| digits * base + (value >> bits));
+            let fields: &[(u32, u32)] = match packing {
+                Packing::Bits => &[(0, 0)],
+                Packing::Quints => &[(0, 3), (3, 2), (5, 2)],
+                Packing::Trits => &[(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)],
+            };
+            let packed = match packing {
+                Packing::Bits => 0,
+                Packing::Quints => self.quints[digits as usize],
+                Packing::Trits => self.trits[digits as usize],
+            };
+
+            for (i, &(shift, length)) in fields.iter().enumerate() {
+                write(values.get(i).copied().unwrap_or(0), bits);
+                write(packed >> shift, length);
+            }
+        }
+
+        block & (u128::MAX >> (128 - end))
+    }
+}
+
+/// Compresses tightly packed RGBA8 pixels into blocks with a single partition
+/// and direct RGB or RGBA endpoints, or a void extent for blocks of a single
+/// color. Pixels past the edge of the image are clamped to the last row or
+/// column.
+pub fn compress(
+    block_width: u32,
+    block_height: u32,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Vec<u8> {
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let blocks_high = (height + block_height - 1) / block_height;
+    let opaque = Encoder::new(block_width, block_height, 8);
+    let transparent = Encoder::new(block_width, block_height, 12);
+    let mut data = Vec::with_capacity((blocks_wide * blocks_high) as usize * BLOCK_BYTES);
+
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let texels: Vec<[u8; 4]> = (0..block_width * block_height)
+                .map(|i| {
+                    let x = (block_x * block_width + i % block_width).min(width - 1);
+                    let y = (block_y * block_height + i / block_width).min(height - 1);
+                    let offset = ((y * width + x) * 4) as usize;
+
+                    rgba[offset..offset + 4].try_into().unwrap()
+                })
+                .collect();
+            let encoder = if texels.iter().all(|texel| texel[3] == 255) {
+                &opaque
+            } else {
+                &transparent
+            };
+
+            data.extend(encoder.encode_block(&texels).to_le_bytes());
+        }
+    }
+
+    data
+}
+
+/// Decompresses into tightly packed RGBA8 pixels. sRGB blocks are expanded
+/// the way sRGB hardware does, which only affects rounding.
+pub fn decompress(
+    block_width: u32,
+    block_height: u32,
+    srgb: bool,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Vec<u8> {
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let mut rgba = vec![0; (width * height * 4) as usize];
+
+    for (block_index, block) in data.chunks_exact(BLOCK_BYTES).enumerate() {
+        let block_x = block_index as u32 % blocks_wide;
+        let block_y = block_index as u32 / blocks_wide;
+        let block = u128::from_le_bytes(block.try_into().unwrap());
+        let texels = decode_block(block, block_width, block_height, srgb)
+            .unwrap_or_else(|| vec![ERROR_COLOR; (block_width * block_height) as usize]);
+
+        for (i, texel) in texels.iter().enumerate() {
+            let x = block_x * block_width + i as u32 % block_width;
+            let y = block_y * block_height + i as u32 / block_width;
+
+            if x < width && y < height {
+                let offset = ((y * width + x) * 4) as usize;
+                rgba[offset..offset + 4].copy_from_slice(texel);
+            }
+        }
+    }
+
+    rgba
+}
+
+/// Returns `None` for blocks that decode to the error color.
+fn decode_block(
+    block: u128,
+    block_width: u32,
+    block_height: u32,
+    srgb: bool,
+) -> Option<Vec<[u8; 4]>> {
+    let texel_count = (block_width * block_height) as usize;
+
+    if read_bits(block, 0, 9) == 0x1fc {
+        return void_extent(block).map(|color| vec![color; texel_count]);
+    }
+
+    let mode = block_mode(read_bits(block, 0, 11))?;
+    let partition_count = read_bits(block, 11, 2) + 1;
+    let planes = if mode.dual_plane { 2 } else { 1 };
+    let weight_count = mode.grid_width * mode.grid_height * planes;
+    let weight_bits = ise_bits(weight_count, mode.weight_range);
+
+    if weight_count > 64
+        || !(24..=96).contains(&weight_bits)
+        || mode.grid_width > block_width
+        || mode.grid_height > block_height
+        || (partition_count == 4 && mode.dual_plane)
+    {
+        return None;
+    }
+
+    let weights_start = 128 - weight_bits;
+    let mut partition_seed = 0;
+    let mut endpoint_modes = [0; 4];
+    let mut extra_bits = 0;
+    let color_start;
+
+    if partition_count == 1 {
+        endpoint_modes[0] = read_bits(block, 13, 4);
+        color_start = 17;
+    } else {
+        partition_seed = read_bits(block, 13, 10);
+        let field = read_bits(block, 23, 6);
+
+        if field & 3 == 0 {
+            endpoint_modes = [field >> 2; 4];
+        } else {
+            // Per partition modes don't fit into the field, the rest of them
+            // sits right below the weights.
+            extra_bits = 3 * partition_count - 4;
+            let bits =
+                (field >> 2) | (read_bits(block, weights_start - extra_bits, extra_bits) << 4);
+            let base_class = (field & 3) - 1;
+
+            for (partition, endpoint_mode) in endpoint_modes[..partition_count as usize]
+                .iter_mut()
+                .enumerate()
+            {
+                let class = base_class + ((bits >> partition) & 1);
+                let offset = (bits >> (partition_count as usize + 2 * partition)) & 3;
+
+                *endpoint_mode = (class << 2) | offset;
+            }
+        }
+
+        color_start = 29;
+    }
+
+    let endpoint_modes = &endpoint_modes[..partition_count as usize];
+    let color_end =
+        (weights_start - extra_bits).checked_sub(if mode.dual_plane { 2 } else { 0 })?;
+    let dual_plane_channel = mode
+        .dual_plane
+        .then(|| read_bits(block, color_end, 2) as usize);
+
+    let color_count: u32 = endpoint_modes
+        .iter()
+        .map(|mode| ((mode >> 2) + 1) * 2)
+        .sum();
+    let color_bits = color_end.checked_sub(color_start)?;
+
+    if color_count > 18 {
+        return None;
+    }
+
+    let color_range = *COLOR_RANGES
+        .iter()
+        .find(|&&range| ise_bits(color_count, range) <= color_bits)?;
+    let colors: Vec<u32> = decode_ise(block, color_start, color_count, color_range)
+        .into_iter()
+        .map(|value| unquantize_color(value, color_range))
+        .collect();
+
+    let mut endpoints = Vec::with_capacity(partition_count as usize);
+    let mut colors = colors.as_slice();
+
+    for &endpoint_mode in endpoint_modes {
+        let (values, rest) = colors.split_at((((endpoint_mode >> 2) + 1) * 2) as usize);
+
+        endpoints.push(decode_endpoints(endpoint_mode, values)?);
+        colors = rest;
+    }
+
+    // Weights are stored backwards from the top of the block.
+    let weights = Weights {
+        mode: &mode,
+        planes,
+        values: decode_ise(block.reverse_bits(), 0, weight_count, mode.weight_range)
+            .into_iter()
+            .map(|value| unquantize_weight(value, mode.weight_range))
+            .collect(),
+    };
+
+    let small_block = texel_count < 31;
+
+    Some(
+        (0..texel_count as u32)
+            .map(|texel| {
+                let (x, y) = (texel % block_width, texel / block_width);
+                let partition = if partition_count == 1 {
+                    0
+                } else {
+                    select_partition(partition_seed, x, y, partition_count, small_block)
+                };
+                let [e0, e1] = endpoints[partition];
+                let weight = |plane| weights.infill(plane, block_width, block_height, x, y);
+                let (weight0, weight1) = (weight(0), weight(planes - 1));
+
+                std::array::from_fn(|channel| {
+                    let weight = if dual_plane_channel == Some(channel) {
+                        weight1
+                    } else {
+                        weight0
+                    };
+
+                    interpolate(e0[channel], e1[channel], weight, srgb)
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Blocks of a single color, stored as UNORM16. HDR ones are errors in the
+/// LDR profile, and so are empty extents. Coordinates of all ones mean that
+/// there is no extent.
+fn void_extent(block: u128) -> Option<[u8; 4]> {
+    if read_bits(block, 9, 1) != 0 || read_bits(block, 10, 2) != 3 {
+        return None;
+    }
+
+    let [s_low, s_high, t_low, t_high]: [u32; 4] =
+        std::array::from_fn(|i| read_bits(block, 12 + 13 * i as u32, 13));
+
+    if [s_low, s_high, t_low, t_high] != [0x1fff; 4] && (s_low >= s_high || t_low >= t_high) {
+        return None;
+    }
+
+    Some(std::array::from_fn(|channel| {
+        (read_bits(block, 64 + 16 * channel as u32, 16) >> 8) as u8
+    }))
+}
+
+/// A block of one color, without an extent.
+fn void_extent_block(color: [u8; 4]) -> u128 {
+    let color = color.iter().enumerate().fold(0, |block, (channel, &c)| {
+        block | (c as u128 * 257) << (16 * channel)
+    });
+
+    0x1fc | (3 << 10) | (((1 << 52) - 1) << 12) | (color << 64)
+}
+
+/// Decodes the size of the weight grid, whether it has a second plane and the
+/// range of the weights from the first 11 bits of a block.
+fn block_mode(mode: u32) -> Option<BlockMode> {
+    let bit = |index: u32| (mode >> index) & 1;
+    let a = (mode >> 5) & 3;
+    let b = (mode >> 7) & 3;
+
+    let (range_index, grid_width, grid_height, high_precision, dual_plane) = if mode & 3 != 0 {
+        let (width, height) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bit(8) == 0 => (a + 2, bit(7) + 6),
+            _ => (bit(7) + 2, a + 2),
+        };
+
+        (bit(4) | ((mode & 3) << 1), width, height, bit(9), bit(10))
+    } else {
+        let range_index = bit(4) | (((mode >> 2) & 3) << 1);
+
+        match b {
+            0 => (range_index, 12, a + 2, bit(9), bit(10)),
+            1 => (range_index, a + 2, 12, bit(9), bit(10)),
+            2 => (range_index, a + 6, ((mode >> 9) & 3) + 6, 0, 0),
+            _ => match a {
+                0 => (range_index, 6, 10, bit(9), bit(10)),
+                1 => (range_index, 10, 6, bit(9), bit(10)),
+                _ => return None,
+            },
+        }
+    };
+
+    if range_index < 2 {
+        return None;
+    }
+
+    let ranges = if high_precision == 1 {
+        [10, 12, 16, 20, 24, 32]
+    } else {
+        [2, 3, 4, 5, 6, 8]
+    };
+
+    Some(BlockMode {
+        dual_plane: dual_plane == 1,
+        grid_height,
+        grid_width,
+        weight_range: ranges[range_index as usize - 2],
+    })
+}
+
+/// Turns the integers of an endpoint mode into two RGBA endpoints.
+fn decode_endpoints(mode: u32, values: &[u32]) -> Option<[[u32; 4]; 2]> {
+    let mut v: [i32; 8] = [0; 8];
+    for (v, &value) in v.iter_mut().zip(values) {
+        *v = value as i32;
+    }
+
+    let endpoints = match mode {
+        // Luminance.
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = (l0 + (v[1] & 0x3f)).min(255);
+
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        // Luminance and alpha.
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            bit_transfer_signed(&mut v, 1, 0);
+            bit_transfer_signed(&mut v, 3, 2);
+
+            [
+                [v[0], v[0], v[0], v[2]],
+                [v[0] + v[1], v[0] + v[1], v[0] + v[1], v[2] + v[3]],
+            ]
+        }
+        // RGB scaled down for the first endpoint.
+        6 => {
+            let scaled = |c: i32| (c * v[3]) >> 8;
+
+            [
+                [scaled(v[0]), scaled(v[1]), scaled(v[2]), 255],
+                [v[0], v[1], v[2], 255],
+            ]
+        }
+        10 => {
+            let scaled = |c: i32| (c * v[3]) >> 8;
+
+            [
+                [scaled(v[0]), scaled(v[1]), scaled(v[2]), v[4]],
+                [v[0], v[1], v[2], v[5]],
+            ]
+        }
+        // RGB(A), where endpoints in the wrong order signal blue contraction.
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract([v[1], v[3], v[5], a1]),
+                    blue_contract([v[0], v[2], v[4], a0]),
+                ]
+            }
+        }
+        // RGB(A) base and offset.
+        9 | 13 => {
+            for channel in 0..4 {
+                bit_transfer_signed(&mut v, channel * 2 + 1, channel * 2);
+            }
+
+            let (a0, a1) = if mode == 13 {
+                (v[6], v[6] + v[7])
+            } else {
+                (255, 255)
+            };
+            let base = [v[0], v[2], v[4], a0];
+            let offset = [v[0] + v[1], v[2] + v[3], v[4] + v[5], a1];
+
+            if v[1] + v[3] + v[5] >= 0 {
+                [base, offset]
+            } else {
+                [blue_contract(offset), blue_contract(base)]
+            }
+        }
+        // HDR modes.
+        _ => return None,
+    };
+
+    Some(endpoints.map(|endpoint| endpoint.map(|channel| channel.clamp(0, 255) as u32)))
+}
+
+/// Moves the top bit of `v[offset]` into `v[base]` and sign extends the rest.
+fn bit_transfer_signed(v: &mut [i32; 8], offset: usize, base: usize) {
+    v[base] = (v[base] >> 1) | (v[offset] & 0x80);
+    v[offset] = (v[offset] >> 1) & 0x3f;
+
+    if v[offset] & 0x20 != 0 {
+        v[offset] -= 0x40;
+    }
+}
+
+fn blue_contract([r, g, b, a]: [i32; 4]) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// Number of bits taken by `count` values in `0..range`.
+fn ise_bits(count: u32, range: u32) -> u32 {
+    let (packing, bits) = encoding(range);
+
+    count * bits
+        + match packing {
+            Packing::Bits => 0,
+            Packing::Quints => (7 * count + 2) / 3,
+            Packing::Trits => (8 * count + 4) / 5,
+        }
+}
+
+/// Every range is a power of two, or three or five times one.
+fn encoding(range: u32) -> (Packing, u32) {
+    if range % 3 == 0 {
+        (Packing::Trits, (range / 3).trailing_zeros())
+    } else if range % 5 == 0 {
+        (Packing::Quints, (range / 5).trailing_zeros())
+    } else {
+        (Packing::Bits, range.trailing_zeros())
+    }
+}
+
+/// Decodes `count` integers in `0..range` from the integer sequence encoding
+/// starting at bit `start`. Trits and quints are packed in groups of five
+/// and three, with their bits interleaved between the plain bits of each
+/// value; missing bits of a truncated last group are zero.
+fn decode_ise(block: u128, start: u32, count: u32, range: u32) -> Vec<u32> {
+    let (packing, bits) = encoding(range);
+    let end = start + ise_bits(count, range);
+    let mut position = start;
+    let mut read = |count: u32| {
+        let value = if position < end {
+            read_bits(block, position, count.min(end - position))
+        } else {
+            0
+        };
+
+        position += count;
+
+        value
+    };
+
+    let mut values = Vec::with_capacity(count as usize);
+
+    while values.len() < count as usize {
+        match packing {
+            Packing::Bits => values.push(read(bits)),
+            Packing::Quints => {
+                let mut m = [0; 3];
+                let mut q = 0;
+
+                for (i, shift, length) in [(0, 0, 3), (1, 3, 2), (2, 5, 2)] {
+                    m[i] = read(bits);
+                    q |= read(length) << shift;
+                }
+
+                for (quint, m) in decode_quints(q).into_iter().zip(m) {
+                    values.push((quint << bits) | m);
+                }
+            }
+            Packing::Trits => {
+                let mut m = [0; 5];
+                let mut t = 0;
+
+                for (i, shift, length) in [(0, 0, 2), (1, 2, 2), (2, 4, 1), (3, 5, 2), (4, 7, 1)] {
+                    m[i] = read(bits);
+                    t |= read(length) << shift;
+                }
+
+                for (trit, m) in decode_trits(t).into_iter().zip(m) {
+                    values.push((trit << bits) | m);
+                }
+            }
+        }
+    }
+
+    values.truncate(count as usize);
+
+    values
+}
+
+/// Unpacks five base 3 digits from 8 bits.
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |value: u32, index: u32| (value >> index) & 1;
+
+    let (c, t3, t4) = if (t >> 2) & 7 == 7 {
+        ((((t >> 5) & 7) << 2) | (t & 3), 2, 2)
+    } else if (t >> 5) & 3 == 3 {
+        (t & 0x1f, bit(t, 7), 2)
+    } else {
+        (t & 0x1f, (t >> 5) & 3, bit(t, 7))
+    };
+
+    let (t0, t1, t2) = if c & 3 == 3 {
+        (
+            (bit(c, 3) << 1) | (bit(c, 2) & !bit(c, 3) & 1),
+            bit(c, 4),
+            2,
+        )
+    } else if (c >> 2) & 3 == 3 {
+        (c & 3, 2, 2)
+    } else {
+        (
+            (bit(c, 1) << 1) | (bit(c, 0) & !bit(c, 1) & 1),
+            (c >> 2) & 3,
+            bit(c, 4),
+        )
+    };
+
+    [t0, t1, t2, t3, t4]
+}
+
+/// Unpacks three base 5 digits from 7 bits.
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |index: u32| (q >> index) & 1;
+
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let q2 = (bit(0) << 2) | ((bit(4) & !bit(0) & 1) << 1) | (bit(3) & !bit(0) & 1);
+
+        return [4, 4, q2];
+    }
+
+    let (c, q2) = if (q >> 1) & 3 == 3 {
+        ((((q >> 3) & 3) << 3) | ((!q >> 5) & 3) << 1 | bit(0), 4)
+    } else {
+        (q & 0x1f, (q >> 5) & 3)
+    };
+
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+/// Scales an integer in `0..range` to `0..=255`.
+fn unquantize_color(value: u32, range: u32) -> u32 {
+    let (packing, bits) = encoding(range);
+
+    if packing == Packing::Bits {
+        return replicate(value, bits, 8);
+    }
+
+    let digit = value >> bits;
+    let a = if value & 1 == 1 { 0x1ff } else { 0 };
+    let x = (value & ((1 << bits) - 1)) >> 1;
+
+    let (b, c) = match (packing, bits) {
+        (Packing::Trits, 1) => (0, 204),
+        (Packing::Trits, 2) => ((x << 8) | (x << 4) | (x << 2) | (x << 1), 93),
+        (Packing::Trits, 3) => ((x << 7) | (x << 2) | x, 44),
+        (Packing::Trits, 4) => ((x << 6) | x, 22),
+        (Packing::Trits, 5) => ((x << 5) | (x >> 2), 11),
+        (Packing::Trits, _) => ((x << 4) | (x >> 4), 5),
+        (Packing::Quints, 1) => (0, 113),
+        (Packing::Quints, 2) => ((x << 8) | (x << 3) | (x << 2), 54),
+        (Packing::Quints, 3) => ((x << 7) | (x << 1) | (x >> 1), 26),
+        (Packing::Quints, 4) => ((x << 6) | (x >> 1), 13),
+        (_, _) => ((x << 5) | (x >> 3), 6),
+    };
+
+    let t = (digit * c + b) ^ a;
+
+    (a & 0x80) | (t >> 2)
+}
+
+/// Scales an integer in `0..range` to `0..=64`.
+fn unquantize_weight(value: u32, range: u32) -> u32 {
+    let (packing, bits) = encoding(range);
+
+    let weight = match (packing, bits) {
+        (Packing::Bits, _) => replicate(value, bits, 6),
+        (Packing::Trits, 0) => [0, 32, 63][value as usize],
+        (Packing::Quints, 0) => [0, 16, 32, 47, 63][value as usize],
+        _ => {
+            let digit = value >> bits;
+            let a = if value & 1 == 1 { 0x7f } else { 0 };
+            let x = (value & ((1 << bits) - 1)) >> 1;
+
+            let (b, c) = match (packing, bits) {
+                (Packing::Trits, 1) => (0, 50),
+                (Packing::Trits, 2) => ((x << 6) | (x << 2) | x, 23),
+                (Packing::Trits, _) => ((x << 5) | x, 11),
+                (Packing::Quints, 1) => (0, 28),
+                (_, _) => ((x << 6) | (x << 1), 13),
+            };
+
+            let t = (digit * c + b) ^ a;
+
+            (a & 0x20) | (t >> 2)
+        }
+    };
+
+    if weight > 32 {
+        weight + 1
+    } else {
+        weight
+    }
+}
+
+/// Repeats a `bits` wide value until it fills `width` bits.
+fn replicate(value: u32, bits: u32, width: u32) -> u32 {
+    let mut result = 0;
+    let mut filled = 0;
+
+    while filled < width {
+        result = (result << bits) | value;
+        filled += bits;
+    }
+
+    result >> (filled - width)
+}
+
+impl Weights<'_> {
+    /// Bilinearly samples a plane of the grid at a texel of the block.
+    fn infill(&self, plane: u32, block_width: u32, block_height: u32, x: u32, y: u32) -> u32 {
+        let grid_width = self.mode.grid_width;
+        let scale_x = (1024 + block_width / 2) / (block_width - 1);
+        let scale_y = (1024 + block_height / 2) / (block_height - 1);
+        let grid_x = (scale_x * x * (grid_width - 1) + 32) >> 6;
+        let grid_y = (scale_y * y * (self.mode.grid_height - 1) + 32) >> 6;
+        let (fraction_x, fraction_y) = (grid_x & 0xf, grid_y & 0xf);
+        let first = (grid_x >> 4) + (grid_y >> 4) * grid_width;
+
+        let w11 = (fraction_x * fraction_y + 8) >> 4;
+        let w10 = fraction_y - w11;
+        let w01 = fraction_x - w11;
+        let w00 = 16 + w11 - fraction_x - fraction_y;
+
+        // Points past the edge of the grid always have a factor of zero.
+        let point = |index: u32| {
+            self.values
+                .get((index * self.planes + plane) as usize)
+                .copied()
+                .unwrap_or(0)
+        };
+
+        (point(first) * w00
+            + point(first + 1) * w01
+            + point(first + grid_width) * w10
+            + point(first + grid_width + 1) * w11
+            + 8)
+            >> 4
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32, srgb: bool) -> u8 {
+    let expand = |e: u32| if srgb { (e << 8) | 0x80 } else { e * 257 };
+    let value = (expand(e0) * (64 - weight) + expand(e1) * weight + 32) >> 6;
+
+    (value >> 8) as u8
+}
+
+fn read_bits(block: u128, start: u32, count: u32) -> u32 {
+    if start >= 128 {
+        return 0;
+    }
+
+    ((block >> start) as u64 & ((1u64 << count) - 1)) as u32
+}
+
+/// The hash the format uses to assign texels to partitions.
+fn select_partition(seed: u32, x: u32, y: u32, partition_count: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partition_count - 1) * 1024;
+    let random = hash(seed);
+
+    let mut seeds: [u32; 8] = std::array::from_fn(|i| {
+        let value = (random >> (4 * i)) & 0xf;
+
+        value * value
+    });
+
+    let (shift1, shift2) = if seed & 1 != 0 {
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partition_count == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partition_count == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+
+    for (i, seed) in seeds.iter_mut().enumerate() {
+        *seed >>= if i % 2 == 0 { shift1 } else { shift2 };
+    }
+
+    // The z seeds drop out, 2D blocks have z = 0.
+    let a = (seeds[0] * x + seeds[1] * y + (random >> 14)) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y + (random >> 10)) & 0x3f;
+    let c = if partition_count < 3 {
+        0
+    } else {
+        (seeds[4] * x + seeds[5] * y + (random >> 6)) & 0x3f
+    };
+    let d = if partition_count < 4 {
+        0
+    } else {
+        (seeds[6] * x + seeds[7] * y + (random >> 2)) & 0x3f
+    };
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+fn hash(seed: u32) -> u32 {
+    let mut p = seed;
+
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::tests::{gradient, hex, reference};
+
+    /// Builds a block from `(value, bit count)` fields, from bit 0 up.
+    fn block(fields: &[(u128, u32)]) -> u128 {
+        let mut block = 0;
+        let mut position = 0;
+
+        for &(value, bits) in fields {
+            block |= value << position;
+            position += bits;
+        }
+
+        block
+    }
+
+    #[test]
+    fn every_trit_and_quint_combination_can_be_encoded() {
+        let mut trits = std::collections::HashSet::new();
+        let mut quints = std::collections::HashSet::new();
+
+        for t in 0..256 {
+            let digits = decode_trits(t);
+            assert!(digits.iter().all(|&digit| digit < 3));
+            trits.insert(digits);
+        }
+        for q in 0..128 {
+            let digits = decode_quints(q);
+            assert!(digits.iter().all(|&digit| digit < 5));
+            quints.insert(digits);
+        }
+
+        assert_eq!(trits.len(), 243);
+        assert_eq!(quints.len(), 125);
+    }
+
+    #[test]
+    fn unquantization_matches_the_specification_tables() {
+        let colors = |range| {
+            (0..range)
+                .map(|v| unquantize_color(v, range))
+                .collect::<Vec<_>>()
+        };
+        let weights = |range| {
+            (0..range)
+                .map(|v| unquantize_weight(v, range))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(colors(6), [0, 255, 51, 204, 102, 153]);
+        assert_eq!(
+            colors(12),
+            [0, 255, 69, 186, 23, 232, 92, 163, 46, 209, 116, 139]
+        );
+        assert_eq!(colors(8), [0, 36, 73, 109, 146, 182, 219, 255]);
+        assert_eq!(weights(3), [0, 32, 64]);
+        assert_eq!(weights(4), [0, 21, 43, 64]);
+        assert_eq!(weights(5), [0, 16, 32, 48, 64]);
+        assert_eq!(weights(6), [0, 64, 12, 52, 25, 39]);
+    }
+
+    #[test]
+    fn void_extent_blocks_are_one_color() {
+        let block = block(&[
+            (0x1fc, 9),
+            (0, 1),
+            (3, 2),
+            (u128::MAX >> 76, 52),
+            (0x1234, 16),
+            (0x5678, 16),
+            (0x9abc, 16),
+            (0xffff, 16),
+        ]);
+
+        assert_eq!(
+            decode_block(block, 4, 4, false),
+            Some(vec![[0x12, 0x56, 0x9a, 0xff]; 16])
+        );
+    }
+
+    #[test]
+    fn hdr_void_extent_blocks_are_errors() {
+        let block = block(&[(0x1fc, 9), (1, 1), (3, 2)]);
+
+        assert_eq!(decode_block(block, 4, 4, false), None);
+    }
+
+    #[test]
+    fn weights_interpolate_between_direct_rgb_endpoints() {
+        // A 4x4 grid of 2 bit weights: weight range 4 without high precision,
+        // so R = 0b100, with A = 2 and B = 0.
+        let mode = (1 << 1) | (2 << 5);
+        let weights = (0..16u128).fold(0, |weights, texel| weights | ((texel % 4) << (2 * texel)));
+        let mut block = block(&[
+            (mode, 11),
+            (0, 2),
+            (8, 4),
+            // The endpoints, red, green and blue of each.
+            (0, 8),
+            (255, 8),
+            (0, 8),
+            (255, 8),
+            (0, 8),
+            (255, 8),
+        ]);
+
+        // Weights are stored from the top bit down.
+        block |= weights.reverse_bits();
+
+        let texels = decode_block(block, 4, 4, false).unwrap();
+
+        for (texel, color) in texels.iter().enumerate() {
+            let value = [0, 84, 171, 255][texel % 4];
+
+            assert_eq!(*color, [value, value, value, 255]);
+        }
+    }
+
+    #[test]
+    fn reserved_block_modes_are_errors() {
+        assert_eq!(decode_block(0, 4, 4, false), None);
+    }
+
+    #[test]
+    fn partitions_stay_in_range() {
+        for partition_count in 2..=4 {
+            for seed in 0..1024 {
+                for texel in 0..16 {
+                    let partition =
+                        select_partition(seed, texel % 4, texel / 4, partition_count, true);
+
+                    assert!(partition < partition_count as usize);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blocks_decode_like_a_reference_decoder() {
+        for fields in reference(include_str!("testdata/astc.txt")) {
+            let (width, height) = fields[0].split_once('x').unwrap();
+            let (width, height) = (width.parse().unwrap(), height.parse().unwrap());
+            let srgb = fields[1] == "srgb";
+
+            assert_eq!(
+                decompress(width, height, srgb, width, height, &hex(fields[2])),
+                hex(fields[3]),
+                "{} {} block {}",
+                fields[0],
+                fields[1],
+                fields[2]
+            );
+        }
+    }
+
+    #[test]
+    fn integer_sequences_round_trip() {
+        let encoder = Encoder::new(4, 4, 8);
+
+        for range in COLOR_RANGES.into_iter().chain([2, 3, 5]) {
+            for count in (1..=20).filter(|&count| ise_bits(count, range) < 128) {
+                let values: Vec<u32> = (0..count).map(|i| (i * 7 + 3) % range).collect();
+                let block = encoder.encode_ise(&values, range);
+
+                assert!(block >> ise_bits(count, range) == 0);
+                assert_eq!(decode_ise(block, 0, count, range), values, "range {range}");
+            }
+        }
+    }
+
+    #[test]
+    fn blocks_of_one_color_are_void_extents() {
+        let rgba = [12, 34, 56, 78].repeat(36);
+        let data = compress(6, 6, 6, 6, &rgba);
+        let block = u128::from_le_bytes(data.try_into().unwrap());
+
+        assert_eq!(read_bits(block, 0, 9), 0x1fc);
+        assert_eq!(
+            decode_block(block, 6, 6, false),
+            Some(vec![[12, 34, 56, 78]; 36])
+        );
+    }
+
+    #[test]
+    fn compressed_gradients_decompress_close_to_the_original() {
+        let (width, height) = (38, 27);
+        let rgba = gradient(width, height);
+
+        for (block_width, block_height) in [
+            (4, 4),
+            (5, 4),
+            (5, 5),
+            (6, 5),
+            (6, 6),
+            (8, 5),
+            (8, 6),
+            (8, 8),
+            (10, 5),
+            (10, 6),
+            (10, 8),
+            (10, 10),
+            (12, 10),
+            (12, 12),
+        ] {
+            let data = compress(block_width, block_height, width, height, &rgba);
+            let decompressed = decompress(block_width, block_height, false, width, height, &data);
+            // Larger blocks have coarser grids of weights.
+            let tolerance = 2 * block_width.max(block_height) as u8;
+
+            for (pixel, original) in decompressed.chunks_exact(4).zip(rgba.chunks_exact(4)) {
+                for c in 0..4 {
+                    assert!(
+                        pixel[c].abs_diff(original[c]) <= tolerance,
+                        "{block_width}x{block_height} turned {original:?} into {pixel:?}"
+                    );
+                }
+            }
+        }
+    }
+}
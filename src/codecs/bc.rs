@@ -0,0 +1,294 @@
+//! CPU encoding and decoding of the BC1, BC3, BC4 and BC5 block formats. The
+//! encoder favours speed over quality: endpoints are the corners of each
+//! block's bounding box rather than the result of a principal axis search.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Bc1,
+    Bc3,
+    Bc4,
+    Bc5,
+}
+
+impl Format {
+    pub fn block_bytes(self) -> usize {
+        match self {
+            Format::Bc1 | Format::Bc4 => 8,
+            Format::Bc3 | Format::Bc5 => 16,
+        }
+    }
+
+    /// Size of a `width` x `height` image in this format.
+    pub fn bytes(self, width: u32, height: u32) -> usize {
+        (blocks(width) * blocks(height)) as usize * self.block_bytes()
+    }
+}
+
+const BLOCK_DIMENSION: u32 = 4;
+
+fn blocks(pixels: u32) -> u32 {
+    (pixels + BLOCK_DIMENSION - 1) / BLOCK_DIMENSION
+}
+
+/// Compresses tightly packed RGBA8 pixels. Pixels past the edge of the image
+/// are clamped to the last row or column.
+pub fn compress(format: Format, width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(format.bytes(width, height));
+
+    for block_y in 0..blocks(height) {
+        for block_x in 0..blocks(width) {
+            let pixels: [[u8; 4]; 16] = std::array::from_fn(|i| {
+                let x = (block_x * BLOCK_DIMENSION + i as u32 % 4).min(width - 1);
+                let y = (block_y * BLOCK_DIMENSION + i as u32 / 4).min(height - 1);
+                let offset = ((y * width + x) * 4) as usize;
+
+                rgba[offset..offset + 4].try_into().unwrap()
+            });
+            let channel = |c: usize| std::array::from_fn(|i| pixels[i][c]);
+
+            match format {
+                Format::Bc1 => data.extend(encode_color_block(&pixels)),
+                Format::Bc3 => {
+                    data.extend(encode_alpha_block(&channel(3)));
+                    data.extend(encode_color_block(&pixels));
+                }
+                Format::Bc4 => data.extend(encode_alpha_block(&channel(0))),
+                Format::Bc5 => {
+                    data.extend(encode_alpha_block(&channel(0)));
+                    data.extend(encode_alpha_block(&channel(1)));
+                }
+            }
+        }
+    }
+
+    data
+}
+
+/// Decompresses into tightly packed RGBA8 pixels. Single and dual channel
+/// formats leave the missing color channels at zero, like sampling an R8 or
+/// RG8 texture would.
+pub fn decompress(format: Format, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let mut rgba = vec![0; (width * height * 4) as usize];
+
+    for (block_index, block) in data.chunks_exact(format.block_bytes()).enumerate() {
+        let block_x = block_index as u32 % blocks(width);
+        let block_y = block_index as u32 / blocks(width);
+
+        let pixels = match format {
+            Format::Bc1 => decode_color_block(block, false),
+            Format::Bc3 => {
+                let alpha = decode_alpha_block(&block[..8]);
+                let mut pixels = decode_color_block(&block[8..], true);
+
+                for (pixel, alpha) in pixels.iter_mut().zip(alpha) {
+                    pixel[3] = alpha;
+                }
+
+                pixels
+            }
+            Format::Bc4 => decode_alpha_block(block).map(|r| [r, 0, 0, 255]),
+            Format::Bc5 => {
+                let r = decode_alpha_block(&block[..8]);
+                let g = decode_alpha_block(&block[8..]);
+
+                std::array::from_fn(|i| [r[i], g[i], 0, 255])
+            }
+        };
+
+        for (i, pixel) in pixels.iter().enumerate() {
+            let x = block_x * BLOCK_DIMENSION + i as u32 % 4;
+            let y = block_y * BLOCK_DIMENSION + i as u32 / 4;
+
+            if x < width && y < height {
+                let offset = ((y * width + x) * 4) as usize;
+                rgba[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    rgba
+}
+
+fn alpha_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let mix = |w0: u32, w1: u32, d: u32| ((a0 as u32 * w0 + a1 as u32 * w1) / d) as u8;
+
+    if a0 > a1 {
+        [
+            a0,
+            a1,
+            mix(6, 1, 7),
+            mix(5, 2, 7),
+            mix(4, 3, 7),
+            mix(3, 4, 7),
+            mix(2, 5, 7),
+            mix(1, 6, 7),
+        ]
+    } else {
+        [
+            a0,
+            a1,
+            mix(4, 1, 5),
+            mix(3, 2, 5),
+            mix(2, 3, 5),
+            mix(1, 4, 5),
+            0,
+            255,
+        ]
+    }
+}
+
+/// BC2 and BC3 color blocks always use the four color mode, BC1 blocks use
+/// the three color and transparent black mode when `c0 <= c1`.
+fn color_palette(c0: u16, c1: u16, four_colors: bool) -> [[u8; 4]; 4] {
+    let color0 = from_565(c0);
+    let color1 = from_565(c1);
+    let mix = |w0: u32, w1: u32, d: u32| -> [u8; 4] {
+        let [r, g, b] =
+            std::array::from_fn(|i| ((color0[i] as u32 * w0 + color1[i] as u32 * w1) / d) as u8);
+
+        [r, g, b, 255]
+    };
+
+    if four_colors || c0 > c1 {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0; 4]]
+    }
+}
+
+fn decode_alpha_block(block: &[u8]) -> [u8; 16] {
+    let palette = alpha_palette(block[0], block[1]);
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
+
+fn decode_color_block(block: &[u8], four_colors: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = color_palette(c0, c1, four_colors);
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+
+    std::array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+}
+
+fn encode_alpha_block(values: &[u8; 16]) -> [u8; 8] {
+    let a0 = *values.iter().max().unwrap();
+    let a1 = *values.iter().min().unwrap();
+
+    let mut block = [a0, a1, 0, 0, 0, 0, 0, 0];
+
+    if a0 == a1 {
+        return block;
+    }
+
+    let palette = alpha_palette(a0, a1);
+    let indices = values
+        .iter()
+        .enumerate()
+        .fold(0u64, |indices, (i, &value)| {
+            indices | (nearest(&palette, |entry| entry.abs_diff(value) as u32) << (3 * i))
+        });
+
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+
+    block
+}
+
+fn encode_color_block(pixels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let min: [u8; 3] = std::array::from_fn(|c| pixels.iter().map(|pixel| pixel[c]).min().unwrap());
+    let max: [u8; 3] = std::array::from_fn(|c| pixels.iter().map(|pixel| pixel[c]).max().unwrap());
+
+    // Pulling the endpoints slightly inwards reduces the error of the
+    // interpolated colors.
+    let inset = |c: usize| (max[c] - min[c]) / 16;
+    let mut c0 = to_565(std::array::from_fn(|c| max[c] - inset(c)));
+    let mut c1 = to_565(std::array::from_fn(|c| min[c] + inset(c)));
+
+    if c0 < c1 {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+
+    if c0 == c1 {
+        return block;
+    }
+
+    let palette = color_palette(c0, c1, true);
+    let indices = pixels.iter().enumerate().fold(0u32, |indices, (i, pixel)| {
+        let index = nearest(&palette, |entry| {
+            (0..3)
+                .map(|c| (entry[c] as i32 - pixel[c] as i32).pow(2) as u32)
+                .sum()
+        });
+
+        indices | ((index as u32) << (2 * i))
+    });
+
+    block[4..].copy_from_slice(&indices.to_le_bytes());
+
+    block
+}
+
+fn from_565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) & 31;
+    let g = (color >> 5) & 63;
+    let b = color & 31;
+
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+fn nearest<T>(palette: &[T], mut distance: impl FnMut(&T) -> u32) -> u64 {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u64)
+}
+
+fn to_565(color: [u8; 3]) -> u16 {
+    ((color[0] as u16 >> 3) << 11) | ((color[1] as u16 >> 2) << 5) | (color[2] as u16 >> 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::tests::gradient;
+
+    #[test]
+    fn compressed_gradients_decompress_close_to_the_original() {
+        // Not a multiple of the block size, to clamp the last blocks.
+        let (width, height) = (38, 27);
+        let rgba = gradient(width, height);
+
+        for (format, channels, tolerance) in [
+            (Format::Bc1, 3, 10),
+            (Format::Bc3, 4, 10),
+            (Format::Bc4, 1, 3),
+            (Format::Bc5, 2, 3),
+        ] {
+            let data = compress(format, width, height, &rgba);
+            assert_eq!(data.len(), format.bytes(width, height));
+
+            let decompressed = decompress(format, width, height, &data);
+
+            for (pixel, original) in decompressed.chunks_exact(4).zip(rgba.chunks_exact(4)) {
+                for c in 0..channels {
+                    assert!(
+                        pixel[c].abs_diff(original[c]) <= tolerance,
+                        "{format:?} turned {original:?} into {pixel:?}"
+                    );
+                }
+            }
+        }
+    }
+}
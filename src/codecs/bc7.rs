@@ -0,0 +1,645 @@
+//! CPU decoding of BC7 blocks, for adapters without BC texture compression,
+//! and encoding for the offline compress tool. The encoder only uses mode 6,
+//! which suits smooth color and alpha gradients best.
+
+const BLOCK_BYTES: usize = 16;
+const BLOCK_DIMENSION: u32 = 4;
+
+struct Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// One p-bit per endpoint.
+    endpoint_p_bits: bool,
+    /// One p-bit per subset, shared by both of its endpoints.
+    shared_p_bits: bool,
+    index_bits: u32,
+    /// Modes 4 and 5 index their alpha separately.
+    secondary_index_bits: u32,
+}
+
+const MODES: [Mode; 8] = [
+    Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+/// Which subset each pixel of a two subset block belongs to, one bit per
+/// pixel.
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Which subset each pixel of a three subset block belongs to.
+#[rustfmt::skip]
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2], [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1], [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2], [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2], [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2], [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2], [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2], [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0], [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0], [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2], [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1], [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2], [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0], [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0], [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1], [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1], [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1], [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1], [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2], [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2], [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2], [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1], [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2], [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// The pixel of the second subset whose index drops its top bit, in two
+/// subset blocks.
+#[rustfmt::skip]
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// The anchors of the second and third subsets, in three subset blocks.
+#[rustfmt::skip]
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Reads a block from its least significant bit up.
+struct Bits {
+    block: u128,
+    position: u32,
+}
+
+impl Bits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.block >> self.position) as u32 & ((1u64 << count) - 1) as u32;
+
+        self.position += count;
+
+        value
+    }
+}
+
+/// Compresses tightly packed RGBA8 pixels into mode 6 blocks, which have a
+/// single subset with 7 bit endpoints, a p-bit for each and 4 bit indices.
+/// Pixels past the edge of the image are clamped to the last row or column.
+pub fn compress(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let blocks_wide = (width + BLOCK_DIMENSION - 1) / BLOCK_DIMENSION;
+    let blocks_high = (height + BLOCK_DIMENSION - 1) / BLOCK_DIMENSION;
+    let mut data = Vec::with_capacity((blocks_wide * blocks_high) as usize * BLOCK_BYTES);
+
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let pixels: [[u8; 4]; 16] = std::array::from_fn(|i| {
+                let x = (block_x * BLOCK_DIMENSION + i as u32 % 4).min(width - 1);
+                let y = (block_y * BLOCK_DIMENSION + i as u32 / 4).min(height - 1);
+                let offset = ((y * width + x) * 4) as usize;
+
+                rgba[offset..offset + 4].try_into().unwrap()
+            });
+
+            data.extend(encode_block(&pixels).to_le_bytes());
+        }
+    }
+
+    data
+}
+
+/// Decompresses into tightly packed RGBA8 pixels.
+pub fn decompress(width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let blocks_wide = (width + BLOCK_DIMENSION - 1) / BLOCK_DIMENSION;
+    let mut rgba = vec![0; (width * height * 4) as usize];
+
+    for (block_index, block) in data.chunks_exact(BLOCK_BYTES).enumerate() {
+        let block_x = block_index as u32 % blocks_wide;
+        let block_y = block_index as u32 / blocks_wide;
+        let pixels = decode_block(u128::from_le_bytes(block.try_into().unwrap()));
+
+        for (i, pixel) in pixels.iter().enumerate() {
+            let x = block_x * BLOCK_DIMENSION + i as u32 % 4;
+            let y = block_y * BLOCK_DIMENSION + i as u32 / 4;
+
+            if x < width && y < height {
+                let offset = ((y * width + x) * 4) as usize;
+                rgba[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    rgba
+}
+
+fn decode_block(block: u128) -> [[u8; 4]; 16] {
+    // The mode is the number of zero bits before the first set bit. Blocks
+    // without any are reserved and decode to transparent black.
+    let mode_index = block.trailing_zeros();
+    let mode = match MODES.get(mode_index as usize) {
+        Some(mode) => mode,
+        None => return [[0; 4]; 16],
+    };
+    let mut bits = Bits {
+        block,
+        position: mode_index + 1,
+    };
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+
+    for channel in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(mode.color_bits);
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = if mode.alpha_bits > 0 {
+            bits.read(mode.alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let mut p_bits = [0; 6];
+
+    if mode.endpoint_p_bits {
+        for p_bit in &mut p_bits[..endpoint_count] {
+            *p_bit = bits.read(1);
+        }
+    } else if mode.shared_p_bits {
+        for subset in 0..mode.subsets {
+            let p_bit = bits.read(1);
+
+            p_bits[subset * 2] = p_bit;
+            p_bits[subset * 2 + 1] = p_bit;
+        }
+    }
+
+    let has_p_bits = mode.endpoint_p_bits || mode.shared_p_bits;
+    for (endpoint, p_bit) in endpoints[..endpoint_count].iter_mut().zip(p_bits) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let bit_count = if channel < 3 {
+                mode.color_bits
+            } else if mode.alpha_bits > 0 {
+                mode.alpha_bits
+            } else {
+                continue;
+            };
+
+            *value = if has_p_bits {
+                expand((*value << 1) | p_bit, bit_count + 1)
+            } else {
+                expand(*value, bit_count)
+            };
+        }
+    }
+
+    let subset = |pixel: usize| match mode.subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> pixel) as usize & 1,
+        _ => PARTITIONS_3[partition][pixel] as usize,
+    };
+    let is_anchor = |pixel: usize| {
+        pixel == 0
+            || match mode.subsets {
+                1 => false,
+                2 => pixel == ANCHORS_2[partition] as usize,
+                _ => {
+                    pixel == ANCHORS_3[0][partition] as usize
+                        || pixel == ANCHORS_3[1][partition] as usize
+                }
+            }
+    };
+
+    let primary: [u32; 16] =
+        std::array::from_fn(|pixel| bits.read(mode.index_bits - is_anchor(pixel) as u32));
+    let secondary: [u32; 16] = std::array::from_fn(|pixel| {
+        if mode.secondary_index_bits == 0 {
+            0
+        } else {
+            bits.read(mode.secondary_index_bits - (pixel == 0) as u32)
+        }
+    });
+
+    std::array::from_fn(|pixel| {
+        let subset = subset(pixel);
+        let [e0, e1] = [endpoints[subset * 2], endpoints[subset * 2 + 1]];
+        let (color_index, color_bits, alpha_index, alpha_bits) = if mode.secondary_index_bits == 0 {
+            (
+                primary[pixel],
+                mode.index_bits,
+                primary[pixel],
+                mode.index_bits,
+            )
+        } else if index_selection == 0 {
+            let secondary_bits = mode.secondary_index_bits;
+
+            (
+                primary[pixel],
+                mode.index_bits,
+                secondary[pixel],
+                secondary_bits,
+            )
+        } else {
+            let secondary_bits = mode.secondary_index_bits;
+
+            (
+                secondary[pixel],
+                secondary_bits,
+                primary[pixel],
+                mode.index_bits,
+            )
+        };
+
+        let mut color: [u8; 4] = std::array::from_fn(|channel| {
+            let (index, bits) = if channel < 3 {
+                (color_index, color_bits)
+            } else {
+                (alpha_index, alpha_bits)
+            };
+
+            interpolate(e0[channel], e1[channel], weight(index, bits))
+        });
+
+        // Rotation swaps alpha with one of the color channels.
+        if rotation > 0 {
+            color.swap(3, rotation as usize - 1);
+        }
+
+        color
+    })
+}
+
+/// Encodes a mode 6 block, trying every combination of p-bits.
+fn encode_block(pixels: &[[u8; 4]; 16]) -> u128 {
+    let ends = super::principal_endpoints(pixels);
+
+    let (_, mut endpoints, mut p_bits, mut indices) = [[0, 0], [0, 1], [1, 0], [1, 1]]
+        .into_iter()
+        .map(|p_bits: [u32; 2]| {
+            // A p-bit is the lowest bit of every channel of its endpoint.
+            let endpoints: [[u32; 4]; 2] = std::array::from_fn(|i| {
+                ends[i]
+                    .to_array()
+                    .map(|c| ((c - p_bits[i] as f32) / 2.0).round().clamp(0.0, 127.0) as u32)
+            });
+            let [e0, e1] = [0, 1].map(|i| endpoints[i].map(|c| (c << 1) | p_bits[i]));
+
+            let mut error = 0;
+            let indices = pixels.map(|pixel| {
+                let (index, pixel_error) = (0..16)
+                    .map(|index| {
+                        let pixel_error = (0..4)
+                            .map(|c| {
+                                let value = interpolate(e0[c], e1[c], WEIGHTS_4[index as usize]);
+
+                                (value as i32 - pixel[c] as i32).pow(2) as u32
+                            })
+                            .sum::<u32>();
+
+                        (index, pixel_error)
+                    })
+                    .min_by_key(|&(_, pixel_error)| pixel_error)
+                    .unwrap();
+
+                error += pixel_error;
+
+                index
+            });
+
+            (error, endpoints, p_bits, indices)
+        })
+        .min_by_key(|&(error, ..)| error)
+        .unwrap();
+
+    // The first index drops its top bit, which has to be zero.
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        p_bits.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut block = 0;
+    let mut position = 0;
+    let mut write = |value: u32, bits: u32| {
+        block |= (value as u128) << position;
+        position += bits;
+    };
+
+    write(1 << 6, 7);
+    for channel in 0..4 {
+        for endpoint in &endpoints {
+            write(endpoint[channel], 7);
+        }
+    }
+    for p_bit in p_bits {
+        write(p_bit, 1);
+    }
+    for (pixel, index) in indices.into_iter().enumerate() {
+        write(index, if pixel == 0 { 3 } else { 4 });
+    }
+
+    block
+}
+
+/// Replicates the top bits of a `bits` wide value into the bottom ones.
+fn expand(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+
+    value | (value >> bits)
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u8 {
+    (((64 - weight) * e0 + weight * e1 + 32) >> 6) as u8
+}
+
+fn weight(index: u32, bits: u32) -> u32 {
+    match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::tests::{gradient, hex, reference};
+
+    /// Builds a block from `(value, bit count)` fields, from bit 0 up.
+    fn block(fields: &[(u128, u32)]) -> u128 {
+        let mut block = 0;
+        let mut position = 0;
+
+        for &(value, bits) in fields {
+            block |= value << position;
+            position += bits;
+        }
+
+        assert_eq!(position, 128);
+
+        block
+    }
+
+    #[test]
+    fn anchors_lie_in_their_subsets() {
+        for partition in 0..64 {
+            let anchor = ANCHORS_2[partition];
+            assert_eq!((PARTITIONS_2[partition] >> anchor) & 1, 1);
+            assert_eq!(PARTITIONS_2[partition] & 1, 0);
+
+            for (subset, anchors) in ANCHORS_3.iter().enumerate() {
+                let anchor = anchors[partition] as usize;
+                assert_eq!(PARTITIONS_3[partition][anchor] as usize, subset + 1);
+            }
+            assert_eq!(PARTITIONS_3[partition][0], 0);
+        }
+    }
+
+    #[test]
+    fn mode_6_interpolates_with_p_bits() {
+        let mut fields = vec![(1 << 6, 7)];
+        // Red ramps from 0 to 255. The p-bits raise the second endpoint of
+        // green and blue to 1 and the first alpha endpoint stays at 254.
+        fields.extend([
+            (0, 7),
+            (127, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (127, 7),
+            (127, 7),
+        ]);
+        fields.extend([(0, 1), (1, 1)]);
+        fields.push((0, 3));
+        fields.extend((1..16).map(|index| (index, 4)));
+
+        let pixels = decode_block(block(&fields));
+
+        for (pixel, color) in pixels.iter().enumerate() {
+            let weight = WEIGHTS_4[pixel];
+
+            assert_eq!(
+                *color,
+                [
+                    interpolate(0, 255, weight),
+                    interpolate(0, 1, weight),
+                    interpolate(0, 1, weight),
+                    interpolate(254, 255, weight)
+                ]
+            );
+        }
+        assert_eq!(pixels[15], [255, 1, 1, 255]);
+    }
+
+    #[test]
+    fn mode_5_rotation_swaps_alpha_into_a_color_channel() {
+        let mut fields = vec![(1 << 5, 6), (1, 2)];
+        fields.extend([
+            (127, 7),
+            (127, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (10, 8),
+            (10, 8),
+        ]);
+        fields.extend([(0, 31), (0, 31)]);
+
+        assert_eq!(decode_block(block(&fields)), [[10, 0, 0, 255]; 16]);
+    }
+
+    #[test]
+    fn reserved_blocks_are_transparent_black() {
+        assert_eq!(decode_block(0), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn partial_blocks_are_clipped() {
+        let block = block(&[
+            (1 << 5, 6),
+            (0, 2),
+            (127, 7),
+            (127, 7),
+            (0, 28),
+            (255, 8),
+            (255, 8),
+            (0, 62),
+        ]);
+        let rgba = decompress(2, 3, &block.to_le_bytes());
+
+        assert_eq!(rgba, [255, 0, 0, 255].repeat(6));
+    }
+
+    #[test]
+    fn blocks_decode_like_a_reference_decoder() {
+        for fields in reference(include_str!("testdata/bc7.txt")) {
+            assert_eq!(
+                decompress(4, 4, &hex(fields[0])),
+                hex(fields[1]),
+                "block {}",
+                fields[0]
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_gradients_decompress_close_to_the_original() {
+        let (width, height) = (38, 27);
+        let rgba = gradient(width, height);
+        let data = compress(width, height, &rgba);
+
+        assert_eq!(data.len(), 10 * 7 * BLOCK_BYTES);
+
+        for (pixel, original) in decompress(width, height, &data)
+            .chunks_exact(4)
+            .zip(rgba.chunks_exact(4))
+        {
+            for c in 0..4 {
+                assert!(
+                    pixel[c].abs_diff(original[c]) <= 2,
+                    "{original:?} turned into {pixel:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+pub mod astc;
+pub mod bc;
+pub mod bc7;
+
+use glam::{Mat4, Vec4};
+
+/// The ends of the line along which `pixels` vary the most, found by power
+/// iteration on their covariance, clipped to the pixels furthest along it.
+fn principal_endpoints(pixels: &[[u8; 4]]) -> [Vec4; 2] {
+    let points: Vec<Vec4> = pixels
+        .iter()
+        .map(|pixel| Vec4::from(pixel.map(f32::from)))
+        .collect();
+    let mean = points.iter().sum::<Vec4>() / points.len() as f32;
+    let covariance: Mat4 = points
+        .iter()
+        .map(|&point| {
+            let d = point - mean;
+
+            Mat4::from_cols(d * d.x, d * d.y, d * d.z, d * d.w)
+        })
+        .sum();
+
+    // Start from the channel that varies the most, which is never orthogonal
+    // to the axis.
+    let variances = [
+        covariance.x_axis.x,
+        covariance.y_axis.y,
+        covariance.z_axis.z,
+        covariance.w_axis.w,
+    ];
+    let channel = (0..4)
+        .max_by(|&a, &b| variances[a].total_cmp(&variances[b]))
+        .unwrap();
+    let mut axis = Vec4::ZERO;
+    axis[channel] = 1.0;
+
+    for _ in 0..8 {
+        axis = (covariance * axis).normalize_or_zero();
+    }
+
+    let (low, high) = points
+        .iter()
+        .map(|&point| (point - mean).dot(axis))
+        .fold((0.0f32, 0.0f32), |(low, high), t| (low.min(t), high.max(t)));
+
+    [low, high].map(|t| (mean + axis * t).clamp(Vec4::ZERO, Vec4::splat(255.0)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    /// The fields of each line of a file in `testdata`, skipping comments.
+    pub(crate) fn reference(text: &str) -> impl Iterator<Item = Vec<&str>> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split(' ').collect())
+    }
+
+    /// RGBA8 pixels with every channel ramping diagonally across the image.
+    pub(crate) fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let t = (i % width + i / width) * 255 / (width + height - 2);
+
+                [t, t / 2 + 64, t / 4, 255 - t / 2].map(|c| c as u8)
+            })
+            .collect()
+    }
+
+    pub(crate) fn hex(field: &str) -> Vec<u8> {
+        (0..field.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&field[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
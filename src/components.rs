@@ -0,0 +1,109 @@
+//! The components game objects are made of.
+
+use crate::{
+    assets::{self, Handle},
+    data::shaders::basic::Instance,
+};
+use std::path::PathBuf;
+use weng_game::bvh::Aabb;
+
+/// The camera keeps its own position and orientation, since it moves itself
+/// from the input rather than through a [`Transform`].
+pub use crate::camera::Camera;
+
+/// Marks a [`MeshRenderer`] of a crowd too large to sort on the CPU every
+/// frame, such as the cube field. Its instances are culled on the GPU, and
+/// only uploaded again when they change, so they suit objects that stay put.
+pub struct GpuCulled;
+
+/// Draws its entity between its [`Transform`]s of the last two ticks, so that
+/// it moves smoothly when there are more frames than ticks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Interpolated {
+    /// Before the last tick, or `None` until the entity has seen one.
+    pub previous: Option<Transform>,
+}
+
+/// A point light at the [`Transform`] of its entity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub color: glam::Vec3,
+}
+
+/// Draws a model at the [`Transform`] of its entity, once it has loaded.
+pub struct MeshRenderer {
+    /// Around the model, in model space. Set along with `model`.
+    pub bounds: Aabb,
+    /// Set by the main loop when the model at `path` is ready.
+    pub model: Option<Handle<assets::Model<weng::graphics::Context>>>,
+    pub path: PathBuf,
+}
+
+impl MeshRenderer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            bounds: Aabb::EMPTY,
+            model: None,
+            path: path.into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
+    pub translation: glam::Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        rotation: glam::Quat::IDENTITY,
+        scale: glam::Vec3::ONE,
+        translation: glam::Vec3::ZERO,
+    };
+
+    pub fn from_translation(translation: glam::Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// What the basic shader draws. Normals only follow the rotation, which is
+    /// right for uniform scales.
+    pub fn instance(&self) -> Instance {
+        Instance {
+            model: self.matrix().to_cols_array(),
+            normal: glam::Mat3::from_quat(self.rotation).to_cols_array(),
+        }
+    }
+
+    /// `self` at `t` 0, `end` at 1.
+    pub fn lerp(&self, end: &Self, t: f32) -> Self {
+        Self {
+            rotation: self.rotation.slerp(end.rotation, t),
+            scale: self.scale.lerp(end.scale, t),
+            translation: self.translation.lerp(end.translation, t),
+        }
+    }
+
+    pub fn matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Moves the [`Transform`] of its entity every tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Velocity {
+    /// The axis to turn around, scaled by the speed in radians per second.
+    pub angular: glam::Vec3,
+    /// In units per second.
+    pub linear: glam::Vec3,
+}
@@ -0,0 +1,310 @@
+//! Frustum culling of instances on the GPU, with the same test on the CPU for
+//! adapters that cannot run it.
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct Params {
+    planes: [[f32; 4]; 6],
+    bounding_sphere: [f32; 4],
+    instance_count: u32,
+    instance_floats: u32,
+    // Uniform buffers are padded to 16 bytes
+    _padding: [u32; 2],
+}
+
+/// The arguments of `draw_indexed_indirect`.
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct DrawIndexedIndirect {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+/// Instances that are culled against the camera frustum on the GPU, which
+/// writes the visible ones and the arguments of a single indirect draw. The
+/// CPU does not touch the instances after uploading them.
+pub struct Culling {
+    bind_group: weng::wgpu::BindGroup,
+    draw_buffer: weng::wgpu::Buffer,
+    instance_count: u32,
+    instance_floats: u32,
+    params: weng::wgpu::Buffer,
+    pipeline: weng::wgpu::ComputePipeline,
+    visible: weng::wgpu::Buffer,
+}
+
+impl Culling {
+    /// Records the culling of every instance of a mesh with `index_count`
+    /// indices, bounded by `bounding_sphere` in model space.
+    pub fn cull(
+        &self,
+        queue: &weng::wgpu::Queue,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        view_projection: glam::Mat4,
+        bounding_sphere: glam::Vec4,
+        index_count: u32,
+    ) {
+        queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&Params {
+                planes: frustum_planes(view_projection).map(|plane| plane.to_array()),
+                bounding_sphere: bounding_sphere.to_array(),
+                instance_count: self.instance_count,
+                instance_floats: self.instance_floats,
+                _padding: [0; 2],
+            }),
+        );
+        queue.write_buffer(
+            &self.draw_buffer,
+            0,
+            bytemuck::bytes_of(&DrawIndexedIndirect {
+                index_count,
+                instance_count: 0,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: 0,
+            }),
+        );
+
+        let mut pass =
+            encoder.begin_compute_pass(&weng::wgpu::ComputePassDescriptor { label: None });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(
+            (self.instance_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+            1,
+            1,
+        );
+    }
+
+    /// Draws the visible instances. The mesh has to be bound already, with the
+    /// instances expected at vertex buffer slot 1.
+    pub fn draw<'a>(&'a self, pass: &mut weng::wgpu::RenderPass<'a>) {
+        pass.set_vertex_buffer(1, self.visible.slice(..));
+        pass.draw_indexed_indirect(&self.draw_buffer, 0);
+    }
+
+    /// The [`DrawIndexedIndirect`] the last cull wrote, which can be copied
+    /// out of it.
+    pub fn draw_buffer(&self) -> &weng::wgpu::Buffer {
+        &self.draw_buffer
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+
+    /// `instances` have to start with their model matrix.
+    pub fn new<T: bytemuck::Pod>(
+        device: &weng::wgpu::Device,
+        shader: &weng::wgpu::ShaderModule,
+        instances: &[T],
+    ) -> Self {
+        use weng::wgpu::util::DeviceExt;
+
+        let bytes = bytemuck::cast_slice::<_, u8>(instances);
+
+        let pipeline = device.create_compute_pipeline(&weng::wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: shader,
+            entry_point: "cull",
+        });
+
+        let draw_buffer = device.create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<DrawIndexedIndirect>() as weng::wgpu::BufferAddress,
+            usage: weng::wgpu::BufferUsages::STORAGE
+                | weng::wgpu::BufferUsages::INDIRECT
+                | weng::wgpu::BufferUsages::COPY_DST
+                | weng::wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let input = device.create_buffer_init(&weng::wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytes,
+            usage: weng::wgpu::BufferUsages::STORAGE,
+        });
+        let params = device.create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<Params>() as weng::wgpu::BufferAddress,
+            usage: weng::wgpu::BufferUsages::UNIFORM | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let visible = device.create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size: bytes.len() as weng::wgpu::BufferAddress,
+            usage: weng::wgpu::BufferUsages::STORAGE | weng::wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&weng::wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                weng::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                weng::wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: input.as_entire_binding(),
+                },
+                weng::wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: visible.as_entire_binding(),
+                },
+                weng::wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: draw_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            bind_group,
+            draw_buffer,
+            instance_count: instances.len() as u32,
+            instance_floats: (std::mem::size_of::<T>() / std::mem::size_of::<f32>()) as u32,
+            params,
+            pipeline,
+            visible,
+        }
+    }
+}
+
+/// The planes of the frustum of a 0 to 1 depth projection, with normals
+/// pointing inside and `w` the distance from the origin.
+pub fn frustum_planes(view_projection: glam::Mat4) -> [glam::Vec4; 6] {
+    let (x, y, z, w) = (
+        view_projection.row(0),
+        view_projection.row(1),
+        view_projection.row(2),
+        view_projection.row(3),
+    );
+
+    [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().length())
+}
+
+/// Whether an instance with the model matrix `model` of a mesh bounded by
+/// `bounding_sphere` is kept by `cull.wgsl`. The sphere grows with the largest
+/// scale of the matrix.
+pub fn instance_visible(
+    planes: &[glam::Vec4; 6],
+    model: glam::Mat4,
+    bounding_sphere: glam::Vec4,
+) -> bool {
+    let scale = model
+        .x_axis
+        .length()
+        .max(model.y_axis.length())
+        .max(model.z_axis.length());
+
+    sphere_visible(
+        planes,
+        model.transform_point3(bounding_sphere.truncate()),
+        bounding_sphere.w * scale,
+    )
+}
+
+/// Whether a sphere is at least partly inside the planes of
+/// [`frustum_planes`].
+pub fn sphere_visible(planes: &[glam::Vec4; 6], center: glam::Vec3, radius: f32) -> bool {
+    planes
+        .iter()
+        .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Mat4, Vec3};
+
+    /// Looks down -z from the origin, seeing from 1 to 100 units away.
+    fn planes() -> [glam::Vec4; 6] {
+        frustum_planes(Mat4::perspective_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+            100.0,
+        ))
+    }
+
+    #[test]
+    fn planes_are_normalized_and_face_inside() {
+        for plane in planes() {
+            assert!((plane.truncate().length() - 1.0).abs() < 1e-5);
+            assert!(plane.truncate().dot(Vec3::new(0.0, 0.0, -10.0)) + plane.w > 0.0);
+        }
+    }
+
+    #[test]
+    fn spheres_inside_or_straddling_the_frustum_are_visible() {
+        let planes = planes();
+
+        assert!(sphere_visible(&planes, Vec3::new(0.0, 0.0, -10.0), 1.0));
+        // The field of view is 90 degrees, so x = -z is on the right plane.
+        assert!(sphere_visible(&planes, Vec3::new(10.5, 0.0, -10.0), 1.0));
+        assert!(sphere_visible(&planes, Vec3::new(0.0, 0.0, -100.5), 1.0));
+        // Around the camera, with its center behind the near plane.
+        assert!(sphere_visible(&planes, Vec3::ZERO, 2.0));
+    }
+
+    #[test]
+    fn spheres_outside_any_plane_are_culled() {
+        let planes = planes();
+
+        assert!(!sphere_visible(&planes, Vec3::new(0.0, 0.0, 10.0), 1.0));
+        assert!(!sphere_visible(&planes, Vec3::new(12.0, 0.0, -10.0), 1.0));
+        assert!(!sphere_visible(&planes, Vec3::new(0.0, -12.0, -10.0), 1.0));
+        assert!(!sphere_visible(&planes, Vec3::new(0.0, 0.0, -102.0), 1.0));
+        assert!(!sphere_visible(&planes, Vec3::new(0.0, 0.0, -0.5), 0.25));
+    }
+
+    #[test]
+    fn instance_spheres_follow_the_model_matrix() {
+        let planes = planes();
+        let sphere = glam::Vec4::new(1.0, 0.0, 0.0, 1.0);
+
+        // Moved to x = 12.5, the center is about 1.8 outside the right plane,
+        // so only the sphere scaled to a radius of 2 reaches inside.
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::new(1.0, 2.0, 1.0),
+            glam::Quat::IDENTITY,
+            Vec3::new(11.5, 0.0, -10.0),
+        );
+        assert!(instance_visible(&planes, model, sphere));
+
+        let model = Mat4::from_translation(Vec3::new(11.5, 0.0, -10.0));
+        assert!(!instance_visible(&planes, model, sphere));
+    }
+
+    #[test]
+    fn culling_is_invariant_under_moving_the_camera_with_the_scene() {
+        let offset = Vec3::new(30.0, -5.0, 12.0);
+        let view = Mat4::from_translation(-offset);
+        let moved = frustum_planes(
+            Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 100.0) * view,
+        );
+        let planes = planes();
+
+        for x in -20..20 {
+            for z in -120..10 {
+                let center = Vec3::new(x as f32, 0.5, z as f32);
+
+                assert_eq!(
+                    sphere_visible(&planes, center, 0.75),
+                    sphere_visible(&moved, center + offset, 0.75),
+                    "{center}"
+                );
+            }
+        }
+    }
+}
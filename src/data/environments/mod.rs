@@ -0,0 +1,130 @@
+use anyhow::bail;
+use std::path::{Path, PathBuf};
+
+pub static DIR: &str = "assets/environments";
+
+/// The file stems of cubemap faces, in the order of [`Source::Cubemap`].
+const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+/// The images an environment is built from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Source {
+    /// One square image per face, in the +X, -X, +Y, -Y, +Z, -Z order.
+    Cubemap([PathBuf; 6]),
+    /// A single latitude-longitude image, usually an `.hdr` or `.exr` file.
+    Equirectangular(PathBuf),
+}
+
+impl Source {
+    /// The environment called `name` in `dir`: a directory of faces named
+    /// `px`, `nx`, `py`, `ny`, `pz` and `nz` in any image format, or else an
+    /// `.hdr` or `.exr` file.
+    pub fn find(dir: &Path, name: &str) -> anyhow::Result<Self> {
+        let faces_dir = dir.join(name);
+
+        if faces_dir.is_dir() {
+            let files = std::fs::read_dir(&faces_dir)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let faces = FACES.map(|face| {
+                files
+                    .iter()
+                    .find(|path| path.file_stem().and_then(|stem| stem.to_str()) == Some(face))
+                    .cloned()
+            });
+
+            return match faces {
+                [Some(px), Some(nx), Some(py), Some(ny), Some(pz), Some(nz)] => {
+                    Ok(Source::Cubemap([px, nx, py, ny, pz, nz]))
+                }
+                _ => bail!(
+                    "{} lacks some of the {} cubemap faces",
+                    faces_dir.display(),
+                    FACES.join(", ")
+                ),
+            };
+        }
+
+        for extension in ["hdr", "exr"] {
+            let path = dir.join(format!("{name}.{extension}"));
+
+            if path.is_file() {
+                return Ok(Source::Equirectangular(path));
+            }
+        }
+
+        bail!("there is no environment called {name} in {}", dir.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("weng-environments-{name}-{}", std::process::id()));
+
+            std::fs::create_dir_all(&dir).unwrap();
+
+            Self(dir)
+        }
+
+        fn touch(&self, name: &str) -> PathBuf {
+            let path = self.0.join(name);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, []).unwrap();
+
+            path
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn directories_of_faces_are_cubemaps() {
+        let dir = Dir::new("cubemap");
+        let faces = FACES.map(|face| dir.touch(&format!("sky/{face}.png")));
+
+        // Directories take precedence over files of the same name.
+        dir.touch("sky.hdr");
+
+        assert_eq!(Source::find(&dir.0, "sky").unwrap(), Source::Cubemap(faces));
+    }
+
+    #[test]
+    fn single_images_are_equirectangular() {
+        let dir = Dir::new("equirectangular");
+        let hdr = dir.touch("sky.hdr");
+        let exr = dir.touch("night.exr");
+
+        assert_eq!(
+            Source::find(&dir.0, "sky").unwrap(),
+            Source::Equirectangular(hdr)
+        );
+        assert_eq!(
+            Source::find(&dir.0, "night").unwrap(),
+            Source::Equirectangular(exr)
+        );
+    }
+
+    #[test]
+    fn missing_environments_and_faces_are_errors() {
+        let dir = Dir::new("missing");
+
+        for face in &FACES[..5] {
+            dir.touch(&format!("sky/{face}.png"));
+        }
+
+        assert!(Source::find(&dir.0, "sky").is_err());
+        assert!(Source::find(&dir.0, "night").is_err());
+    }
+}
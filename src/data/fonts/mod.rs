@@ -0,0 +1,13 @@
+use anyhow::{anyhow, Context};
+use std::path::Path;
+
+pub static DIR: &str = "assets/fonts";
+
+/// Parses a TTF or OTF file.
+pub fn load(path: &Path) -> anyhow::Result<fontdue::Font> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+        .map_err(|e| anyhow!("invalid font {}: {e}", path.display()))
+}
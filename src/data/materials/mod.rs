@@ -0,0 +1,120 @@
+use crate::data::textures::{self, Source};
+use std::path::Path;
+
+/// Alpha below this is discarded by cutout materials.
+pub const ALPHA_CUTOFF: f32 = 0.5;
+
+/// How the alpha of a material is used. Variants are in drawing order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AlphaMode {
+    Opaque,
+    /// Alpha tested against [`ALPHA_CUTOFF`], so it still writes depth.
+    Cutout,
+    /// Blended over what is behind it, which has to be drawn first.
+    Blended,
+}
+
+/// Where the textures of a material come from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Maps {
+    pub diffuse: Source,
+    pub emissive: Source,
+    pub height: Source,
+    pub normal: Source,
+    pub orm: Source,
+}
+
+impl Maps {
+    /// Besides `map_Kd` and `map_Bump`, reads the emissive map from `map_Ke`,
+    /// the height map from `disp` or `map_disp`, and packs the occlusion,
+    /// roughness and metallic maps from `map_Ka`, `map_Pr` and `map_Pm`.
+    /// Missing maps are plain colors: white diffuse, no glow, and flat
+    /// heights and normals.
+    pub fn from_mtl(info: &tobj::Material) -> Self {
+        let path = |name: &str| Path::new(textures::DIR).join(name);
+        let optional =
+            |name: Option<&String>| name.filter(|name| !name.is_empty()).map(|name| path(name));
+
+        Self {
+            diffuse: optional(Some(&info.diffuse_texture))
+                .map_or(Source::Color([255, 255, 255, 255]), Source::File),
+            emissive: optional(info.unknown_param.get("map_Ke"))
+                .map_or(Source::Color([0, 0, 0, 255]), Source::File),
+            height: optional(info.unknown_param.get("disp"))
+                .or_else(|| optional(info.unknown_param.get("map_disp")))
+                .map_or(Source::Color([255, 255, 255, 255]), Source::File),
+            normal: optional(Some(&info.normal_texture))
+                .map_or(Source::Color([128, 128, 255, 255]), Source::File),
+            orm: Source::Orm {
+                metallic: optional(info.unknown_param.get("map_Pm")),
+                occlusion: optional(Some(&info.ambient_texture)),
+                roughness: optional(info.unknown_param.get("map_Pr")),
+            },
+        }
+    }
+
+    /// In the order of their bindings.
+    pub fn sources(&self) -> [&Source; 5] {
+        [
+            &self.diffuse,
+            &self.normal,
+            &self.emissive,
+            &self.orm,
+            &self.height,
+        ]
+    }
+}
+
+/// What a material needs besides its textures.
+#[derive(Clone, Copy, Debug)]
+pub struct Properties {
+    pub alpha_mode: AlphaMode,
+    /// Multiplies the alpha of the diffuse texture.
+    pub dissolve: f32,
+}
+
+impl Properties {
+    /// A `d` below 1 makes the material blended, while a `map_d` marks the
+    /// alpha of the diffuse texture as a cutout mask.
+    pub fn from_mtl(info: &tobj::Material) -> Self {
+        let alpha_mode = if info.dissolve < 1.0 {
+            AlphaMode::Blended
+        } else if !info.dissolve_texture.is_empty() {
+            AlphaMode::Cutout
+        } else {
+            AlphaMode::Opaque
+        };
+
+        Self {
+            alpha_mode,
+            dissolve: info.dissolve.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn uniform(&self) -> crate::data::shaders::basic::MaterialUniform {
+        crate::data::shaders::basic::MaterialUniform {
+            alpha_cutoff: match self.alpha_mode {
+                AlphaMode::Cutout => ALPHA_CUTOFF,
+                AlphaMode::Opaque | AlphaMode::Blended => 0.0,
+            },
+            dissolve: self.dissolve,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+// Used in material keys, where equal bits are what counts.
+impl PartialEq for Properties {
+    fn eq(&self, other: &Self) -> bool {
+        self.alpha_mode == other.alpha_mode && self.dissolve.to_bits() == other.dissolve.to_bits()
+    }
+}
+
+impl Eq for Properties {}
+
+impl std::hash::Hash for Properties {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.alpha_mode.hash(state);
+        self.dissolve.to_bits().hash(state);
+    }
+}
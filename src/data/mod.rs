@@ -1,3 +1,9 @@
+pub mod environments;
+
+pub mod fonts;
+
+pub mod materials;
+
 pub mod models;
 
 pub mod shaders;
@@ -7,6 +7,12 @@ pub struct Instance {
     pub normal: [f32; 9],
 }
 
+impl Instance {
+    pub fn position(&self) -> glam::Vec3 {
+        glam::Vec3::new(self.model[12], self.model[13], self.model[14])
+    }
+}
+
 impl weng::graphics::instances::Instance for Instance {
     const ATTRIBUTES: &'static [weng::wgpu::VertexAttribute] = &weng::graphics::vertices::vertex_attr_array![
         5 => Float32x4,
@@ -24,6 +30,8 @@ impl weng::graphics::instances::Instance for Instance {
 pub struct CameraUniform {
     pub pos: [f32; 4],
     pub view_proj: [f32; 16],
+    /// Maps clip space to world space directions, ignoring the camera position.
+    pub sky_inv_view_proj: [f32; 16],
 }
 
 impl weng::graphics::uniforms::Uniform for CameraUniform {}
@@ -40,3 +48,12 @@ pub struct LightUniform {
 }
 
 impl weng::graphics::uniforms::Uniform for LightUniform {}
+
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct MaterialUniform {
+    /// 0 for materials that are not alpha tested.
+    pub alpha_cutoff: f32,
+    pub dissolve: f32,
+    pub _padding: [f32; 2],
+}
@@ -0,0 +1,15 @@
+pub const NAME: &str = "gizmos.wgsl";
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl weng::graphics::vertices::Vertex for Vertex {
+    const ATTRIBUTES: &'static [weng::wgpu::VertexAttribute] = &weng::graphics::vertices::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x4,
+    ];
+}
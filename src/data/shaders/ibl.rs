@@ -0,0 +1,10 @@
+pub const NAME: &str = "ibl.wgsl";
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Params {
+    pub lod: f32,
+    pub roughness: f32,
+    // Uniform buffers are padded to 16 bytes
+    pub _padding: [f32; 2],
+}
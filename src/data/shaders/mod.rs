@@ -1,3 +1,74 @@
+use anyhow::anyhow;
+use std::path::Path;
+
 pub mod basic;
+pub mod cull;
+#[cfg(feature = "gizmos")]
+pub mod gizmos;
+pub mod ibl;
+pub mod post;
+pub mod skybox;
+pub mod sprite;
+pub mod text;
+pub mod ui;
 
 pub static DIR: &str = "assets/shaders";
+
+/// The shader capabilities wgpu allows on a device with `features`, the same
+/// way wgpu derives them when it creates a shader module.
+fn capabilities(features: weng::wgpu::Features) -> naga::valid::Capabilities {
+    use naga::valid::Capabilities;
+    use weng::wgpu::Features;
+
+    let mut capabilities = Capabilities::empty();
+    let non_uniform_indexing =
+        features.contains(Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+    capabilities.set(
+        Capabilities::PUSH_CONSTANT,
+        features.contains(Features::PUSH_CONSTANTS),
+    );
+    capabilities.set(
+        Capabilities::FLOAT64,
+        features.contains(Features::SHADER_FLOAT64),
+    );
+    capabilities.set(
+        Capabilities::PRIMITIVE_INDEX,
+        features.contains(Features::SHADER_PRIMITIVE_INDEX),
+    );
+    capabilities.set(
+        Capabilities::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+        non_uniform_indexing,
+    );
+    capabilities.set(
+        Capabilities::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+        features.contains(Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING),
+    );
+    capabilities.set(
+        Capabilities::SAMPLER_NON_UNIFORM_INDEXING,
+        non_uniform_indexing,
+    );
+    capabilities.set(
+        Capabilities::STORAGE_TEXTURE_16BIT_NORM_FORMATS,
+        features.contains(Features::TEXTURE_FORMAT_16BIT_NORM),
+    );
+
+    capabilities
+}
+
+/// Parses and validates a WGSL file without touching the GPU, against what a
+/// device with `features` supports, so that a broken shader can be rejected
+/// before it replaces a working pipeline.
+pub fn validate(path: &Path, features: weng::wgpu::Features) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(path)?;
+    let path_str = path.to_string_lossy();
+
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|e| anyhow!(e.emit_to_string_with_path(&source, &path_str)))?;
+
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), capabilities(features))
+        .validate(&module)
+        .map_err(|e| anyhow!(e.emit_to_string_with_path(&source, &path_str)))?;
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+pub const NAME: &str = "post.wgsl";
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Params {
+    pub bloom_intensity: f32,
+    pub bloom_threshold: f32,
+    pub bloom_knee: f32,
+    pub exposure: f32,
+    pub tonemapper: u32,
+    pub encode_srgb: u32,
+    // Uniform buffers are padded to 16 bytes
+    pub _padding: [u32; 2],
+}
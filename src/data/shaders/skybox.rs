@@ -0,0 +1 @@
+pub const NAME: &str = "skybox.wgsl";
@@ -0,0 +1,27 @@
+pub const NAME: &str = "sprite.wgsl";
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct SpriteUniform {
+    /// Maps framebuffer pixels, from the top left corner, to clip space.
+    pub projection: [f32; 16],
+    /// 1 when the surface does not encode to sRGB on write.
+    pub encode_srgb: u32,
+    pub _padding: [u32; 3],
+}
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl weng::graphics::vertices::Vertex for Vertex {
+    const ATTRIBUTES: &'static [weng::wgpu::VertexAttribute] = &weng::graphics::vertices::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+    ];
+}
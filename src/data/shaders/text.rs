@@ -0,0 +1,28 @@
+pub const NAME: &str = "text.wgsl";
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct TextUniform {
+    /// The framebuffer size in pixels, which text positions are in.
+    pub size: [f32; 2],
+    /// 1 when the surface does not encode to sRGB on write.
+    pub encode_srgb: u32,
+    /// 1 when the atlas holds signed distances rather than coverage.
+    pub sdf: u32,
+}
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl weng::graphics::vertices::Vertex for Vertex {
+    const ATTRIBUTES: &'static [weng::wgpu::VertexAttribute] = &weng::graphics::vertices::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+    ];
+}
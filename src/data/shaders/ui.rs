@@ -0,0 +1,11 @@
+pub const NAME: &str = "ui.wgsl";
+
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ScreenUniform {
+    /// In points, which UI vertices are in.
+    pub size: [f32; 2],
+    /// 1 when the surface does not encode to sRGB on write.
+    pub encode_srgb: u32,
+    pub _padding: u32,
+}
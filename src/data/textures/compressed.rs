@@ -0,0 +1,408 @@
+use anyhow::{anyhow, bail};
+use std::path::Path;
+use weng_game::codecs::{astc, bc, bc7};
+
+/// Block compressed image data as stored in a KTX2 or DDS container.
+pub struct CompressedImage {
+    pub format: weng::wgpu::TextureFormat,
+    pub height: u32,
+    /// Mip levels from the largest to the smallest.
+    pub levels: Vec<Vec<u8>>,
+    pub width: u32,
+}
+
+impl CompressedImage {
+    pub fn bytes(&self) -> usize {
+        self.levels.iter().map(Vec::len).sum()
+    }
+
+    /// Decompresses every level on the CPU into `target`, for adapters that
+    /// lack the texture compression feature. `target` is one of the formats
+    /// [`matching_format`] accepts.
+    pub fn decompress(&self, target: weng::wgpu::TextureFormat) -> anyhow::Result<Vec<Vec<u8>>> {
+        use weng::wgpu::TextureFormat;
+
+        let format = matching_format(self.format, target)?;
+        let info = format.describe();
+
+        let decompress_level = |width: u32, height: u32, data: &[u8]| match format {
+            TextureFormat::Astc { .. } => Ok(astc::decompress(
+                info.block_dimensions.0 as u32,
+                info.block_dimensions.1 as u32,
+                info.srgb,
+                width,
+                height,
+                data,
+            )),
+            TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => {
+                Ok(bc7::decompress(width, height, data))
+            }
+            format => match bc_format(format) {
+                Some(format) => Ok(bc::decompress(format, width, height, data)),
+                None => Err(anyhow!("{format:?} cannot be decompressed")),
+            },
+        };
+
+        self.levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let (width, height) = level_size(self.width, self.height, level as u32);
+                let rgba = decompress_level(width, height, data)?;
+
+                Ok(match target {
+                    TextureFormat::R8Unorm => rgba.chunks_exact(4).map(|pixel| pixel[0]).collect(),
+                    _ => rgba,
+                })
+            })
+            .collect()
+    }
+}
+
+fn bc_format(format: weng::wgpu::TextureFormat) -> Option<bc::Format> {
+    use weng::wgpu::TextureFormat;
+
+    match format {
+        TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => Some(bc::Format::Bc1),
+        TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => Some(bc::Format::Bc3),
+        TextureFormat::Bc4RUnorm => Some(bc::Format::Bc4),
+        TextureFormat::Bc5RgUnorm => Some(bc::Format::Bc5),
+        _ => None,
+    }
+}
+
+/// The variant of a container's `format` to upload it as when it is loaded as
+/// a texture type of format `target`. Compressed textures need at least as
+/// many channels as the texture type, and are read as sRGB or linear to match
+/// it whatever their container says.
+pub fn matching_format(
+    format: weng::wgpu::TextureFormat,
+    target: weng::wgpu::TextureFormat,
+) -> anyhow::Result<weng::wgpu::TextureFormat> {
+    use weng::wgpu::{AstcChannel, TextureFormat};
+
+    let srgb = match target {
+        TextureFormat::R8Unorm | TextureFormat::Rgba8Unorm => false,
+        TextureFormat::Rgba8UnormSrgb => true,
+        target => bail!("compressed textures cannot be loaded as {target:?}"),
+    };
+
+    if format.describe().components < target.describe().components {
+        bail!("{format:?} has too few channels to be loaded as {target:?}");
+    }
+
+    Ok(match (format, srgb) {
+        (TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb, false) => {
+            TextureFormat::Bc1RgbaUnorm
+        }
+        (TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb, true) => {
+            TextureFormat::Bc1RgbaUnormSrgb
+        }
+        (TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb, false) => {
+            TextureFormat::Bc3RgbaUnorm
+        }
+        (TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb, true) => {
+            TextureFormat::Bc3RgbaUnormSrgb
+        }
+        (TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb, false) => {
+            TextureFormat::Bc7RgbaUnorm
+        }
+        (TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb, true) => {
+            TextureFormat::Bc7RgbaUnormSrgb
+        }
+        (TextureFormat::Astc { block, .. }, srgb) => TextureFormat::Astc {
+            block,
+            channel: if srgb {
+                AstcChannel::UnormSrgb
+            } else {
+                AstcChannel::Unorm
+            },
+        },
+        (format, false) => format,
+        (format, true) => bail!("{format:?} has no sRGB variant to be loaded as {target:?}"),
+    })
+}
+
+/// wgpu requires the base level of compressed textures to consist of whole blocks.
+fn check_dimensions(
+    format: weng::wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> anyhow::Result<()> {
+    let (block_width, block_height) = format.describe().block_dimensions;
+
+    if width % block_width as u32 != 0 || height % block_height as u32 != 0 {
+        bail!("{width}x{height} is not a multiple of the {format:?} block size");
+    }
+
+    Ok(())
+}
+
+/// Splits the concatenated mip levels of a container into one buffer per level.
+fn split_levels(
+    format: weng::wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level_count: u32,
+    mut data: &[u8],
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    check_dimensions(format, width, height)?;
+
+    (0..level_count.max(1))
+        .map(|level| {
+            let size = level_bytes(format, width, height, level);
+
+            if data.len() < size {
+                bail!("mip level {level} is truncated");
+            }
+
+            let (bytes, rest) = data.split_at(size);
+            data = rest;
+
+            Ok(bytes.to_vec())
+        })
+        .collect()
+}
+
+pub fn level_bytes(
+    format: weng::wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> usize {
+    let info = format.describe();
+    let (width, height) = level_size(width, height, level);
+    let blocks_wide = (width + info.block_dimensions.0 as u32 - 1) / info.block_dimensions.0 as u32;
+    let blocks_high =
+        (height + info.block_dimensions.1 as u32 - 1) / info.block_dimensions.1 as u32;
+
+    (blocks_wide * blocks_high) as usize * info.block_size as usize
+}
+
+pub fn level_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+pub fn load_dds(path: &Path) -> anyhow::Result<CompressedImage> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+    use weng::wgpu::TextureFormat;
+
+    let dds = ddsfile::Dds::read(std::fs::File::open(path)?)?;
+
+    let format = match (dds.get_dxgi_format(), dds.get_d3d_format()) {
+        (Some(DxgiFormat::BC1_UNorm), _) | (_, Some(D3DFormat::DXT1)) => {
+            TextureFormat::Bc1RgbaUnorm
+        }
+        (Some(DxgiFormat::BC1_UNorm_sRGB), _) => TextureFormat::Bc1RgbaUnormSrgb,
+        (Some(DxgiFormat::BC3_UNorm), _) | (_, Some(D3DFormat::DXT5)) => {
+            TextureFormat::Bc3RgbaUnorm
+        }
+        (Some(DxgiFormat::BC3_UNorm_sRGB), _) => TextureFormat::Bc3RgbaUnormSrgb,
+        (Some(DxgiFormat::BC4_UNorm), _) => TextureFormat::Bc4RUnorm,
+        (Some(DxgiFormat::BC5_UNorm), _) => TextureFormat::Bc5RgUnorm,
+        (Some(DxgiFormat::BC7_UNorm), _) => TextureFormat::Bc7RgbaUnorm,
+        (Some(DxgiFormat::BC7_UNorm_sRGB), _) => TextureFormat::Bc7RgbaUnormSrgb,
+        (dxgi, d3d) => bail!("unsupported dds format {dxgi:?} {d3d:?}"),
+    };
+
+    Ok(CompressedImage {
+        format,
+        height: dds.get_height(),
+        levels: split_levels(
+            format,
+            dds.get_width(),
+            dds.get_height(),
+            dds.get_num_mipmap_levels(),
+            dds.get_data(0)?,
+        )?,
+        width: dds.get_width(),
+    })
+}
+
+/// The texture format of a KTX2 ASTC format. Vulkan numbers the block sizes in
+/// this order, each as UNORM followed by SRGB.
+fn astc_format(format: ktx2::Format) -> Option<weng::wgpu::TextureFormat> {
+    use ktx2::Format;
+    use weng::wgpu::{AstcBlock, AstcChannel, TextureFormat};
+
+    const BLOCKS: [AstcBlock; 14] = [
+        AstcBlock::B4x4,
+        AstcBlock::B5x4,
+        AstcBlock::B5x5,
+        AstcBlock::B6x5,
+        AstcBlock::B6x6,
+        AstcBlock::B8x5,
+        AstcBlock::B8x6,
+        AstcBlock::B8x8,
+        AstcBlock::B10x5,
+        AstcBlock::B10x6,
+        AstcBlock::B10x8,
+        AstcBlock::B10x10,
+        AstcBlock::B12x10,
+        AstcBlock::B12x12,
+    ];
+
+    let index = format
+        .0
+        .get()
+        .checked_sub(Format::ASTC_4x4_UNORM_BLOCK.0.get())?;
+
+    Some(TextureFormat::Astc {
+        block: *BLOCKS.get(index as usize / 2)?,
+        channel: if index % 2 == 0 {
+            AstcChannel::Unorm
+        } else {
+            AstcChannel::UnormSrgb
+        },
+    })
+}
+
+pub fn load_ktx2(path: &Path) -> anyhow::Result<CompressedImage> {
+    use ktx2::Format;
+    use weng::wgpu::TextureFormat;
+
+    let bytes = std::fs::read(path)?;
+    let reader = ktx2::Reader::new(bytes.as_slice())?;
+    let header = reader.header();
+
+    if let Some(scheme) = header.supercompression_scheme {
+        bail!("supercompressed ktx2 files are not supported ({scheme:?})");
+    }
+
+    let format = match header.format {
+        Some(Format::BC1_RGBA_UNORM_BLOCK) => TextureFormat::Bc1RgbaUnorm,
+        Some(Format::BC1_RGBA_SRGB_BLOCK) => TextureFormat::Bc1RgbaUnormSrgb,
+        Some(Format::BC3_UNORM_BLOCK) => TextureFormat::Bc3RgbaUnorm,
+        Some(Format::BC3_SRGB_BLOCK) => TextureFormat::Bc3RgbaUnormSrgb,
+        Some(Format::BC4_UNORM_BLOCK) => TextureFormat::Bc4RUnorm,
+        Some(Format::BC5_UNORM_BLOCK) => TextureFormat::Bc5RgUnorm,
+        Some(Format::BC7_UNORM_BLOCK) => TextureFormat::Bc7RgbaUnorm,
+        Some(Format::BC7_SRGB_BLOCK) => TextureFormat::Bc7RgbaUnormSrgb,
+        Some(format) => match astc_format(format) {
+            Some(format) => format,
+            None => bail!("unsupported ktx2 format {format:?}"),
+        },
+        None => bail!("ktx2 files without a format are not supported"),
+    };
+
+    check_dimensions(format, header.pixel_width, header.pixel_height.max(1))?;
+
+    let levels = reader.levels().map(<[u8]>::to_vec).collect::<Vec<_>>();
+
+    for (level, data) in levels.iter().enumerate() {
+        if data.len()
+            != level_bytes(
+                format,
+                header.pixel_width,
+                header.pixel_height,
+                level as u32,
+            )
+        {
+            bail!("mip level {level} has an unexpected size");
+        }
+    }
+
+    Ok(CompressedImage {
+        format,
+        height: header.pixel_height.max(1),
+        levels,
+        width: header.pixel_width,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weng::wgpu::{AstcBlock, AstcChannel, TextureFormat};
+
+    #[test]
+    fn containers_are_read_as_srgb_or_linear_to_match_the_texture_type() {
+        let astc = |channel| TextureFormat::Astc {
+            block: AstcBlock::B6x6,
+            channel,
+        };
+
+        for (format, target, matching) in [
+            (
+                TextureFormat::Bc1RgbaUnorm,
+                TextureFormat::Rgba8UnormSrgb,
+                TextureFormat::Bc1RgbaUnormSrgb,
+            ),
+            (
+                TextureFormat::Bc7RgbaUnormSrgb,
+                TextureFormat::Rgba8Unorm,
+                TextureFormat::Bc7RgbaUnorm,
+            ),
+            (
+                astc(AstcChannel::Unorm),
+                TextureFormat::Rgba8UnormSrgb,
+                astc(AstcChannel::UnormSrgb),
+            ),
+            (
+                TextureFormat::Bc3RgbaUnormSrgb,
+                TextureFormat::R8Unorm,
+                TextureFormat::Bc3RgbaUnorm,
+            ),
+            (
+                TextureFormat::Bc4RUnorm,
+                TextureFormat::R8Unorm,
+                TextureFormat::Bc4RUnorm,
+            ),
+        ] {
+            assert_eq!(matching_format(format, target).unwrap(), matching);
+        }
+    }
+
+    #[test]
+    fn every_ktx2_astc_format_is_recognized() {
+        use ktx2::Format;
+
+        for (format, block, channel) in [
+            (
+                Format::ASTC_4x4_UNORM_BLOCK,
+                AstcBlock::B4x4,
+                AstcChannel::Unorm,
+            ),
+            (
+                Format::ASTC_5x4_SRGB_BLOCK,
+                AstcBlock::B5x4,
+                AstcChannel::UnormSrgb,
+            ),
+            (
+                Format::ASTC_8x6_UNORM_BLOCK,
+                AstcBlock::B8x6,
+                AstcChannel::Unorm,
+            ),
+            (
+                Format::ASTC_10x10_SRGB_BLOCK,
+                AstcBlock::B10x10,
+                AstcChannel::UnormSrgb,
+            ),
+            (
+                Format::ASTC_12x12_SRGB_BLOCK,
+                AstcBlock::B12x12,
+                AstcChannel::UnormSrgb,
+            ),
+        ] {
+            assert_eq!(
+                astc_format(format),
+                Some(TextureFormat::Astc { block, channel })
+            );
+        }
+
+        for format in [Format::BC7_SRGB_BLOCK, Format::R8G8B8A8_UNORM] {
+            assert_eq!(astc_format(format), None);
+        }
+    }
+
+    #[test]
+    fn containers_without_a_matching_variant_are_rejected() {
+        for (format, target) in [
+            (TextureFormat::Bc5RgUnorm, TextureFormat::Rgba8Unorm),
+            (TextureFormat::Bc4RUnorm, TextureFormat::Rgba8UnormSrgb),
+            (TextureFormat::Bc7RgbaUnorm, TextureFormat::Rgba16Float),
+        ] {
+            assert!(matching_format(format, target).is_err());
+        }
+    }
+}
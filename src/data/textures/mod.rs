@@ -1,34 +1,457 @@
-use image::ImageError;
-use std::path::Path;
+pub mod compressed;
+
+use anyhow::bail;
+use compressed::CompressedImage;
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Pixel, Rgba};
+use std::{
+    num::{NonZeroU32, NonZeroU8},
+    path::{Path, PathBuf},
+};
 
 pub static DIR: &str = "assets/textures";
 
+/// A decoded texture file. Block compressed containers are uploaded as they
+/// are, everything else is converted to the format of the texture type it is
+/// loaded as.
+pub enum Image {
+    Compressed(CompressedImage),
+    Uncompressed(DynamicImage),
+}
+
+impl Image {
+    /// GPU memory used by the image once uploaded as `format`, including mip
+    /// levels.
+    pub fn bytes(&self, format: weng::wgpu::TextureFormat) -> usize {
+        match self {
+            Image::Compressed(image) => image.bytes(),
+            Image::Uncompressed(image) => mip_chain_bytes(format, image.width(), image.height()),
+        }
+    }
+}
+
+/// How a texture is sampled. Anisotropic filtering is only applied when every
+/// filter is linear, as wgpu requires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Sampling {
+    pub address_mode: weng::wgpu::AddressMode,
+    pub anisotropy: u8,
+    pub filter: weng::wgpu::FilterMode,
+    pub mipmap_filter: weng::wgpu::FilterMode,
+}
+
+impl Sampling {
+    pub const DEFAULT: Self = Self {
+        address_mode: weng::wgpu::AddressMode::Repeat,
+        anisotropy: 16,
+        filter: weng::wgpu::FilterMode::Linear,
+        mipmap_filter: weng::wgpu::FilterMode::Linear,
+    };
+
+    pub fn descriptor(&self) -> weng::wgpu::SamplerDescriptor<'static> {
+        let linear = self.filter == weng::wgpu::FilterMode::Linear
+            && self.mipmap_filter == weng::wgpu::FilterMode::Linear;
+
+        weng::wgpu::SamplerDescriptor {
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: NonZeroU8::new(self.anisotropy)
+                .filter(|anisotropy| linear && anisotropy.get() > 1),
+            ..Default::default()
+        }
+    }
+}
+
 pub trait Type {
     const FORMAT: weng::wgpu::TextureFormat;
+    const SAMPLING: Sampling = Sampling::DEFAULT;
 }
 
 pub struct Diffuse;
+pub struct Emissive;
+/// High dynamic range images such as `.hdr` and `.exr` environment maps.
+pub struct Hdr;
+/// Displacement maps for parallax mapping, white at the surface and black at
+/// the deepest point.
+pub struct Height;
 pub struct Normal;
+/// Occlusion, roughness and metallic maps packed into the red, green and blue
+/// channels, see [`pack_orm`].
+pub struct Orm;
+/// 2D images drawn on screen, which are not tiled.
+pub struct Sprite;
 
 impl Type for Diffuse {
     const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba8UnormSrgb;
 }
 
+impl Type for Emissive {
+    const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba8UnormSrgb;
+}
+
+impl Type for Hdr {
+    // Unlike Rgba32Float, half floats can be filtered without extra features.
+    const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba16Float;
+}
+
+impl Type for Height {
+    const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::R8Unorm;
+}
+
 impl Type for Normal {
     const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba8Unorm;
 }
 
-pub fn load<T: Type>(
+impl Type for Orm {
+    const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba8Unorm;
+}
+
+impl Type for Sprite {
+    const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba8UnormSrgb;
+    const SAMPLING: Sampling = Sampling {
+        address_mode: weng::wgpu::AddressMode::ClampToEdge,
+        anisotropy: 1,
+        ..Sampling::DEFAULT
+    };
+}
+
+/// Where the pixels of a texture come from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// A single pixel, for maps a material leaves out.
+    Color([u8; 4]),
+    File(PathBuf),
+    /// Separate maps packed by [`pack_orm`].
+    Orm {
+        metallic: Option<PathBuf>,
+        occlusion: Option<PathBuf>,
+        roughness: Option<PathBuf>,
+    },
+}
+
+impl Source {
+    pub fn decode(&self) -> anyhow::Result<Image> {
+        match self {
+            Source::Color(color) => Ok(Image::Uncompressed(DynamicImage::ImageRgba8(
+                ImageBuffer::from_pixel(1, 1, Rgba(*color)),
+            ))),
+            Source::File(path) => decode(path),
+            Source::Orm {
+                metallic,
+                occlusion,
+                roughness,
+            } => pack_orm(
+                occlusion.as_deref(),
+                roughness.as_deref(),
+                metallic.as_deref(),
+            ),
+        }
+    }
+
+    /// The files the texture is built from.
+    pub fn files(&self) -> Vec<&Path> {
+        match self {
+            Source::Color(_) => Vec::new(),
+            Source::File(path) => vec![path],
+            Source::Orm {
+                metallic,
+                occlusion,
+                roughness,
+            } => [occlusion, roughness, metallic]
+                .into_iter()
+                .flatten()
+                .map(PathBuf::as_path)
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Source::Color([r, g, b, a]) => write!(f, "#{r:02x}{g:02x}{b:02x}{a:02x}"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Orm { .. } => {
+                let files = self.files();
+                let names = files.iter().map(|path| path.display().to_string());
+
+                write!(f, "orm of [{}]", names.collect::<Vec<_>>().join(", "))
+            }
+        }
+    }
+}
+
+pub struct Texture {
+    pub sampler: weng::wgpu::Sampler,
+    pub texture: weng::wgpu::Texture,
+    pub view: weng::wgpu::TextureView,
+}
+
+/// Uploads `image`. Uncompressed images are converted to `format` and get a
+/// full mip chain, downsampled from level to level on the CPU. Compressed
+/// images keep the mip levels of their container, in the variant of its
+/// format that matches `format`, and are decompressed to `format` on the CPU
+/// if the adapter lacks support for them.
+pub fn create(
     graphics: &weng::graphics::Context,
-    path: &Path,
-) -> Result<weng::graphics::textures::Texture, ImageError> {
-    image::open(path).map(|image| {
-        let rgba8 = image.to_rgba8();
-        graphics.create_texture(
-            &rgba8,
-            rgba8.dimensions().0,
-            rgba8.dimensions().1,
-            T::FORMAT,
-        )
+    image: &Image,
+    format: weng::wgpu::TextureFormat,
+    sampling: Sampling,
+) -> anyhow::Result<Texture> {
+    match image {
+        Image::Compressed(image) => {
+            let compressed_format = compressed::matching_format(image.format, format)?;
+            let required_features = compressed_format.describe().required_features;
+
+            if graphics.device().features().contains(required_features) {
+                Ok(upload(
+                    graphics,
+                    compressed_format,
+                    image.width,
+                    image.height,
+                    &image.levels,
+                    sampling,
+                ))
+            } else {
+                log::warn!(
+                    "{:?} is unsupported by the adapter, decompressing on the CPU",
+                    compressed_format
+                );
+
+                Ok(upload(
+                    graphics,
+                    format,
+                    image.width,
+                    image.height,
+                    &image.decompress(format)?,
+                    sampling,
+                ))
+            }
+        }
+        Image::Uncompressed(image) => Ok(upload(
+            graphics,
+            format,
+            image.width(),
+            image.height(),
+            &convert(image, format)?,
+            sampling,
+        )),
+    }
+}
+
+pub fn decode(path: &Path) -> anyhow::Result<Image> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("dds") => compressed::load_dds(path).map(Image::Compressed),
+        Some("hdr") => load_hdr(path).map(Image::Uncompressed),
+        Some("ktx2") => compressed::load_ktx2(path).map(Image::Compressed),
+        _ => Ok(Image::Uncompressed(image::open(path)?)),
+    }
+}
+
+/// `image::open` tone maps Radiance files to 8 bits, so they are decoded
+/// separately to keep their full range.
+fn load_hdr(path: &Path) -> anyhow::Result<DynamicImage> {
+    let decoder =
+        image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(std::fs::File::open(path)?))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr()?;
+
+    image::Rgb32FImage::from_raw(
+        metadata.width,
+        metadata.height,
+        pixels.into_iter().flat_map(|pixel| pixel.0).collect(),
+    )
+    .map(DynamicImage::ImageRgb32F)
+    .ok_or_else(|| anyhow::anyhow!("{} has an unexpected size", path.display()))
+}
+
+pub fn load<T: Type>(graphics: &weng::graphics::Context, path: &Path) -> anyhow::Result<Texture> {
+    create(graphics, &decode(path)?, T::FORMAT, T::SAMPLING)
+}
+
+/// Size of a full mip chain of an uncompressed image.
+pub fn mip_chain_bytes(format: weng::wgpu::TextureFormat, width: u32, height: u32) -> usize {
+    (0..mip_level_count(width, height))
+        .map(|level| compressed::level_bytes(format, width, height, level))
+        .sum()
+}
+
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// Packs separate occlusion, roughness and metallic maps into one [`Orm`]
+/// image. Missing maps default to no occlusion, full roughness and no
+/// metalness, and without any the image is a single pixel. The maps that are
+/// given must all have the same size.
+pub fn pack_orm(
+    occlusion: Option<&Path>,
+    roughness: Option<&Path>,
+    metallic: Option<&Path>,
+) -> anyhow::Result<Image> {
+    let mut channels = Vec::new();
+
+    for path in [occlusion, roughness, metallic] {
+        channels.push(
+            path.map(|path| image::open(path).map(|image| red_channel(&image)))
+                .transpose()?,
+        );
+    }
+
+    let (width, height) = channels
+        .iter()
+        .flatten()
+        .map(GrayImage::dimensions)
+        .next()
+        .unwrap_or((1, 1));
+
+    if channels
+        .iter()
+        .flatten()
+        .any(|channel| channel.dimensions() != (width, height))
+    {
+        bail!("orm maps differ in size");
+    }
+
+    let defaults = [255, 255, 0];
+
+    Ok(Image::Uncompressed(DynamicImage::ImageRgba8(
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let [r, g, b] = std::array::from_fn(|c| {
+                channels[c]
+                    .as_ref()
+                    .map_or(defaults[c], |channel| channel.get_pixel(x, y)[0])
+            });
+
+            Rgba([r, g, b, 255])
+        }),
+    )))
+}
+
+/// Converts `image` to `format` and returns the bytes of every mip level.
+pub fn convert(
+    image: &DynamicImage,
+    format: weng::wgpu::TextureFormat,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    use weng::wgpu::TextureFormat;
+
+    Ok(match format {
+        TextureFormat::R8Unorm => mip_chain(red_channel(image))
+            .into_iter()
+            .map(ImageBuffer::into_raw)
+            .collect(),
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => mip_chain(image.to_rgba8())
+            .into_iter()
+            .map(ImageBuffer::into_raw)
+            .collect(),
+        TextureFormat::Rgba16Float => mip_chain(image.to_rgba32f())
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .flat_map(|&value| half::f16::from_f32(value).to_le_bytes())
+                    .collect()
+            })
+            .collect(),
+        format => bail!("images cannot be converted to {format:?}"),
+    })
+}
+
+fn mip_chain<P>(image: ImageBuffer<P, Vec<P::Subpixel>>) -> Vec<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: 'static,
+{
+    let mut levels = vec![image];
+
+    for _ in 1..mip_level_count(levels[0].width(), levels[0].height()) {
+        let previous = levels.last().unwrap();
+
+        levels.push(image::imageops::resize(
+            previous,
+            (previous.width() / 2).max(1),
+            (previous.height() / 2).max(1),
+            image::imageops::FilterType::Triangle,
+        ));
+    }
+
+    levels
+}
+
+/// Single channel maps are usually grayscale, but some only fill the red
+/// channel of an RGB file. Taking the red channel works for both.
+fn red_channel(image: &DynamicImage) -> GrayImage {
+    let rgba = image.to_rgba8();
+
+    GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        Luma([rgba.get_pixel(x, y)[0]])
     })
 }
+
+fn upload(
+    graphics: &weng::graphics::Context,
+    format: weng::wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    levels: &[impl AsRef<[u8]>],
+    sampling: Sampling,
+) -> Texture {
+    let info = format.describe();
+    let (block_width, block_height) = (
+        info.block_dimensions.0 as u32,
+        info.block_dimensions.1 as u32,
+    );
+
+    let texture = graphics
+        .device()
+        .create_texture(&weng::wgpu::TextureDescriptor {
+            label: None,
+            size: weng::wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: weng::wgpu::TextureDimension::D2,
+            format,
+            usage: weng::wgpu::TextureUsages::TEXTURE_BINDING | weng::wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+    for (mip_level, data) in levels.iter().enumerate() {
+        let (level_width, level_height) = compressed::level_size(width, height, mip_level as u32);
+        let blocks_wide = (level_width + block_width - 1) / block_width;
+        let blocks_high = (level_height + block_height - 1) / block_height;
+
+        graphics.queue().write_texture(
+            weng::wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: mip_level as u32,
+                origin: weng::wgpu::Origin3d::ZERO,
+                aspect: weng::wgpu::TextureAspect::All,
+            },
+            data.as_ref(),
+            weng::wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(blocks_wide * info.block_size as u32),
+                rows_per_image: NonZeroU32::new(blocks_high),
+            },
+            // Copies of compressed textures have to cover whole blocks.
+            weng::wgpu::Extent3d {
+                width: blocks_wide * block_width,
+                height: blocks_high * block_height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    Texture {
+        sampler: graphics.device().create_sampler(&sampling.descriptor()),
+        view: texture.create_view(&weng::wgpu::TextureViewDescriptor::default()),
+        texture,
+    }
+}
@@ -0,0 +1,268 @@
+//! A small entity component system. Components of a type are stored together,
+//! indexed by entity, behind a `RefCell` so that a system can borrow several
+//! of them at once. Borrowing the same components mutably twice panics, like
+//! the `RefCell` would.
+
+mod schedule;
+
+pub use schedule::Schedule;
+
+use std::{
+    any::{Any, TypeId},
+    cell::{Ref, RefCell, RefMut},
+    collections::HashMap,
+};
+
+/// The generation makes ids of despawned entities stale instead of silently
+/// pointing at whatever reuses the slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    generation: u32,
+    index: u32,
+}
+
+/// The components of one type.
+pub struct Components<T> {
+    slots: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Components<T> {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index as usize) {
+            Some(Some((generation, component))) if *generation == entity.generation => {
+                Some(component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index as usize) {
+            Some(Some((generation, component))) if *generation == entity.generation => {
+                Some(component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(generation, component)| {
+                (
+                    Entity {
+                        generation: *generation,
+                        index: index as u32,
+                    },
+                    component,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut().map(|(generation, component)| {
+                    (
+                        Entity {
+                            generation: *generation,
+                            index: index as u32,
+                        },
+                        component,
+                    )
+                })
+            })
+    }
+
+    fn insert(&mut self, entity: Entity, component: T) {
+        let index = entity.index as usize;
+
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        self.slots[index] = Some((entity.generation, component));
+    }
+
+    fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index as usize)?;
+
+        match slot {
+            Some((generation, _)) if *generation == entity.generation => {
+                slot.take().map(|(_, component)| component)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Lets a despawned entity lose its components without knowing their types.
+trait Storage {
+    fn as_any(&self) -> &dyn Any;
+    fn remove(&mut self, entity: Entity);
+}
+
+impl<T: 'static> Storage for RefCell<Components<T>> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        self.get_mut().remove(entity);
+    }
+}
+
+/// Entities, their components, and resources, which are values that exist
+/// once rather than per entity.
+pub struct World {
+    /// The current generation of every slot.
+    generations: Vec<u32>,
+    /// Slots of despawned entities, to reuse.
+    free: Vec<u32>,
+    resources: HashMap<TypeId, Box<dyn Any>>,
+    storages: HashMap<TypeId, Box<dyn Storage>>,
+}
+
+impl World {
+    /// Panics if `T` was never registered, or if its components are borrowed
+    /// mutably.
+    pub fn components<T: 'static>(&self) -> Ref<'_, Components<T>> {
+        self.storage::<T>().borrow()
+    }
+
+    /// Panics if `T` was never registered, or if its components are borrowed.
+    pub fn components_mut<T: 'static>(&self) -> RefMut<'_, Components<T>> {
+        self.storage::<T>().borrow_mut()
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        for storage in self.storages.values_mut() {
+            storage.remove(entity);
+        }
+
+        self.generations[entity.index as usize] += 1;
+        self.free.push(entity.index);
+    }
+
+    /// Adds `component` to `entity`, replacing one of the same type.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        self.register::<T>();
+        self.storage::<T>().borrow_mut().insert(entity, component);
+    }
+
+    pub fn insert_resource<T: 'static>(&mut self, resource: T) {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(RefCell::new(resource)));
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index as usize) == Some(&entity.generation)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            generations: Vec::new(),
+            free: Vec::new(),
+            resources: HashMap::new(),
+            storages: HashMap::new(),
+        }
+    }
+
+    /// Makes `T` a component type, so that systems can go through its
+    /// components before any entity has one. Inserting a component does it
+    /// too.
+    pub fn register<T: 'static>(&mut self) {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Components::<T>::new())));
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.storages.contains_key(&TypeId::of::<T>()) {
+            return None;
+        }
+
+        self.storage::<T>().borrow_mut().remove(entity)
+    }
+
+    /// Panics if the resource is missing or borrowed mutably.
+    pub fn resource<T: 'static>(&self) -> Ref<'_, T> {
+        self.resource_cell::<T>().borrow()
+    }
+
+    /// Panics if the resource is missing or borrowed.
+    pub fn resource_mut<T: 'static>(&self) -> RefMut<'_, T> {
+        self.resource_cell::<T>().borrow_mut()
+    }
+
+    /// A new entity, which components are added to with the returned builder.
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        let entity = match self.free.pop() {
+            Some(index) => Entity {
+                generation: self.generations[index as usize],
+                index,
+            },
+            None => {
+                self.generations.push(0);
+
+                Entity {
+                    generation: 0,
+                    index: self.generations.len() as u32 - 1,
+                }
+            }
+        };
+
+        EntityBuilder {
+            entity,
+            world: self,
+        }
+    }
+
+    fn resource_cell<T: 'static>(&self) -> &RefCell<T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|resource| resource.downcast_ref::<RefCell<T>>())
+            .unwrap_or_else(|| panic!("no {} resource", std::any::type_name::<T>()))
+    }
+
+    fn storage<T: 'static>(&self) -> &RefCell<Components<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref())
+            .unwrap_or_else(|| panic!("{} is not a component", std::any::type_name::<T>()))
+    }
+}
+
+pub struct EntityBuilder<'a> {
+    entity: Entity,
+    world: &'a mut World,
+}
+
+impl EntityBuilder<'_> {
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn with<T: 'static>(self, component: T) -> Self {
+        self.world.insert(self.entity, component);
+
+        self
+    }
+}
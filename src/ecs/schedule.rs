@@ -0,0 +1,53 @@
+use super::World;
+use crate::time::{Blend, Time};
+
+type System = Box<dyn FnMut(&mut World)>;
+
+/// Systems in the order they were added, some run every tick and some every
+/// frame.
+pub struct Schedule {
+    frame: Vec<System>,
+    tick: Vec<System>,
+}
+
+impl Schedule {
+    /// Adds a system that runs once a frame, after the ticks, such as one that
+    /// gathers what to draw.
+    pub fn add_frame_system(&mut self, system: impl FnMut(&mut World) + 'static) -> &mut Self {
+        self.frame.push(Box::new(system));
+
+        self
+    }
+
+    /// Adds a system that runs at [`Time::TICK_RATE`], such as movement.
+    pub fn add_tick_system(&mut self, system: impl FnMut(&mut World) + 'static) -> &mut Self {
+        self.tick.push(Box::new(system));
+
+        self
+    }
+
+    pub fn new() -> Self {
+        Self {
+            frame: Vec::new(),
+            tick: Vec::new(),
+        }
+    }
+
+    /// Runs the tick systems once for every tick `time` has accumulated, then
+    /// the frame systems with the [`Blend`] of what is left.
+    pub fn run(&mut self, world: &mut World, time: &mut Time) {
+        while time.should_update() {
+            for system in &mut self.tick {
+                system(world);
+            }
+
+            time.update();
+        }
+
+        world.insert_resource(Blend(time.blend_factor() as f32));
+
+        for system in &mut self.frame {
+            system(world);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Immediate mode debug drawing. Shapes are added during a frame, by the
+//! gizmos system and whatever it calls, and drawn as unlit lines over that
+//! frame until the next one clears them.
+//!
+//! Without the `gizmos` feature, every call does nothing and the lines are
+//! never stored nor drawn. Callers skip work that only feeds the gizmos by
+//! checking [`Gizmos::enabled`], which is then always false.
+
+use crate::camera::Camera;
+
+#[cfg(feature = "gizmos")]
+use crate::data::shaders::gizmos::Vertex;
+
+pub const RED: glam::Vec4 = glam::Vec4::new(1.0, 0.0, 0.0, 1.0);
+pub const GREEN: glam::Vec4 = glam::Vec4::new(0.0, 1.0, 0.0, 1.0);
+pub const BLUE: glam::Vec4 = glam::Vec4::new(0.0, 0.0, 1.0, 1.0);
+pub const YELLOW: glam::Vec4 = glam::Vec4::new(1.0, 1.0, 0.0, 1.0);
+pub const WHITE: glam::Vec4 = glam::Vec4::ONE;
+
+/// The corners a box edge joins, with the bits of an index picking the
+/// maximum along x, y and z.
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// The lines a character of a label is made of, on a cell 1 wide and 2 high.
+/// [`glyph`] picks them by letter, from `a` for the first.
+const SEGMENTS: [(glam::Vec2, glam::Vec2); 16] = [
+    (glam::Vec2::new(0.0, 2.0), glam::Vec2::new(0.5, 2.0)),
+    (glam::Vec2::new(0.5, 2.0), glam::Vec2::new(1.0, 2.0)),
+    (glam::Vec2::new(1.0, 2.0), glam::Vec2::new(1.0, 1.0)),
+    (glam::Vec2::new(1.0, 1.0), glam::Vec2::new(1.0, 0.0)),
+    (glam::Vec2::new(1.0, 0.0), glam::Vec2::new(0.5, 0.0)),
+    (glam::Vec2::new(0.5, 0.0), glam::Vec2::new(0.0, 0.0)),
+    (glam::Vec2::new(0.0, 0.0), glam::Vec2::new(0.0, 1.0)),
+    (glam::Vec2::new(0.0, 1.0), glam::Vec2::new(0.0, 2.0)),
+    (glam::Vec2::new(0.0, 1.0), glam::Vec2::new(0.5, 1.0)),
+    (glam::Vec2::new(0.5, 1.0), glam::Vec2::new(1.0, 1.0)),
+    (glam::Vec2::new(0.0, 2.0), glam::Vec2::new(0.5, 1.0)),
+    (glam::Vec2::new(0.5, 2.0), glam::Vec2::new(0.5, 1.0)),
+    (glam::Vec2::new(1.0, 2.0), glam::Vec2::new(0.5, 1.0)),
+    (glam::Vec2::new(0.0, 0.0), glam::Vec2::new(0.5, 1.0)),
+    (glam::Vec2::new(0.5, 0.0), glam::Vec2::new(0.5, 1.0)),
+    (glam::Vec2::new(1.0, 0.0), glam::Vec2::new(0.5, 1.0)),
+];
+
+/// The debug shapes of the current frame.
+pub struct Gizmos {
+    camera_position: glam::Vec3,
+    camera_right: glam::Vec3,
+    camera_up: glam::Vec3,
+    enabled: bool,
+    #[cfg(feature = "gizmos")]
+    vertices: Vec<Vertex>,
+    vectors: bool,
+}
+
+impl Gizmos {
+    /// A box between `min` and `max`.
+    pub fn aabb(&mut self, min: glam::Vec3, max: glam::Vec3, color: glam::Vec4) {
+        let corner = |index: usize| {
+            glam::Vec3::select(
+                glam::BVec3::new(index & 1 != 0, index & 2 != 0, index & 4 != 0),
+                max,
+                min,
+            )
+        };
+
+        for (a, b) in BOX_EDGES {
+            self.line(corner(a), corner(b), color);
+        }
+    }
+
+    /// A line from `start` to `end` with a head at `end`.
+    pub fn arrow(&mut self, start: glam::Vec3, end: glam::Vec3, color: glam::Vec4) {
+        let direction = end - start;
+        let length = direction.length();
+
+        self.line(start, end, color);
+
+        if length == 0.0 {
+            return;
+        }
+
+        let (side, other) = (direction / length).any_orthonormal_pair();
+        let back = end - direction * 0.2;
+
+        for offset in [side, -side, other, -other] {
+            self.line(end, back + offset * length * 0.1, color);
+        }
+    }
+
+    /// The x, y and z axes of `transform` in red, green and blue.
+    pub fn axes(&mut self, transform: glam::Mat4, length: f32) {
+        let origin = transform.transform_point3(glam::Vec3::ZERO);
+
+        for (axis, color) in [
+            (glam::Vec3::X, RED),
+            (glam::Vec3::Y, GREEN),
+            (glam::Vec3::Z, BLUE),
+        ] {
+            self.arrow(origin, transform.transform_point3(axis * length), color);
+        }
+    }
+
+    /// Two spheres joined by four lines, around the segment from `start` to
+    /// `end`.
+    pub fn capsule(&mut self, start: glam::Vec3, end: glam::Vec3, radius: f32, color: glam::Vec4) {
+        let (side, other) = (end - start).normalize_or_zero().any_orthonormal_pair();
+
+        self.sphere(start, radius, color);
+        self.sphere(end, radius, color);
+
+        for offset in [side, -side, other, -other] {
+            self.line(start + offset * radius, end + offset * radius, color);
+        }
+    }
+
+    /// A box reaching `half_extents` out from the origin of `transform` along
+    /// its axes.
+    pub fn cuboid(&mut self, transform: glam::Mat4, half_extents: glam::Vec3, color: glam::Vec4) {
+        let corner = |index: usize| {
+            transform.transform_point3(glam::Vec3::select(
+                glam::BVec3::new(index & 1 != 0, index & 2 != 0, index & 4 != 0),
+                half_extents,
+                -half_extents,
+            ))
+        };
+
+        for (a, b) in BOX_EDGES {
+            self.line(corner(a), corner(b), color);
+        }
+    }
+
+    /// Clears the shapes of the last frame. Labels added afterwards face
+    /// `camera`.
+    pub fn begin(&mut self, camera: &Camera) {
+        #[cfg(feature = "gizmos")]
+        self.vertices.clear();
+
+        self.camera_position = camera.position();
+        self.camera_right = camera.right();
+        self.camera_up = camera.direction().cross(self.camera_right).normalize();
+    }
+
+    pub fn enabled(&self) -> bool {
+        cfg!(feature = "gizmos") && self.enabled
+    }
+
+    /// The edges of the frustum that `view_projection` maps to clip space.
+    pub fn frustum(&mut self, view_projection: glam::Mat4, color: glam::Vec4) {
+        let inverse = view_projection.inverse();
+        let corner = |index: usize| {
+            inverse.project_point3(glam::Vec3::new(
+                if index & 1 != 0 { 1.0 } else { -1.0 },
+                if index & 2 != 0 { 1.0 } else { -1.0 },
+                if index & 4 != 0 { 1.0 } else { 0.0 },
+            ))
+        };
+
+        for (a, b) in BOX_EDGES {
+            self.line(corner(a), corner(b), color);
+        }
+    }
+
+    /// A square grid on the xz plane around `center`, `cells` wide.
+    pub fn grid(&mut self, center: glam::Vec3, cells: u32, spacing: f32, color: glam::Vec4) {
+        let half = cells as f32 * spacing / 2.0;
+
+        for index in 0..=cells {
+            let offset = index as f32 * spacing - half;
+
+            self.line(
+                center + glam::Vec3::new(offset, 0.0, -half),
+                center + glam::Vec3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                center + glam::Vec3::new(-half, 0.0, offset),
+                center + glam::Vec3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    pub fn line(&mut self, start: glam::Vec3, end: glam::Vec3, color: glam::Vec4) {
+        #[cfg(not(feature = "gizmos"))]
+        let _ = (start, end, color);
+        #[cfg(feature = "gizmos")]
+        if self.enabled {
+            let color = color.to_array();
+
+            self.vertices.push(Vertex {
+                position: start.to_array(),
+                color,
+            });
+            self.vertices.push(Vertex {
+                position: end.to_array(),
+                color,
+            });
+        }
+    }
+
+    pub fn new() -> Self {
+        Self {
+            camera_position: glam::Vec3::ZERO,
+            camera_right: glam::Vec3::X,
+            camera_up: glam::Vec3::Y,
+            enabled: true,
+            #[cfg(feature = "gizmos")]
+            vertices: Vec::new(),
+            vectors: false,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Whether the normals and tangents of vertices are drawn too, from the
+    /// debug settings.
+    pub fn set_vectors(&mut self, vectors: bool) {
+        self.vectors = vectors;
+    }
+
+    /// Three circles around the axes.
+    pub fn sphere(&mut self, center: glam::Vec3, radius: f32, color: glam::Vec4) {
+        const STEPS: usize = 24;
+
+        for (a, b) in [
+            (glam::Vec3::X, glam::Vec3::Y),
+            (glam::Vec3::Y, glam::Vec3::Z),
+            (glam::Vec3::Z, glam::Vec3::X),
+        ] {
+            let point = |index: usize| {
+                let (sin, cos) = (index as f32 / STEPS as f32 * std::f32::consts::TAU).sin_cos();
+
+                center + (a * cos + b * sin) * radius
+            };
+
+            for index in 0..STEPS {
+                self.line(point(index), point(index + 1), color);
+            }
+        }
+    }
+
+    /// A label facing the camera, starting at `position` and keeping about
+    /// the same size on screen. Only some ASCII characters have a glyph.
+    pub fn text(&mut self, position: glam::Vec3, text: &str, color: glam::Vec4) {
+        if !self.enabled() {
+            return;
+        }
+
+        let size = self.camera_position.distance(position) * 0.01;
+
+        for (index, character) in text.chars().enumerate() {
+            let origin = position + self.camera_right * index as f32 * size * 1.5;
+
+            for (segment, (start, end)) in SEGMENTS.iter().enumerate() {
+                if glyph(character) & 1 << segment != 0 {
+                    self.line(
+                        origin + (self.camera_right * start.x + self.camera_up * start.y) * size,
+                        origin + (self.camera_right * end.x + self.camera_up * end.y) * size,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn vectors(&self) -> bool {
+        self.vectors
+    }
+
+    #[cfg(feature = "gizmos")]
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+}
+
+/// The lit [`SEGMENTS`] of `character`, as a mask.
+fn glyph(character: char) -> u16 {
+    let segments = match character.to_ascii_uppercase() {
+        ' ' => "",
+        '0' => "abcdefghmn",
+        '1' => "cdm",
+        '2' => "abcijgef",
+        '3' => "abcdefj",
+        '4' => "hijcd",
+        '5' => "abhijdef",
+        '6' => "abhgfedji",
+        '7' => "abcd",
+        '8' => "abcdefghij",
+        '9' => "abchijdef",
+        'A' => "abcdghij",
+        'B' => "abcdefjlo",
+        'C' => "abefgh",
+        'D' => "abcdeflo",
+        'E' => "abefghi",
+        'F' => "abghi",
+        'G' => "abhgfedj",
+        'H' => "cdghij",
+        'I' => "abeflo",
+        'J' => "cdefg",
+        'K' => "ghimp",
+        'L' => "efgh",
+        'M' => "cdghkm",
+        'N' => "cdghkp",
+        'O' => "abcdefgh",
+        'P' => "abcghij",
+        'Q' => "abcdefghp",
+        'R' => "abcghijp",
+        'S' => "abhijdef",
+        'T' => "ablo",
+        'U' => "cdefgh",
+        'V' => "hmn",
+        'W' => "cdghnp",
+        'X' => "kmnp",
+        'Y' => "kmo",
+        'Z' => "abmnef",
+        '-' => "ij",
+        '+' => "ijlo",
+        '=' => "efij",
+        '.' => "f",
+        ',' => "n",
+        '/' => "mn",
+        '(' => "mp",
+        ')' => "kn",
+        '_' => "ef",
+        _ => "abcdefghkmnp",
+    };
+
+    segments
+        .bytes()
+        .fold(0, |mask, segment| mask | 1 << (segment - b'a'))
+}
@@ -5,53 +5,157 @@ bitflags! {
     pub struct Commands: u8 {
         const FLYING_CAMERA = 1 << 0;
         const FPS_CAMERA = 1 << 1;
+        const TOGGLE_GIZMOS = 1 << 2;
+        const SWITCH_CAMERA = 1 << 3;
     }
 }
+
+bitflags! {
+    /// What the game states react to. Unlike [`Commands`] these are read every
+    /// frame, so menus work while the simulation is paused.
+    pub struct Actions: u8 {
+        const BACK = 1 << 0;
+        const CONFIRM = 1 << 1;
+        const DOWN = 1 << 2;
+        const UP = 1 << 3;
+    }
+}
+
+/// The actions of the keys pressed, or repeated, since the last
+/// [`Window::events`].
+pub fn actions(window: &Window) -> Actions {
+    window
+        .polled_events()
+        .iter()
+        .filter_map(|event| match event {
+            glfw::WindowEvent::Key(key, _, glfw::Action::Press | glfw::Action::Repeat, _) => {
+                match key {
+                    glfw::Key::Escape => Some(Actions::BACK),
+                    glfw::Key::Enter | glfw::Key::KpEnter | glfw::Key::Space => {
+                        Some(Actions::CONFIRM)
+                    }
+                    glfw::Key::Down | glfw::Key::S => Some(Actions::DOWN),
+                    glfw::Key::Up | glfw::Key::W => Some(Actions::UP),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .fold(Actions::empty(), |actions, action| actions | action)
+}
 pub struct Input {
+    /// The commands of the tick, which only the first tick of a frame gets.
     commands: Commands,
+    /// Where the cursor is in framebuffer pixels, unless it is captured.
+    cursor: Option<glam::Vec2>,
+    framebuffer_size: glam::UVec2,
+    jump: bool,
+    /// Where the cursor was at the last tick.
     last_mouse_pos: glam::DVec2,
+    mouse_diff: glam::Vec2,
     mouse_pos: glam::DVec2,
     movement: glam::Vec3,
+    /// The commands of the frame, for the frame systems.
+    polled: Commands,
+    /// The commands polled since the last tick.
+    pressed: Commands,
 }
 
 impl Input {
+    /// Where the free cursor is, in framebuffer pixels. While the cursor is
+    /// captured the player aims with the center of the screen instead.
+    pub fn cursor(&self) -> Option<glam::Vec2> {
+        self.cursor
+    }
     pub fn flying_camera(&self) -> bool {
         self.commands.contains(Commands::FLYING_CAMERA)
     }
+    /// Follows the cursor without turning it into camera movement, for frames
+    /// that do not tick.
+    pub fn hold_mouse(&mut self) {
+        self.last_mouse_pos = self.mouse_pos;
+    }
+    /// Drops the keys of this frame, for when the UI takes them.
+    pub fn ignore_keys(&mut self) {
+        self.polled = Commands::empty();
+        self.pressed = Commands::empty();
+        self.jump = false;
+        self.movement = glam::Vec3::ZERO;
+    }
     pub fn fps_camera(&self) -> bool {
         self.commands.contains(Commands::FPS_CAMERA)
     }
+    pub fn framebuffer_size(&self) -> glam::UVec2 {
+        self.framebuffer_size
+    }
+    /// Whether the jump key is held.
+    pub fn jump(&self) -> bool {
+        self.jump
+    }
+    /// Whether to look through the next camera.
+    pub fn switch_camera(&self) -> bool {
+        self.commands.contains(Commands::SWITCH_CAMERA)
+    }
+    /// Whether the gizmos were toggled this frame. Unlike the other commands
+    /// this is read by a frame system, so it does not wait for a tick.
+    pub fn toggle_gizmos(&self) -> bool {
+        self.polled.contains(Commands::TOGGLE_GIZMOS)
+    }
     pub fn movement(&self) -> glam::Vec3 {
         self.movement
     }
+    /// How far the cursor moved during the last tick.
     pub fn mouse_diff(&self) -> glam::Vec2 {
-        (self.mouse_pos - self.last_mouse_pos).as_vec2()
+        self.mouse_diff
     }
     pub fn new() -> Self {
         Self {
             commands: Commands::empty(),
+            cursor: None,
+            framebuffer_size: glam::UVec2::ONE,
+            jump: false,
             last_mouse_pos: glam::DVec2::ZERO,
+            mouse_diff: glam::Vec2::ZERO,
             mouse_pos: glam::DVec2::ZERO,
             movement: glam::Vec3::ZERO,
+            polled: Commands::empty(),
+            pressed: Commands::empty(),
         }
     }
-    pub fn update(&mut self, window: &Window) {
+    /// Reads the keys and the cursor, once a frame after [`Window::events`].
+    pub fn poll(&mut self, window: &Window) {
         let move_z = window.key_down(glfw::Key::W) as i8 - window.key_down(glfw::Key::S) as i8;
         let move_x = window.key_down(glfw::Key::D) as i8 - window.key_down(glfw::Key::A) as i8;
         self.movement = glam::Vec3::new(move_x as f32, 0.0, move_z as f32).normalize_or_zero();
+        self.jump = window.key_down(glfw::Key::Space);
 
-        self.last_mouse_pos = self.mouse_pos;
         self.mouse_pos = window.get_relative_mouse_position();
         // +y = go up
         self.mouse_pos.y = -self.mouse_pos.y;
+        // A free cursor belongs to the UI.
+        if !window.cursor_captured() {
+            self.last_mouse_pos = self.mouse_pos;
+        }
+        self.cursor = (!window.cursor_captured()).then(|| window.get_cursor_position());
+        self.framebuffer_size = window.get_framebuffer_size();
 
-        self.commands = [
+        self.polled = [
             (glfw::Key::F8, Commands::FLYING_CAMERA),
             (glfw::Key::F9, Commands::FPS_CAMERA),
+            (glfw::Key::F3, Commands::TOGGLE_GIZMOS),
+            (glfw::Key::F7, Commands::SWITCH_CAMERA),
         ]
         .into_iter()
         .filter_map(|(key, command)| window.key_pressed(key).then_some(command))
         .reduce(|commands, command| commands | command)
         .unwrap_or(Commands::empty());
+        self.pressed = self.polled;
+    }
+    /// Starts a tick. The first tick of a frame gets all the cursor movement
+    /// and the commands since the last poll, and the ones after it none.
+    pub fn tick(&mut self) {
+        self.commands = std::mem::replace(&mut self.pressed, Commands::empty());
+        self.mouse_diff = (self.mouse_pos - self.last_mouse_pos).as_vec2();
+        self.last_mouse_pos = self.mouse_pos;
     }
 }
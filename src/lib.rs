@@ -0,0 +1,8 @@
+//! What the game shares with the offline tools: the block compression codecs,
+//! which the game uses to decode textures for adapters without texture
+//! compression, the GPU culling, which `cull_check` runs on a software
+//! adapter, and the bounding volume hierarchy, which the benchmarks time.
+
+pub mod bvh;
+pub mod codecs;
+pub mod culling;
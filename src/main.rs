@@ -1,145 +1,297 @@
+mod assets;
 mod camera;
+mod canvas;
+mod character;
+mod components;
 mod data;
+mod ecs;
+mod gizmos;
 mod input;
+mod physics;
+mod raycast;
+mod renderer;
+mod scene;
+mod settings;
+mod states;
+mod systems;
+mod text;
 mod time;
+mod ui;
+mod watcher;
 mod window;
 
 use std::path::Path;
 
+use assets::{Assets, Loaded, Loader};
 use camera::Camera;
+use canvas::{Canvas, SpriteSheet};
+use components::MeshRenderer;
+use ecs::World;
+use gizmos::Gizmos;
 use input::Input;
-use rand::{Rng, SeedableRng};
+use rand::SeedableRng;
+use renderer::{passes::Renderer, Frame};
+use settings::Settings;
+use states::{game, StateStack};
+use systems::{Extracted, Models};
+use text::Fonts;
 use time::Time;
+use ui::DebugUi;
+use watcher::Watcher;
 use window::Window;
 
 fn run() -> anyhow::Result<()> {
     env_logger::init();
 
+    let settings_path = Path::new(settings::PATH);
+    let mut settings = Settings::load(settings_path)?;
+
     let mut window = Window::new("title", 1920, 1080)?;
     let mut graphics = weng::graphics::Context::new(&window)?;
 
     let mut camera = Camera::new(graphics.surface_width(), graphics.surface_height());
-
-    let shader =
-        graphics.load_shader(&Path::new(data::shaders::DIR).join(data::shaders::basic::NAME))?;
-
-    let diffuse_texture_bind_group_layout = graphics.create_texture_bind_group_layout();
-
-    let camera_bind_group_layout =
-        graphics.create_uniform_bind_group_layout(weng::wgpu::ShaderStages::VERTEX_FRAGMENT);
-    let light_bind_group_layout =
-        graphics.create_uniform_bind_group_layout(weng::wgpu::ShaderStages::VERTEX_FRAGMENT);
-
-    let camera_uniform_buffer =
-        graphics.create_uniform_buffer(&[data::shaders::basic::CameraUniform {
-            pos: glam::Vec4::from((camera.position(), 0.0)).to_array(),
-            view_proj: camera.build_matrix().to_cols_array(),
-        }]);
-    let light_uniform_buffer =
-        graphics.create_uniform_buffer(&[data::shaders::basic::LightUniform {
-            position: [0.0, 0.0, 0.0],
-            _padding: 0,
-            color: [1.0, 1.0, 1.0],
-            _padding2: 0,
-        }]);
-
-    let camera_bind_group =
-        graphics.create_uniform_bind_group(&camera_bind_group_layout, &camera_uniform_buffer);
-    let light_bind_group =
-        graphics.create_uniform_bind_group(&light_bind_group_layout, &light_uniform_buffer);
-
-    let render_pipeline = graphics
-        .create_pipeline_instanced::<data::models::Vertex, data::shaders::basic::Instance>(
-            &shader,
-            &[
-                &diffuse_texture_bind_group_layout,
-                &camera_bind_group_layout,
-                &light_bind_group_layout,
-            ],
-        );
+    let mut light = data::shaders::basic::LightUniform {
+        position: [0.0, 0.0, 0.0],
+        _padding: 0,
+        color: [1.0, 1.0, 1.0],
+        _padding2: 0,
+    };
 
     let mut random = rand::rngs::SmallRng::from_entropy();
 
-    let instances: [_; 100] = std::array::from_fn(|_| {
-        let scale = random.gen_range(0.25..4.0);
-        let rotation = random.gen();
-
-        data::shaders::basic::Instance {
-            model: glam::Mat4::from_scale_rotation_translation(
-                glam::Vec3::splat(scale),
-                rotation,
-                glam::Vec3::new(
-                    random.gen_range(-50.0..50.0),
-                    random.gen_range(-10.0..10.0),
-                    random.gen_range(-50.0..50.0),
-                ),
-            )
-            .to_cols_array(),
-            normal: glam::Mat3::from_quat(rotation).to_cols_array(),
-        }
-    });
+    let mut world = World::new();
+    let mut schedule = systems::schedule();
+
+    systems::register(&mut world);
+
+    let mut assets = Assets::new(renderer::create_material_bind_group_layout(&graphics));
+    let mut renderer = Renderer::new(&graphics, &mut assets, &settings)?;
+    let hud_panel = renderer.load_sprite(
+        &graphics,
+        &Path::new(data::textures::DIR).join("hud-panel.png"),
+    )?;
+    let camera_icons = SpriteSheet {
+        cell: glam::UVec2::splat(16),
+        texture: renderer.load_sprite(
+            &graphics,
+            &Path::new(data::textures::DIR).join("camera-icons.png"),
+        )?,
+    };
+
+    let mut watcher = Watcher::new();
+
+    watcher.watch(settings_path);
 
-    let instance_buffer = graphics.create_instance_buffer(&instances);
+    for path in assets.dependencies() {
+        watcher.watch(path);
+    }
+
+    world.insert_resource(assets);
+    scene::setup(&mut world, &mut random, camera.clone());
+    systems::resolve_models(&mut world);
 
-    let model = data::models::Model::load(&mut graphics, &diffuse_texture_bind_group_layout)?;
+    let mut fonts = Fonts::new();
+    let font = fonts.load(&Path::new(data::fonts::DIR).join("DejaVuSans.ttf"))?;
 
-    let mut input = Input::new();
+    let mut context = game::Context {
+        camera_icons,
+        camera_type: camera.type_(),
+        canvas: Canvas::new(),
+        font,
+        fonts,
+        framebuffer_size: window.get_framebuffer_size(),
+        labels: Vec::new(),
+        loading: world.resource::<Loader>().progress(),
+        settings: settings.clone(),
+        simulate: false,
+    };
+    let mut states = StateStack::new(&mut context, Box::new(game::Loading));
+    let mut debug_ui = DebugUi::new();
     let mut time = Time::new();
 
     let mut fb_size = window.get_framebuffer_size();
 
-    while !window.should_close() {
+    while !window.should_close() && !states.is_empty() {
         time.begin_loop();
 
         let new_fb_size = window.get_framebuffer_size();
         if fb_size != new_fb_size {
-            resize(&mut graphics, &mut camera, new_fb_size);
+            resize(&mut graphics, &world, &mut renderer, new_fb_size);
         }
         fb_size = new_fb_size;
         window.events();
 
-        while time.should_update() {
-            input.update(&window);
-            camera.update(&input);
-            time.update();
-        }
-
-        camera_uniform_buffer.set(
+        reload_changed(
             &graphics,
-            &[data::shaders::basic::CameraUniform {
-                pos: glam::Vec4::from((camera.position(), 0.0)).to_array(),
-                view_proj: camera.build_matrix().to_cols_array(),
-            }],
+            &world,
+            &mut renderer,
+            &mut watcher,
+            &mut settings,
         );
+        receive_assets(&graphics, &world, &mut watcher);
 
-        let render_results = [graphics.render(
-            &render_pipeline,
-            &model.meshes[0].vertex_buffer,
-            &model.meshes[0].index_buffer,
-            &instance_buffer,
-            [
-                &model.materials[model.meshes[0].material_indice].texture_bind_group,
-                &camera_bind_group,
-                &light_bind_group,
-            ]
-            .into_iter(),
-        )];
-        for result in render_results {
-            match result {
-                Ok(_) => (),
-                Err(weng::wgpu::SurfaceError::Lost | weng::wgpu::SurfaceError::Outdated) => {
-                    let size = window.get_framebuffer_size();
-
-                    resize(&mut graphics, &mut camera, size);
-                }
-                Err(weng::wgpu::SurfaceError::OutOfMemory) => {
-                    log::error!("out of memory, exiting");
+        context.camera_type = camera.type_();
+        context.framebuffer_size = fb_size;
+        context.loading = world.resource::<Loader>().progress();
+        context.settings.clone_from(&settings);
+        context.simulate = false;
 
-                    return Ok(());
-                }
-                Err(weng::wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
+        // Keys typed into the UI are not meant for the menus either.
+        let actions = if debug_ui.wants_keyboard() {
+            input::Actions::empty()
+        } else {
+            input::actions(&window)
+        };
+        states.update(&mut context, actions);
+
+        // The settings menu only changes what post-processing reads.
+        if context.settings != settings {
+            settings.clone_from(&context.settings);
+            renderer.set_settings(
+                &graphics,
+                &world.resource::<Assets<weng::graphics::Context>>(),
+                &settings,
+            );
+        }
+
+        let cursor_captured = states.captures_cursor() && !debug_ui.is_open();
+        if window.cursor_captured() != cursor_captured {
+            window.set_cursor_captured(cursor_captured);
+        }
+
+        {
+            let mut input = world.resource_mut::<Input>();
+
+            input.poll(&window);
+            // Typing into the UI should not also move the camera.
+            if debug_ui.wants_keyboard() {
+                input.ignore_keys();
+            }
+            // Paused, the scene is still drawn but nothing moves.
+            if !context.simulate {
+                time.skip_ticks();
+                input.hold_mouse();
             }
         }
+        world
+            .resource_mut::<Gizmos>()
+            .set_vectors(settings.debug.vectors);
+
+        schedule.run(&mut world, &mut time);
+
+        {
+            let extracted = world.resource::<Extracted>();
+
+            if let Some(extracted) = &extracted.camera {
+                camera.clone_from(extracted);
+            }
+            if let Some(extracted) = extracted.light {
+                light = extracted;
+            }
+        }
+
+        let ui_output = debug_ui.run(
+            &mut window,
+            &ui::Status {
+                camera: &camera,
+                states: states.names().collect(),
+                time: &time,
+            },
+            &mut light,
+            &mut settings.debug,
+            &mut world.resource_mut::<Gizmos>(),
+        );
+
+        if ui_output.light_changed {
+            systems::set_light(&world, &light);
+        }
+        if ui_output.debug_changed {
+            renderer.set_settings(
+                &graphics,
+                &world.resource::<Assets<weng::graphics::Context>>(),
+                &settings,
+            );
+        }
+        if let Some(path) = &ui_output.load_model {
+            let path = Path::new(data::models::DIR).join(path);
+
+            scene::spawn_model(&mut world, &mut random, &path);
+        }
+
+        let mut frame = match Frame::begin(&graphics) {
+            Ok(frame) => frame,
+            Err(weng::wgpu::SurfaceError::Lost | weng::wgpu::SurfaceError::Outdated) => {
+                let size = window.get_framebuffer_size();
+
+                resize(&mut graphics, &world, &mut renderer, size);
+
+                continue;
+            }
+            Err(weng::wgpu::SurfaceError::OutOfMemory) => {
+                log::error!("out of memory, exiting");
+
+                return Ok(());
+            }
+            Err(weng::wgpu::SurfaceError::Timeout) => {
+                log::warn!("Surface timeout");
+
+                continue;
+            }
+        };
+
+        let assets = world.resource::<Assets<weng::graphics::Context>>();
+
+        renderer.prepare(
+            &graphics,
+            &assets,
+            &world.resource::<Extracted>(),
+            &camera,
+            &light,
+        );
+        #[cfg(feature = "gizmos")]
+        renderer.prepare_gizmos(&graphics, &world.resource::<Gizmos>());
+
+        let readout = text::layout(
+            &context.fonts,
+            &format!(
+                "{:.0} fps\n{:.2} ms",
+                time.fps(),
+                time.frame_time() * 1000.0
+            ),
+            &text::Style {
+                align: text::Align::Right,
+                ..text::Style::new(context.font, 20.0)
+            },
+        );
+        let readout_position = glam::Vec2::new(fb_size.x as f32 - readout.size.x - 16.0, 16.0);
+
+        context.canvas.begin();
+        context.labels.clear();
+        states.render(&mut context);
+        context.canvas.nine_slice(
+            &hud_panel,
+            4.0,
+            readout_position - 8.0,
+            readout.size + 16.0,
+            glam::Vec4::ONE,
+        );
+        context.labels.push(game::Label {
+            color: glam::Vec4::ONE,
+            layout: readout,
+            position: readout_position,
+        });
+
+        renderer.prepare_overlay(
+            &graphics,
+            &context.canvas,
+            &context.fonts,
+            &context.labels,
+            &ui_output,
+            fb_size,
+        );
+        renderer.render(&graphics, &assets, &mut frame);
+
+        frame.finish(&graphics);
     }
 
     Ok(())
@@ -153,9 +305,145 @@ fn main() {
 
 fn resize(
     graphics: &mut weng::graphics::Context,
-    camera: &mut Camera,
+    world: &World,
+    renderer: &mut Renderer,
     framebuffer_size: glam::UVec2,
 ) {
     graphics.resize(framebuffer_size.x, framebuffer_size.y);
-    camera.resize(framebuffer_size.x, framebuffer_size.y);
+    for (_, camera) in world.components_mut::<Camera>().iter_mut() {
+        camera.resize(framebuffer_size.x, framebuffer_size.y);
+    }
+    renderer.resize(graphics, framebuffer_size);
+}
+
+/// Reloads the settings and the assets that changed on disk. Shaders are
+/// rebuilt at once, while models and textures go back to the loader.
+fn reload_changed(
+    graphics: &weng::graphics::Context,
+    world: &World,
+    renderer: &mut Renderer,
+    watcher: &mut Watcher,
+    settings: &mut Settings,
+) {
+    let settings_path = Path::new(settings::PATH);
+
+    for path in watcher.changed() {
+        let assets = world.resource::<Assets<weng::graphics::Context>>();
+
+        if path == settings_path {
+            match Settings::load(settings_path) {
+                Ok(loaded) => {
+                    *settings = loaded;
+                    renderer.set_settings(graphics, &assets, settings);
+
+                    log::info!("reloaded {}", path.display());
+                }
+                Err(e) => log::error!("{e:#}"),
+            }
+
+            continue;
+        }
+
+        // Models and textures are decoded again on the loader's workers,
+        // and swapped in once they come back.
+        world
+            .resource_mut::<Loader>()
+            .reload(assets.dependents(&path));
+        drop(assets);
+
+        let mut assets = world.resource_mut::<Assets<weng::graphics::Context>>();
+
+        let reloaded = match assets.reload_shader(graphics, &path) {
+            Ok(Some(shader)) => renderer.reload_shader(graphics, &assets, shader),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = reloaded {
+            log::error!("failed to reload {}: {e:#}", path.display());
+
+            continue;
+        }
+
+        log::info!("reloaded {}", path.display());
+    }
+}
+
+/// Hands what the loader finished to the assets, and to the systems waiting
+/// for it.
+fn receive_assets(graphics: &weng::graphics::Context, world: &World, watcher: &mut Watcher) {
+    let mut assets = world.resource_mut::<Assets<weng::graphics::Context>>();
+    let mut loader = world.resource_mut::<Loader>();
+    let mut models = world.resource_mut::<Models>();
+
+    for result in loader.poll() {
+        match result {
+            Ok(Loaded::Model { model, path }) => {
+                match model.and_then(|model| assets.insert_model(graphics, &path, model)) {
+                    Ok(handle) => {
+                        models.insert(path, handle);
+
+                        for path in assets.dependencies() {
+                            watcher.watch(path);
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("failed to load {}: {e:#}", path.display());
+
+                        models.fail(path);
+                    }
+                }
+            }
+            Ok(Loaded::ReloadedModel { model, path }) => {
+                match assets.replace_model(graphics, &path, model) {
+                    Ok(Some(handle)) => {
+                        log::info!("reloaded {}", path.display());
+
+                        // Lets `resolve_models` measure the new bounds.
+                        for (_, renderer) in world.components_mut::<MeshRenderer>().iter_mut() {
+                            if renderer.model == Some(handle) {
+                                renderer.model = None;
+                            }
+                        }
+                        // A changed mtl file can leave the previous
+                        // textures unreferenced.
+                        assets.unload_unused();
+
+                        for path in assets.dependencies() {
+                            watcher.watch(path);
+                        }
+                    }
+                    Ok(None) => (),
+                    Err(e) => log::error!("failed to reload {}: {e:#}", path.display()),
+                }
+            }
+            Ok(Loaded::ReloadedTexture { image, source }) => {
+                match assets.replace_texture(graphics, &source, &image) {
+                    Ok(textures) if textures.is_empty() => (),
+                    Ok(_) => log::info!("reloaded {source}"),
+                    Err(e) => log::error!("failed to reload {source}: {e:#}"),
+                }
+            }
+            Err(e) => log::error!("{e:#}"),
+        }
+
+        let progress = loader.progress();
+        log::info!(
+            "loaded {}/{} assets ({:.0}%)",
+            progress.finished,
+            progress.requested,
+            progress.fraction() * 100.0
+        );
+
+        if progress.done() {
+            let memory = assets.memory();
+            log::info!(
+                "asset memory: {} bytes ({} models, {} shaders, {} textures)",
+                memory.total(),
+                memory.models,
+                memory.shaders,
+                memory.textures,
+            );
+        }
+    }
 }
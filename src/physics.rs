@@ -0,0 +1,537 @@
+//! Rigid bodies simulated by rapier at [`Time::TICK_RATE`]. An entity with a
+//! [`RigidBody`], a [`Collider`] and a [`Transform`] joins the simulation on
+//! the next tick, and leaves it once it loses its body or is despawned.
+//!
+//! Colliders are sized in world units, so the scale of the transform does not
+//! apply to them.
+
+use crate::{
+    components::Transform,
+    ecs::{Entity, World},
+    gizmos::{self, Gizmos},
+    time::Time,
+};
+use rapier3d::{control, na, prelude as rapier};
+use std::collections::HashMap;
+
+/// How low a dynamic body can fall, such as off the edge of the floor, before
+/// it is despawned.
+pub const FALL_LIMIT: f32 = -50.0;
+pub const GRAVITY: glam::Vec3 = glam::Vec3::new(0.0, -9.81, 0.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyType {
+    /// Moved by forces and collisions, and moves its transform.
+    Dynamic,
+    /// Never moves, like the ground.
+    Fixed,
+    /// Follows its transform, pushing dynamic bodies out of the way.
+    Kinematic,
+}
+
+pub struct RigidBody {
+    pub body_type: BodyType,
+    /// The velocity the body starts with, in units per second.
+    pub linear_velocity: glam::Vec3,
+    handle: Option<rapier::RigidBodyHandle>,
+}
+
+impl RigidBody {
+    pub fn dynamic() -> Self {
+        Self::new(BodyType::Dynamic)
+    }
+
+    pub fn fixed() -> Self {
+        Self::new(BodyType::Fixed)
+    }
+
+    pub fn kinematic() -> Self {
+        Self::new(BodyType::Kinematic)
+    }
+
+    pub fn new(body_type: BodyType) -> Self {
+        Self {
+            body_type,
+            linear_velocity: glam::Vec3::ZERO,
+            handle: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Box {
+        half_extents: glam::Vec3,
+    },
+    /// Along the y axis, with `half_height` from the center to the center of
+    /// either cap.
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    Sphere {
+        radius: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collider {
+    pub friction: f32,
+    /// How much of the speed is kept when bouncing, from 0 to 1.
+    pub restitution: f32,
+    pub shape: Shape,
+}
+
+impl Collider {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            friction: 0.5,
+            restitution: 0.0,
+            shape,
+        }
+    }
+}
+
+/// The rapier state, as a resource.
+pub struct Physics {
+    bodies: rapier::RigidBodySet,
+    broad_phase: rapier::BroadPhase,
+    ccd_solver: rapier::CCDSolver,
+    colliders: rapier::ColliderSet,
+    /// The entity of every body, to remove the bodies of entities that are
+    /// gone.
+    entities: HashMap<Entity, rapier::RigidBodyHandle>,
+    impulse_joints: rapier::ImpulseJointSet,
+    integration_parameters: rapier::IntegrationParameters,
+    islands: rapier::IslandManager,
+    multibody_joints: rapier::MultibodyJointSet,
+    narrow_phase: rapier::NarrowPhase,
+    pipeline: rapier::PhysicsPipeline,
+    /// Where the colliders were after the last step, for moving characters.
+    queries: rapier::QueryPipeline,
+}
+
+impl Physics {
+    /// Moves `shape`, centered on `position`, by as much of `translation` as
+    /// the colliders allow: sliding along walls, climbing ledges up to
+    /// `step_height` and slopes up to `max_slope` radians, and sticking to the
+    /// ground on the way down. Returns the translation it managed, and
+    /// whether the shape ends up standing on ground no steeper than
+    /// `max_slope`.
+    pub fn move_shape(
+        &self,
+        shape: Shape,
+        position: glam::Vec3,
+        translation: glam::Vec3,
+        max_slope: f32,
+        step_height: f32,
+    ) -> (glam::Vec3, bool) {
+        let controller = control::KinematicCharacterController {
+            autostep: Some(control::CharacterAutostep {
+                max_height: control::CharacterLength::Absolute(step_height),
+                min_width: control::CharacterLength::Relative(0.5),
+                include_dynamic_bodies: false,
+            }),
+            max_slope_climb_angle: max_slope,
+            min_slope_slide_angle: max_slope,
+            snap_to_ground: Some(control::CharacterLength::Absolute(step_height)),
+            ..Default::default()
+        };
+        let shape = shared_shape(shape);
+        let movement = controller.move_shape(
+            self.integration_parameters.dt,
+            &self.bodies,
+            &self.colliders,
+            &self.queries,
+            &*shape,
+            &vector(position).into(),
+            vector(translation),
+            rapier::QueryFilter::default(),
+            |_| (),
+        );
+        let moved = glam::Vec3::new(
+            movement.translation.x,
+            movement.translation.y,
+            movement.translation.z,
+        );
+
+        // Rapier counts anything below as the ground, which would keep
+        // characters standing on slopes too steep to walk up.
+        let ground = self.queries.cast_shape(
+            &self.bodies,
+            &self.colliders,
+            &vector(position + moved).into(),
+            &-rapier::Vector::y(),
+            &*shape,
+            0.1,
+            false,
+            rapier::QueryFilter::default(),
+        );
+        let walkable = matches!(
+            ground,
+            Some((_, hit)) if hit.normal1.angle(&rapier::Vector::y()) <= max_slope
+        );
+
+        (moved, movement.grounded && walkable)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            bodies: rapier::RigidBodySet::new(),
+            broad_phase: rapier::BroadPhase::new(),
+            ccd_solver: rapier::CCDSolver::new(),
+            colliders: rapier::ColliderSet::new(),
+            entities: HashMap::new(),
+            impulse_joints: rapier::ImpulseJointSet::new(),
+            integration_parameters: rapier::IntegrationParameters {
+                dt: Time::TICK_RATE.as_secs_f32(),
+                ..Default::default()
+            },
+            islands: rapier::IslandManager::new(),
+            multibody_joints: rapier::MultibodyJointSet::new(),
+            narrow_phase: rapier::NarrowPhase::new(),
+            pipeline: rapier::PhysicsPipeline::new(),
+            queries: rapier::QueryPipeline::new(),
+        }
+    }
+
+    fn insert(
+        &mut self,
+        entity: Entity,
+        body: &RigidBody,
+        collider: &Collider,
+        transform: &Transform,
+    ) -> rapier::RigidBodyHandle {
+        let builder = match body.body_type {
+            BodyType::Dynamic => rapier::RigidBodyBuilder::dynamic(),
+            BodyType::Fixed => rapier::RigidBodyBuilder::fixed(),
+            BodyType::Kinematic => rapier::RigidBodyBuilder::kinematic_position_based(),
+        };
+        let handle = self.bodies.insert(
+            builder
+                .position(isometry(transform))
+                .linvel(vector(body.linear_velocity)),
+        );
+
+        self.colliders.insert_with_parent(
+            rapier::ColliderBuilder::new(shared_shape(collider.shape))
+                .friction(collider.friction)
+                .restitution(collider.restitution),
+            handle,
+            &mut self.bodies,
+        );
+
+        self.entities.insert(entity, handle);
+
+        handle
+    }
+}
+
+/// Despawns the dynamic bodies below [`FALL_LIMIT`], which would otherwise
+/// fall forever.
+pub fn despawn_fallen(world: &mut World) {
+    let fallen: Vec<_> = {
+        let transforms = world.components::<Transform>();
+
+        world
+            .components::<RigidBody>()
+            .iter()
+            .filter(|(entity, body)| {
+                body.body_type == BodyType::Dynamic
+                    && matches!(
+                        transforms.get(*entity),
+                        Some(transform) if transform.translation.y < FALL_LIMIT
+                    )
+            })
+            .map(|(entity, _)| entity)
+            .collect()
+    };
+
+    for entity in fallen {
+        world.despawn(entity);
+    }
+}
+
+/// Draws every collider where its transform is: fixed ones in white, sleeping
+/// ones in blue, and moving ones in green.
+pub fn draw_gizmos(world: &World, gizmos: &mut Gizmos) {
+    let physics = world.resource::<Physics>();
+    let bodies = world.components::<RigidBody>();
+    let transforms = world.components::<Transform>();
+
+    for (entity, collider) in world.components::<Collider>().iter() {
+        let (body, transform) = match (bodies.get(entity), transforms.get(entity)) {
+            (Some(body), Some(transform)) => (body, transform),
+            _ => continue,
+        };
+
+        let sleeping = matches!(
+            body.handle.and_then(|handle| physics.bodies.get(handle)),
+            Some(body) if body.is_sleeping()
+        );
+        let color = match body.body_type {
+            BodyType::Fixed => gizmos::WHITE,
+            _ if sleeping => gizmos::BLUE,
+            _ => gizmos::GREEN,
+        };
+
+        match collider.shape {
+            Shape::Box { half_extents } => gizmos.cuboid(
+                glam::Mat4::from_rotation_translation(transform.rotation, transform.translation),
+                half_extents,
+                color,
+            ),
+            Shape::Capsule {
+                half_height,
+                radius,
+            } => {
+                let axis = transform.rotation * glam::Vec3::Y * half_height;
+
+                gizmos.capsule(
+                    transform.translation - axis,
+                    transform.translation + axis,
+                    radius,
+                    color,
+                );
+            }
+            Shape::Sphere { radius } => gizmos.sphere(transform.translation, radius, color),
+        }
+    }
+}
+
+/// Adds new bodies to the simulation and removes those that are gone, moves
+/// kinematic bodies to their transforms, steps the simulation by a tick, and
+/// moves the transforms of dynamic bodies to where they ended up.
+pub fn step(world: &mut World) {
+    let mut physics = world.resource_mut::<Physics>();
+    let physics = &mut *physics;
+    let mut bodies = world.components_mut::<RigidBody>();
+    let colliders = world.components::<Collider>();
+    let mut transforms = world.components_mut::<Transform>();
+
+    physics.entities.retain(|&entity, &mut handle| {
+        let kept = matches!(bodies.get(entity), Some(body) if body.handle == Some(handle));
+
+        if !kept {
+            physics.bodies.remove(
+                handle,
+                &mut physics.islands,
+                &mut physics.colliders,
+                &mut physics.impulse_joints,
+                &mut physics.multibody_joints,
+                true,
+            );
+        }
+
+        kept
+    });
+
+    for (entity, body) in bodies.iter_mut() {
+        let transform = match transforms.get(entity) {
+            Some(transform) => transform,
+            None => continue,
+        };
+
+        match body.handle {
+            None => {
+                if let Some(collider) = colliders.get(entity) {
+                    body.handle = Some(physics.insert(entity, body, collider, transform));
+                }
+            }
+            Some(handle) if body.body_type == BodyType::Kinematic => {
+                physics.bodies[handle].set_next_kinematic_position(isometry(transform));
+            }
+            Some(_) => (),
+        }
+    }
+
+    physics.pipeline.step(
+        &vector(GRAVITY),
+        &physics.integration_parameters,
+        &mut physics.islands,
+        &mut physics.broad_phase,
+        &mut physics.narrow_phase,
+        &mut physics.bodies,
+        &mut physics.colliders,
+        &mut physics.impulse_joints,
+        &mut physics.multibody_joints,
+        &mut physics.ccd_solver,
+        Some(&mut physics.queries),
+        &(),
+        &(),
+    );
+
+    for (entity, body) in bodies.iter() {
+        if body.body_type != BodyType::Dynamic {
+            continue;
+        }
+
+        if let (Some(handle), Some(transform)) = (body.handle, transforms.get_mut(entity)) {
+            let simulated = &physics.bodies[handle];
+            let translation = simulated.translation();
+            let rotation = simulated.rotation();
+
+            transform.translation = glam::Vec3::new(translation.x, translation.y, translation.z);
+            transform.rotation =
+                glam::Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w);
+        }
+    }
+}
+
+fn isometry(transform: &Transform) -> rapier::Isometry<rapier::Real> {
+    let rotation = transform.rotation;
+
+    rapier::Isometry::from_parts(
+        vector(transform.translation).into(),
+        na::UnitQuaternion::from_quaternion(na::Quaternion::new(
+            rotation.w, rotation.x, rotation.y, rotation.z,
+        )),
+    )
+}
+
+fn shared_shape(shape: Shape) -> rapier::SharedShape {
+    match shape {
+        Shape::Box { half_extents } => {
+            rapier::SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z)
+        }
+        Shape::Capsule {
+            half_height,
+            radius,
+        } => rapier::SharedShape::capsule_y(half_height, radius),
+        Shape::Sphere { radius } => rapier::SharedShape::ball(radius),
+    }
+}
+
+fn vector(vector: glam::Vec3) -> rapier::Vector<rapier::Real> {
+    rapier::Vector::new(vector.x, vector.y, vector.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How far a box with `half_extents` and `rotation` reaches below its
+    /// center.
+    fn depth(half_extents: glam::Vec3, rotation: glam::Quat) -> f32 {
+        glam::Mat3::from_quat(rotation)
+            .row(1)
+            .abs()
+            .dot(half_extents)
+    }
+
+    #[test]
+    fn falling_cubes_rest_on_the_floor() {
+        let mut world = World::new();
+        let half_extents = glam::Vec3::splat(0.5);
+
+        world.insert_resource(Physics::new());
+        world
+            .spawn()
+            .with(Transform::IDENTITY)
+            .with(RigidBody::fixed())
+            .with(Collider::new(Shape::Box {
+                half_extents: glam::Vec3::new(10.0, 0.5, 10.0),
+            }));
+
+        // Far enough apart not to land on each other, and turned so that they
+        // land on edges and corners.
+        let cubes: Vec<_> = (0..9)
+            .map(|index| {
+                let translation = glam::Vec3::new(
+                    (index % 3) as f32 * 3.0 - 3.0,
+                    2.0 + index as f32,
+                    (index / 3) as f32 * 3.0 - 3.0,
+                );
+                let rotation = glam::Quat::from_euler(
+                    glam::EulerRot::XYZ,
+                    index as f32 * 0.4,
+                    index as f32 * 0.7,
+                    0.0,
+                );
+
+                world
+                    .spawn()
+                    .with(Transform {
+                        rotation,
+                        ..Transform::from_translation(translation)
+                    })
+                    .with(RigidBody::dynamic())
+                    .with(Collider::new(Shape::Box { half_extents }))
+                    .id()
+            })
+            .collect();
+
+        // Five seconds, enough for the highest to land and settle.
+        for _ in 0..300 {
+            step(&mut world);
+        }
+
+        let physics = world.resource::<Physics>();
+        let bodies = world.components::<RigidBody>();
+        let transforms = world.components::<Transform>();
+        let floor = 0.5;
+
+        for entity in cubes {
+            let transform = transforms.get(entity).unwrap();
+            let bottom = transform.translation.y - depth(half_extents, transform.rotation);
+            let velocity = physics.bodies[bodies.get(entity).unwrap().handle.unwrap()]
+                .linvel()
+                .norm();
+
+            // Contacts are allowed to sink in by a little.
+            assert!(
+                (bottom - floor).abs() < 0.05,
+                "{entity:?} rests at {bottom}, not on the floor at {floor}"
+            );
+            assert!(velocity < 0.01, "{entity:?} still moves at {velocity}");
+        }
+    }
+
+    #[test]
+    fn fallen_bodies_are_despawned() {
+        let mut world = World::new();
+
+        world.insert_resource(Physics::new());
+
+        // Nothing under either of them, but only the dynamic one falls.
+        let ball = world
+            .spawn()
+            .with(Transform::from_translation(
+                glam::Vec3::Y * (FALL_LIMIT + 1.0),
+            ))
+            .with(RigidBody::dynamic())
+            .with(Collider::new(Shape::Sphere { radius: 0.5 }))
+            .id();
+        let ledge = world
+            .spawn()
+            .with(Transform::from_translation(
+                glam::Vec3::Y * (FALL_LIMIT - 1.0),
+            ))
+            .with(RigidBody::fixed())
+            .with(Collider::new(Shape::Box {
+                half_extents: glam::Vec3::splat(0.5),
+            }))
+            .id();
+
+        step(&mut world);
+        despawn_fallen(&mut world);
+        assert!(world.is_alive(ball));
+
+        // A second is enough to fall the first unit.
+        for _ in 0..60 {
+            step(&mut world);
+            despawn_fallen(&mut world);
+        }
+
+        assert!(!world.is_alive(ball));
+        assert!(world.is_alive(ledge));
+        assert_eq!(
+            world
+                .resource::<Physics>()
+                .entities
+                .keys()
+                .collect::<Vec<_>>(),
+            [&ledge]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+//! Rays from the camera into the scene, for picking what the player aims at
+//! or clicks on. The [`Bvh`] of the scene finds the objects whose boxes a ray
+//! passes through, then the bounding spheres of their meshes, and only the
+//! meshes left are tested triangle by triangle.
+
+use crate::{
+    assets::Assets,
+    components::{Camera, MeshRenderer, Transform},
+    ecs::{Entity, World},
+    input::Input,
+};
+use weng_game::bvh::Bvh;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    /// Not necessarily of unit length, so that a ray moved into model space
+    /// keeps measuring distances along the world ray.
+    pub direction: glam::Vec3,
+    pub origin: glam::Vec3,
+}
+
+/// Where a ray first met a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Along the ray, in units of its direction.
+    pub distance: f32,
+    pub entity: Entity,
+    /// The unit normal of the triangle, facing the ray.
+    pub normal: glam::Vec3,
+    pub point: glam::Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> glam::Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Through the center of the screen, where the crosshair is.
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self::new(camera.position(), camera.direction())
+    }
+
+    /// Through the free cursor, or the crosshair while the cursor is captured.
+    pub fn from_input(camera: &Camera, input: &Input) -> Self {
+        match input.cursor() {
+            Some(cursor) => Self::from_screen(camera, cursor, input.framebuffer_size()),
+            None => Self::from_camera(camera),
+        }
+    }
+
+    /// Through `point` on the screen, in framebuffer pixels from the top left
+    /// corner, such as the cursor.
+    pub fn from_screen(camera: &Camera, point: glam::Vec2, framebuffer_size: glam::UVec2) -> Self {
+        let ndc = point / framebuffer_size.as_vec2().max(glam::Vec2::ONE) * 2.0 - 1.0;
+        let inverse = camera.build_matrix().inverse();
+        let near = inverse.project_point3(glam::Vec3::new(ndc.x, -ndc.y, 0.0));
+        let far = inverse.project_point3(glam::Vec3::new(ndc.x, -ndc.y, 1.0));
+
+        Self::new(near, far - near)
+    }
+
+    /// Normalizes `direction`.
+    pub fn new(origin: glam::Vec3, direction: glam::Vec3) -> Self {
+        Self {
+            direction: direction.normalize_or_zero(),
+            origin,
+        }
+    }
+
+    /// The distance to where the ray enters the sphere, or zero if it starts
+    /// inside.
+    pub fn sphere(&self, center: glam::Vec3, radius: f32) -> Option<f32> {
+        let offset = self.origin - center;
+        let a = self.direction.length_squared();
+        let b = offset.dot(self.direction);
+        let c = offset.length_squared() - radius * radius;
+
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+
+        let discriminant = b * b - a * c;
+
+        if a == 0.0 || discriminant < 0.0 || b > 0.0 {
+            return None;
+        }
+
+        Some((-b - discriminant.sqrt()) / a)
+    }
+
+    /// Moves the ray into the space `matrix` transforms from.
+    pub fn transformed(&self, matrix: glam::Mat4) -> Self {
+        let inverse = matrix.inverse();
+
+        Self {
+            direction: inverse.transform_vector3(self.direction),
+            origin: inverse.transform_point3(self.origin),
+        }
+    }
+
+    /// The distance to where the ray crosses the triangle, from either side.
+    /// Möller–Trumbore.
+    pub fn triangle(&self, [a, b, c]: [glam::Vec3; 3]) -> Option<f32> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let determinant = ab.dot(p);
+
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let offset = self.origin - a;
+        let u = offset.dot(p) * inverse;
+
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = offset.cross(ab);
+        let v = self.direction.dot(q) * inverse;
+
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = ac.dot(q) * inverse;
+
+        (distance >= 0.0).then_some(distance)
+    }
+}
+
+/// The closest mesh `ray` hits within `max_distance`, among the entities in
+/// the [`Bvh`] resource, which [`systems::bounds`](crate::systems::bounds)
+/// keeps.
+pub fn cast(
+    world: &World,
+    assets: &Assets<weng::graphics::Context>,
+    ray: &Ray,
+    max_distance: f32,
+) -> Option<Hit> {
+    let renderers = world.components::<MeshRenderer>();
+    let transforms = world.components::<Transform>();
+    let mut candidates = Vec::new();
+
+    world.resource::<Bvh<Entity>>().query_ray(
+        ray.origin,
+        ray.direction,
+        max_distance,
+        |entity, distance| candidates.push((distance, entity)),
+    );
+
+    nearest(ray, candidates, max_distance, |entity, limit| {
+        match (
+            renderers.get(entity).and_then(|renderer| renderer.model),
+            transforms.get(entity),
+        ) {
+            (Some(model), Some(transform)) => hit_meshes(
+                ray,
+                transform,
+                assets
+                    .model(model)
+                    .meshes
+                    .iter()
+                    .map(|mesh| (mesh.buffers.bounding_sphere, mesh.buffers.triangles())),
+                limit,
+            ),
+            _ => None,
+        }
+    })
+}
+
+/// The distance to the closest triangle of `meshes` that `ray` crosses within
+/// `limit`, and the normal there, facing the ray. Each mesh is its bounding
+/// sphere and its triangles in model space, which `transform` places in the
+/// world.
+fn hit_meshes<T: Iterator<Item = [glam::Vec3; 3]>>(
+    ray: &Ray,
+    transform: &Transform,
+    meshes: impl Iterator<Item = (glam::Vec4, T)>,
+    mut limit: f32,
+) -> Option<(f32, glam::Vec3)> {
+    let matrix = transform.matrix();
+    let normal_matrix = glam::Mat3::from_mat4(matrix).inverse().transpose();
+    let scale = transform.scale.abs().max_element();
+    let mut closest = None;
+    let mut local = None;
+
+    for (sphere, triangles) in meshes {
+        match ray.sphere(matrix.transform_point3(sphere.truncate()), sphere.w * scale) {
+            Some(distance) if distance <= limit => (),
+            _ => continue,
+        }
+
+        let local = local.get_or_insert_with(|| ray.transformed(matrix));
+
+        for triangle in triangles {
+            match local.triangle(triangle) {
+                Some(distance) if distance <= limit => {
+                    let [a, b, c] = triangle;
+                    let normal = (normal_matrix * (b - a).cross(c - a)).normalize_or_zero();
+
+                    limit = distance;
+                    closest = Some((
+                        distance,
+                        if normal.dot(ray.direction) > 0.0 {
+                            -normal
+                        } else {
+                            normal
+                        },
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    closest
+}
+
+/// The closest of what `hit` finds along `ray` for the `candidates`, the
+/// entities with the distances to where the ray enters their boxes. `hit` is
+/// given how far the closest hit so far is, and returns the distance and the
+/// normal of a hit closer than that.
+fn nearest(
+    ray: &Ray,
+    mut candidates: Vec<(f32, Entity)>,
+    max_distance: f32,
+    mut hit: impl FnMut(Entity, f32) -> Option<(f32, glam::Vec3)>,
+) -> Option<Hit> {
+    let mut closest: Option<Hit> = None;
+
+    // Nearest box first, so that the boxes behind the closest hit so far can
+    // be skipped.
+    candidates.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    for (box_distance, entity) in candidates {
+        let limit = closest.map_or(max_distance, |hit| hit.distance);
+
+        if box_distance > limit {
+            break;
+        }
+
+        if let Some((distance, normal)) = hit(entity, limit) {
+            closest = Some(Hit {
+                distance,
+                entity,
+                normal,
+                point: ray.at(distance),
+            });
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In the z = 0 plane, facing +z.
+    const TRIANGLE: [glam::Vec3; 3] = [
+        glam::Vec3::ZERO,
+        glam::Vec3::new(1.0, 0.0, 0.0),
+        glam::Vec3::new(0.0, 1.0, 0.0),
+    ];
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn hits_a_triangle_from_either_side() {
+        let front = Ray::new(glam::Vec3::new(0.25, 0.25, 2.0), -glam::Vec3::Z);
+        let back = Ray::new(glam::Vec3::new(0.25, 0.25, -3.0), glam::Vec3::Z);
+
+        assert!(close(front.triangle(TRIANGLE).unwrap(), 2.0));
+        assert!(close(back.triangle(TRIANGLE).unwrap(), 3.0));
+    }
+
+    #[test]
+    fn misses_a_triangle() {
+        // Beside it, past its long edge.
+        let beside = Ray::new(glam::Vec3::new(0.75, 0.75, 2.0), -glam::Vec3::Z);
+        // Pointing away from it.
+        let away = Ray::new(glam::Vec3::new(0.25, 0.25, 2.0), glam::Vec3::Z);
+        // In its plane.
+        let parallel = Ray::new(glam::Vec3::new(-1.0, 0.25, 0.0), glam::Vec3::X);
+
+        assert_eq!(beside.triangle(TRIANGLE), None);
+        assert_eq!(away.triangle(TRIANGLE), None);
+        assert_eq!(parallel.triangle(TRIANGLE), None);
+    }
+
+    #[test]
+    fn enters_a_sphere() {
+        let center = glam::Vec3::new(0.0, 0.0, 5.0);
+        let ray = Ray::new(glam::Vec3::ZERO, glam::Vec3::Z);
+
+        assert!(close(ray.sphere(center, 1.0).unwrap(), 4.0));
+        // Starting inside counts as a hit right away.
+        assert_eq!(
+            Ray::new(center, glam::Vec3::X).sphere(center, 1.0),
+            Some(0.0)
+        );
+
+        assert_eq!(
+            Ray::new(glam::Vec3::ZERO, glam::Vec3::X).sphere(center, 1.0),
+            None
+        );
+        assert_eq!(
+            Ray::new(glam::Vec3::ZERO, -glam::Vec3::Z).sphere(center, 1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn hits_the_nearest_triangle_of_a_transformed_mesh() {
+        // Two layers, the far one first.
+        let far = TRIANGLE.map(|corner| corner + glam::Vec3::Z);
+        let near = TRIANGLE;
+        let sphere = glam::Vec4::new(0.5, 0.5, 0.5, 1.5);
+        // Doubled and moved back along z, so the layers end up at 10 and 12.
+        let transform = Transform {
+            scale: glam::Vec3::splat(2.0),
+            ..Transform::from_translation(glam::Vec3::new(0.0, 0.0, 10.0))
+        };
+        let ray = Ray::new(glam::Vec3::new(0.5, 0.5, 0.0), glam::Vec3::Z);
+        let meshes = || std::iter::once((sphere, [far, near].into_iter()));
+
+        let (distance, normal) = hit_meshes(&ray, &transform, meshes(), 100.0).unwrap();
+
+        assert!(close(distance, 10.0));
+        assert_eq!(normal, -glam::Vec3::Z);
+
+        // Nothing closer than the limit.
+        assert_eq!(hit_meshes(&ray, &transform, meshes(), 9.0), None);
+    }
+
+    #[test]
+    fn keeps_the_nearest_hit_and_skips_boxes_behind_it() {
+        let mut world = World::new();
+        let [first, second, third] = [(); 3].map(|_| world.spawn().id());
+        let ray = Ray::new(glam::Vec3::ZERO, glam::Vec3::Z);
+        let mut tested = Vec::new();
+
+        // The box of `first` is the nearest, but `second` is hit sooner, and
+        // the box of `third` starts behind that hit.
+        let hit = nearest(
+            &ray,
+            vec![(6.0, third), (2.0, second), (1.0, first)],
+            100.0,
+            |entity, limit| {
+                tested.push(entity);
+
+                let distance = if entity == first { 5.0 } else { 3.0 };
+
+                (distance <= limit).then_some((distance, -glam::Vec3::Z))
+            },
+        )
+        .unwrap();
+
+        assert_eq!(tested, [first, second]);
+        assert_eq!(hit.entity, second);
+        assert_eq!(hit.distance, 3.0);
+        assert_eq!(hit.point, glam::Vec3::new(0.0, 0.0, 3.0));
+    }
+}
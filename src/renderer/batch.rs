@@ -0,0 +1,305 @@
+use super::MeshPipelines;
+use crate::{
+    assets::{Assets, Handle, Material, Model},
+    data::{materials::AlphaMode, shaders::basic::Instance},
+};
+
+type Context = weng::graphics::Context;
+
+/// Orders draws so that state changes as little as possible: by pipeline,
+/// then material, then mesh. Blended draws are ordered back to front before
+/// anything else, which leaves `depth` at 0 for the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    alpha_mode: AlphaMode,
+    depth: u32,
+    material: Handle<Material<Context>>,
+    model: Handle<Model<Context>>,
+    mesh: usize,
+}
+
+impl SortKey {
+    fn same_batch(&self, other: &Self) -> bool {
+        self.alpha_mode == other.alpha_mode
+            && self.material == other.material
+            && self.model == other.model
+            && self.mesh == other.mesh
+    }
+}
+
+/// Instances of one mesh with one material, drawn with a single call.
+struct Batch {
+    instances: std::ops::Range<u32>,
+    key: SortKey,
+}
+
+/// Collects the draws of a frame and merges them into instanced batches.
+pub struct DrawList {
+    batches: Vec<Batch>,
+    draws: Vec<(SortKey, Instance)>,
+    instance_buffer: weng::wgpu::Buffer,
+}
+
+impl DrawList {
+    /// Sorts and merges the draws pushed since the last call, and uploads
+    /// their instances.
+    pub fn build(&mut self, graphics: &Context, camera_position: glam::Vec3) {
+        merge(&mut self.draws, &mut self.batches, camera_position);
+
+        let instances = self
+            .draws
+            .drain(..)
+            .map(|(_, instance)| instance)
+            .collect::<Vec<_>>();
+        let bytes = bytemuck::cast_slice(&instances);
+
+        if bytes.len() as u64 > self.instance_buffer.size() {
+            self.instance_buffer = create_instance_buffer(graphics, bytes.len() as u64);
+        }
+        graphics
+            .queue()
+            .write_buffer(&self.instance_buffer, 0, bytes);
+    }
+
+    /// Draws the batches with one of `alpha_modes`, in the order [`build`]
+    /// sorted them. Every bind group but the material must already be set.
+    ///
+    /// [`build`]: DrawList::build
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut weng::wgpu::RenderPass<'a>,
+        assets: &'a Assets<Context>,
+        pipelines: &'a MeshPipelines,
+        alpha_modes: &[AlphaMode],
+    ) {
+        self.draw_with(pass, assets, alpha_modes, |alpha_mode| {
+            pipelines.get(alpha_mode)
+        });
+    }
+
+    /// Draws every batch with the same `pipeline`, like the wireframe.
+    pub fn draw_all<'a>(
+        &'a self,
+        pass: &mut weng::wgpu::RenderPass<'a>,
+        assets: &'a Assets<Context>,
+        pipeline: &'a weng::wgpu::RenderPipeline,
+    ) {
+        self.draw_with(
+            pass,
+            assets,
+            &[AlphaMode::Opaque, AlphaMode::Cutout, AlphaMode::Blended],
+            |_| pipeline,
+        );
+    }
+
+    fn draw_with<'a>(
+        &'a self,
+        pass: &mut weng::wgpu::RenderPass<'a>,
+        assets: &'a Assets<Context>,
+        alpha_modes: &[AlphaMode],
+        pipelines: impl Fn(AlphaMode) -> &'a weng::wgpu::RenderPipeline,
+    ) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        let mut pipeline = None;
+        let mut material = None;
+        let mut mesh = None;
+
+        pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+
+        for batch in &self.batches {
+            let key = batch.key;
+
+            if !alpha_modes.contains(&key.alpha_mode) {
+                continue;
+            }
+
+            // Alpha modes can share a pipeline.
+            let batch_pipeline = pipelines(key.alpha_mode);
+            if pipeline != Some(batch_pipeline as *const _) {
+                pass.set_pipeline(batch_pipeline);
+                pipeline = Some(batch_pipeline as *const _);
+            }
+
+            if material != Some(key.material) {
+                pass.set_bind_group(0, &assets.material(key.material).bind_group, &[]);
+                material = Some(key.material);
+            }
+
+            let buffers = &assets.model(key.model).meshes[key.mesh].buffers;
+            if mesh != Some((key.model, key.mesh)) {
+                pass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+                pass.set_index_buffer(
+                    buffers.index_buffer.slice(..),
+                    weng::wgpu::IndexFormat::Uint32,
+                );
+                mesh = Some((key.model, key.mesh));
+            }
+
+            pass.draw_indexed(0..buffers.index_count, 0, batch.instances.clone());
+        }
+    }
+
+    pub fn new(graphics: &Context) -> Self {
+        Self {
+            batches: Vec::new(),
+            draws: Vec::new(),
+            instance_buffer: create_instance_buffer(graphics, 0),
+        }
+    }
+
+    /// Draws every mesh of `model` at `instance`.
+    pub fn push(
+        &mut self,
+        assets: &Assets<Context>,
+        model: Handle<Model<Context>>,
+        instance: Instance,
+    ) {
+        for (mesh, data) in assets.model(model).meshes.iter().enumerate() {
+            self.draws.push((
+                SortKey {
+                    alpha_mode: assets.material(data.material).properties.alpha_mode,
+                    depth: 0,
+                    material: data.material,
+                    model,
+                    mesh,
+                },
+                instance,
+            ));
+        }
+    }
+}
+
+/// Sorts `draws` and replaces `batches` with runs of them that can be drawn
+/// with one call.
+fn merge(draws: &mut [(SortKey, Instance)], batches: &mut Vec<Batch>, camera_position: glam::Vec3) {
+    for (key, instance) in draws.iter_mut() {
+        if key.alpha_mode == AlphaMode::Blended {
+            key.depth = super::sort::back_to_front_key(camera_position, instance.position());
+        }
+    }
+
+    draws.sort_by_key(|(key, _)| *key);
+    batches.clear();
+
+    for (index, (key, _)) in draws.iter().enumerate() {
+        let index = index as u32;
+
+        match batches.last_mut() {
+            Some(batch) if batch.key.same_batch(key) => batch.instances.end = index + 1,
+            _ => batches.push(Batch {
+                instances: index..index + 1,
+                key: *key,
+            }),
+        }
+    }
+}
+
+fn create_instance_buffer(graphics: &Context, size: u64) -> weng::wgpu::Buffer {
+    graphics
+        .device()
+        .create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: weng::wgpu::BufferUsages::VERTEX | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(alpha_mode: AlphaMode, material: u32, mesh: usize, x: f32) -> (SortKey, Instance) {
+        (
+            SortKey {
+                alpha_mode,
+                depth: 0,
+                material: Handle::test(material),
+                model: Handle::test(0),
+                mesh,
+            },
+            Instance {
+                model: glam::Mat4::from_translation(glam::Vec3::X * x).to_cols_array(),
+                normal: [0.0; 9],
+            },
+        )
+    }
+
+    fn merged(mut draws: Vec<(SortKey, Instance)>) -> (Vec<(SortKey, Instance)>, Vec<Batch>) {
+        let mut batches = Vec::new();
+
+        merge(&mut draws, &mut batches, glam::Vec3::ZERO);
+
+        (draws, batches)
+    }
+
+    #[test]
+    fn blended_draws_come_last_and_back_to_front() {
+        let (draws, batches) = merged(vec![
+            draw(AlphaMode::Blended, 0, 0, 1.0),
+            draw(AlphaMode::Opaque, 0, 0, 5.0),
+            draw(AlphaMode::Blended, 0, 0, 3.0),
+            draw(AlphaMode::Blended, 0, 0, 2.0),
+        ]);
+        let positions = draws
+            .iter()
+            .map(|(_, instance)| instance.position().x)
+            .collect::<Vec<_>>();
+
+        assert_eq!(positions, [5.0, 3.0, 2.0, 1.0]);
+        // Instances are drawn in order, so neighbours of one mesh still merge.
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[1].instances, 1..4);
+    }
+
+    #[test]
+    fn blended_draws_interleave_materials_by_distance() {
+        let (_, batches) = merged(vec![
+            draw(AlphaMode::Blended, 1, 0, 1.0),
+            draw(AlphaMode::Blended, 2, 0, 2.0),
+            draw(AlphaMode::Blended, 1, 0, 3.0),
+        ]);
+        let materials = batches
+            .iter()
+            .map(|batch| batch.key.material)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            materials,
+            [Handle::test(1), Handle::test(2), Handle::test(1)]
+        );
+    }
+
+    #[test]
+    fn opaque_and_cutout_draws_group_by_material_then_mesh() {
+        let (draws, batches) = merged(vec![
+            draw(AlphaMode::Cutout, 0, 0, 0.0),
+            draw(AlphaMode::Opaque, 2, 1, 0.0),
+            draw(AlphaMode::Opaque, 1, 1, 0.0),
+            draw(AlphaMode::Opaque, 2, 0, 0.0),
+            draw(AlphaMode::Opaque, 1, 1, 0.0),
+            draw(AlphaMode::Opaque, 2, 1, 9.0),
+        ]);
+        let order = batches
+            .iter()
+            .map(|batch| (batch.key.alpha_mode, batch.key.material, batch.key.mesh))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            order,
+            [
+                (AlphaMode::Opaque, Handle::test(1), 1),
+                (AlphaMode::Opaque, Handle::test(2), 0),
+                (AlphaMode::Opaque, Handle::test(2), 1),
+                (AlphaMode::Cutout, Handle::test(0), 0),
+            ]
+        );
+        // Distance only matters to blended draws.
+        assert_eq!(batches[0].instances, 0..2);
+        assert_eq!(batches[2].instances, 3..5);
+        assert_eq!(draws.len(), 6);
+    }
+}
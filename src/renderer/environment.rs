@@ -0,0 +1,403 @@
+use crate::data::{
+    self,
+    environments::Source,
+    shaders::ibl::Params,
+    textures::{Hdr, Type},
+};
+use anyhow::bail;
+use std::{num::NonZeroU32, path::PathBuf};
+
+const BRDF_SIZE: u32 = 256;
+/// Face size of cubemaps converted from equirectangular images.
+const CUBE_SIZE: u32 = 512;
+const FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba16Float;
+const IRRADIANCE_SIZE: u32 = 32;
+/// Mip levels of the prefiltered map, from a roughness of 0 to 1.
+const PREFILTERED_LEVELS: u32 = 5;
+const PREFILTERED_SIZE: u32 = 128;
+const WORKGROUP_SIZE: u32 = 8;
+
+/// An environment cubemap and the maps precomputed from it for image based
+/// lighting, bound as described by [`create_bind_group_layout`].
+pub struct Environment {
+    pub bind_group: weng::wgpu::BindGroup,
+}
+
+impl Environment {
+    /// Loads `source` and convolves it into the irradiance and prefiltered
+    /// specular maps with the compute shaders of `shader`.
+    pub fn load(
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+        source: &Source,
+        layout: &weng::wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        let device = graphics.device();
+        let sampler = device.create_sampler(&weng::wgpu::SamplerDescriptor {
+            address_mode_u: weng::wgpu::AddressMode::ClampToEdge,
+            address_mode_v: weng::wgpu::AddressMode::ClampToEdge,
+            address_mode_w: weng::wgpu::AddressMode::ClampToEdge,
+            mag_filter: weng::wgpu::FilterMode::Linear,
+            min_filter: weng::wgpu::FilterMode::Linear,
+            mipmap_filter: weng::wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let mut encoder =
+            device.create_command_encoder(&weng::wgpu::CommandEncoderDescriptor { label: None });
+
+        let environment = match source {
+            Source::Cubemap(faces) => load_faces(graphics, faces)?,
+            Source::Equirectangular(path) => {
+                let image = data::textures::decode(path)?;
+                let equirectangular =
+                    data::textures::create(graphics, &image, Hdr::FORMAT, Hdr::SAMPLING)?;
+                let pipeline = create_pipeline(graphics, shader, "equirectangular_to_cube");
+                let cube = create_cube(
+                    graphics,
+                    CUBE_SIZE,
+                    data::textures::mip_level_count(CUBE_SIZE, CUBE_SIZE),
+                );
+
+                for level in 0..cube.mip_level_count() {
+                    let size = CUBE_SIZE >> level;
+                    // An equirectangular image spans four faces horizontally.
+                    let lod = (equirectangular.texture.width() as f32 / (4 * size) as f32)
+                        .log2()
+                        .max(0.0);
+                    let params = create_params(graphics, lod, 0.0);
+
+                    dispatch(
+                        graphics,
+                        &mut encoder,
+                        &pipeline,
+                        &[
+                            entry(0, weng::wgpu::BindingResource::Sampler(&sampler)),
+                            entry(
+                                1,
+                                weng::wgpu::BindingResource::TextureView(&equirectangular.view),
+                            ),
+                            entry(
+                                3,
+                                weng::wgpu::BindingResource::TextureView(&storage_view(
+                                    &cube, level,
+                                )),
+                            ),
+                            entry(4, params.as_entire_binding()),
+                        ],
+                        size,
+                        6,
+                    );
+                }
+
+                cube
+            }
+        };
+
+        let environment_view = cube_view(&environment);
+
+        let irradiance = create_cube(graphics, IRRADIANCE_SIZE, 1);
+
+        dispatch(
+            graphics,
+            &mut encoder,
+            &create_pipeline(graphics, shader, "irradiance"),
+            &[
+                entry(0, weng::wgpu::BindingResource::Sampler(&sampler)),
+                entry(
+                    2,
+                    weng::wgpu::BindingResource::TextureView(&environment_view),
+                ),
+                entry(
+                    3,
+                    weng::wgpu::BindingResource::TextureView(&storage_view(&irradiance, 0)),
+                ),
+            ],
+            IRRADIANCE_SIZE,
+            6,
+        );
+
+        let prefiltered = create_cube(graphics, PREFILTERED_SIZE, PREFILTERED_LEVELS);
+        let prefilter = create_pipeline(graphics, shader, "prefilter");
+
+        for level in 0..PREFILTERED_LEVELS {
+            // The mirror-like first level samples the environment mip that
+            // matches its size instead of importance sampling it.
+            let lod = (environment.width() as f32 / PREFILTERED_SIZE as f32)
+                .log2()
+                .max(0.0);
+            let params = create_params(
+                graphics,
+                lod,
+                level as f32 / (PREFILTERED_LEVELS - 1) as f32,
+            );
+
+            dispatch(
+                graphics,
+                &mut encoder,
+                &prefilter,
+                &[
+                    entry(0, weng::wgpu::BindingResource::Sampler(&sampler)),
+                    entry(
+                        2,
+                        weng::wgpu::BindingResource::TextureView(&environment_view),
+                    ),
+                    entry(
+                        3,
+                        weng::wgpu::BindingResource::TextureView(&storage_view(
+                            &prefiltered,
+                            level,
+                        )),
+                    ),
+                    entry(4, params.as_entire_binding()),
+                ],
+                PREFILTERED_SIZE >> level,
+                6,
+            );
+        }
+
+        let brdf = device.create_texture(&weng::wgpu::TextureDescriptor {
+            label: None,
+            size: weng::wgpu::Extent3d {
+                width: BRDF_SIZE,
+                height: BRDF_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: weng::wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: weng::wgpu::TextureUsages::TEXTURE_BINDING
+                | weng::wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+        let brdf_view = brdf.create_view(&weng::wgpu::TextureViewDescriptor::default());
+
+        dispatch(
+            graphics,
+            &mut encoder,
+            &create_pipeline(graphics, shader, "brdf"),
+            &[entry(
+                5,
+                weng::wgpu::BindingResource::TextureView(&brdf_view),
+            )],
+            BRDF_SIZE,
+            1,
+        );
+
+        graphics.queue().submit(Some(encoder.finish()));
+
+        Ok(Self {
+            bind_group: device.create_bind_group(&weng::wgpu::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &[
+                    entry(
+                        0,
+                        weng::wgpu::BindingResource::TextureView(&environment_view),
+                    ),
+                    entry(
+                        1,
+                        weng::wgpu::BindingResource::TextureView(&cube_view(&irradiance)),
+                    ),
+                    entry(
+                        2,
+                        weng::wgpu::BindingResource::TextureView(&cube_view(&prefiltered)),
+                    ),
+                    entry(3, weng::wgpu::BindingResource::TextureView(&brdf_view)),
+                    entry(4, weng::wgpu::BindingResource::Sampler(&sampler)),
+                ],
+            }),
+        })
+    }
+}
+
+/// The environment cubemap at binding 0, its irradiance and prefiltered
+/// specular maps at 1 and 2, the BRDF lookup table at 3 and a linear sampler
+/// for all of them at 4.
+pub fn create_bind_group_layout(graphics: &weng::graphics::Context) -> weng::wgpu::BindGroupLayout {
+    let texture = |binding, view_dimension| weng::wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+        ty: weng::wgpu::BindingType::Texture {
+            sample_type: weng::wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension,
+            multisampled: false,
+        },
+        count: None,
+    };
+
+    graphics
+        .device()
+        .create_bind_group_layout(&weng::wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                texture(0, weng::wgpu::TextureViewDimension::Cube),
+                texture(1, weng::wgpu::TextureViewDimension::Cube),
+                texture(2, weng::wgpu::TextureViewDimension::Cube),
+                texture(3, weng::wgpu::TextureViewDimension::D2),
+                weng::wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: weng::wgpu::ShaderStages::FRAGMENT,
+                    ty: weng::wgpu::BindingType::Sampler(weng::wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+}
+
+fn create_cube(
+    graphics: &weng::graphics::Context,
+    size: u32,
+    mip_level_count: u32,
+) -> weng::wgpu::Texture {
+    graphics
+        .device()
+        .create_texture(&weng::wgpu::TextureDescriptor {
+            label: None,
+            size: weng::wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: weng::wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: weng::wgpu::TextureUsages::TEXTURE_BINDING
+                | weng::wgpu::TextureUsages::STORAGE_BINDING
+                | weng::wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+}
+
+fn create_params(
+    graphics: &weng::graphics::Context,
+    lod: f32,
+    roughness: f32,
+) -> weng::wgpu::Buffer {
+    super::create_buffer(
+        graphics,
+        &[Params {
+            lod,
+            roughness,
+            _padding: [0.0; 2],
+        }],
+        weng::wgpu::BufferUsages::UNIFORM,
+    )
+}
+
+/// Each entry point gets a pipeline with a layout derived from the bindings it
+/// uses.
+fn create_pipeline(
+    graphics: &weng::graphics::Context,
+    shader: &weng::wgpu::ShaderModule,
+    entry_point: &str,
+) -> weng::wgpu::ComputePipeline {
+    graphics
+        .device()
+        .create_compute_pipeline(&weng::wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            module: shader,
+            entry_point,
+        })
+}
+
+fn cube_view(texture: &weng::wgpu::Texture) -> weng::wgpu::TextureView {
+    texture.create_view(&weng::wgpu::TextureViewDescriptor {
+        dimension: Some(weng::wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    })
+}
+
+/// Runs `pipeline` once per texel of every layer of a `size` x `size` target.
+fn dispatch(
+    graphics: &weng::graphics::Context,
+    encoder: &mut weng::wgpu::CommandEncoder,
+    pipeline: &weng::wgpu::ComputePipeline,
+    entries: &[weng::wgpu::BindGroupEntry],
+    size: u32,
+    layers: u32,
+) {
+    let bind_group = graphics
+        .device()
+        .create_bind_group(&weng::wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries,
+        });
+    let workgroups = (size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+
+    let mut pass = encoder.begin_compute_pass(&weng::wgpu::ComputePassDescriptor { label: None });
+
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, &bind_group, &[]);
+    pass.dispatch_workgroups(workgroups, workgroups, layers);
+}
+
+fn entry(binding: u32, resource: weng::wgpu::BindingResource) -> weng::wgpu::BindGroupEntry {
+    weng::wgpu::BindGroupEntry { binding, resource }
+}
+
+fn load_faces(
+    graphics: &weng::graphics::Context,
+    faces: &[PathBuf; 6],
+) -> anyhow::Result<weng::wgpu::Texture> {
+    let images = faces
+        .iter()
+        .map(image::open)
+        .collect::<Result<Vec<_>, _>>()?;
+    let size = images[0].width();
+
+    if images
+        .iter()
+        .any(|image| image.width() != size || image.height() != size)
+    {
+        bail!("cubemap faces have to be square and of equal size");
+    }
+
+    let cube = create_cube(graphics, size, data::textures::mip_level_count(size, size));
+
+    for (layer, image) in images.iter().enumerate() {
+        for (level, data) in data::textures::convert(image, FORMAT)?.iter().enumerate() {
+            let level_size = (size >> level).max(1);
+
+            graphics.queue().write_texture(
+                weng::wgpu::ImageCopyTexture {
+                    texture: &cube,
+                    mip_level: level as u32,
+                    origin: weng::wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: weng::wgpu::TextureAspect::All,
+                },
+                data,
+                weng::wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(
+                        level_size * FORMAT.describe().block_size as u32,
+                    ),
+                    rows_per_image: NonZeroU32::new(level_size),
+                },
+                weng::wgpu::Extent3d {
+                    width: level_size,
+                    height: level_size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    Ok(cube)
+}
+
+fn storage_view(texture: &weng::wgpu::Texture, level: u32) -> weng::wgpu::TextureView {
+    texture.create_view(&weng::wgpu::TextureViewDescriptor {
+        dimension: Some(weng::wgpu::TextureViewDimension::D2Array),
+        base_mip_level: level,
+        mip_level_count: NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
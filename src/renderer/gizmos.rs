@@ -0,0 +1,124 @@
+use crate::{data::shaders::gizmos::Vertex, gizmos::Gizmos};
+
+/// Draws the lines of [`Gizmos`] over the final image, without depth testing
+/// so that nothing in the scene hides them.
+pub struct GizmoRenderer {
+    pipeline: weng::wgpu::RenderPipeline,
+    vertex_buffer: weng::wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl GizmoRenderer {
+    pub fn new(
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+        camera_layout: &weng::wgpu::BindGroupLayout,
+    ) -> Self {
+        let layout =
+            graphics
+                .device()
+                .create_pipeline_layout(&weng::wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[camera_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let pipeline =
+            graphics
+                .device()
+                .create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(&layout),
+                    vertex: weng::wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: &[weng::wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<Vertex>()
+                                as weng::wgpu::BufferAddress,
+                            step_mode: weng::wgpu::VertexStepMode::Vertex,
+                            attributes: <Vertex as weng::graphics::vertices::Vertex>::ATTRIBUTES,
+                        }],
+                    },
+                    primitive: weng::wgpu::PrimitiveState {
+                        topology: weng::wgpu::PrimitiveTopology::LineList,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: weng::wgpu::MultisampleState::default(),
+                    fragment: Some(weng::wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(weng::wgpu::ColorTargetState {
+                            format: graphics.surface_format(),
+                            blend: Some(weng::wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: weng::wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview: None,
+                });
+
+        Self {
+            pipeline,
+            vertex_buffer: create_vertex_buffer(graphics, 0),
+            vertex_count: 0,
+        }
+    }
+
+    /// Uploads the lines of `gizmos`, which are drawn until the next call.
+    pub fn prepare(&mut self, graphics: &weng::graphics::Context, gizmos: &Gizmos) {
+        let vertices = if gizmos.enabled() {
+            gizmos.vertices()
+        } else {
+            &[]
+        };
+        let bytes = bytemuck::cast_slice(vertices);
+
+        if bytes.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = create_vertex_buffer(graphics, bytes.len() as u64);
+        }
+        graphics.queue().write_buffer(&self.vertex_buffer, 0, bytes);
+
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Draws over `output`, keeping what is already in it.
+    pub fn render(
+        &self,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        output: &weng::wgpu::TextureView,
+        camera_bind_group: &weng::wgpu::BindGroup,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&weng::wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(weng::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: weng::wgpu::Operations {
+                    load: weng::wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+fn create_vertex_buffer(graphics: &weng::graphics::Context, size: u64) -> weng::wgpu::Buffer {
+    graphics
+        .device()
+        .create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: weng::wgpu::BufferUsages::VERTEX | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+}
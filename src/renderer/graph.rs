@@ -0,0 +1,362 @@
+/// A target allocated by the graph. Targets with the same description are
+/// reused from one frame to the next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TargetDesc {
+    pub format: weng::wgpu::TextureFormat,
+    pub height: u32,
+    pub sample_count: u32,
+    pub width: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceId(usize);
+
+enum Resource<'a> {
+    Buffer,
+    Imported(&'a weng::wgpu::TextureView),
+    Transient(TargetDesc),
+}
+
+type Execute<'a> = Box<dyn FnOnce(&mut weng::wgpu::CommandEncoder, &Resources) + 'a>;
+
+struct Pass<'a> {
+    execute: Execute<'a>,
+    name: &'static str,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+}
+
+/// The passes of a frame and the resources they use. A pass that reads a
+/// resource runs after the passes added before it that write it, or after
+/// every pass writing it when none were. Passes writing the same resource run
+/// in the order they were added.
+pub struct Graph<'a> {
+    passes: Vec<Pass<'a>>,
+    resources: Vec<Resource<'a>>,
+}
+
+impl<'a> Graph<'a> {
+    pub fn add_pass<'g>(&'g mut self, name: &'static str) -> PassBuilder<'g, 'a> {
+        PassBuilder {
+            graph: self,
+            name,
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// A buffer that compute passes fill for later passes to draw with. Passes
+    /// bind it themselves, the graph only orders them by it.
+    pub fn buffer(&mut self) -> ResourceId {
+        self.resources.push(Resource::Buffer);
+
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// A target that only lives for the frame.
+    pub fn create(&mut self, desc: TargetDesc) -> ResourceId {
+        self.resources.push(Resource::Transient(desc));
+
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Records every pass into `encoder`, allocating transient targets from
+    /// `pool`.
+    pub fn execute(
+        self,
+        graphics: &weng::graphics::Context,
+        pool: &mut TargetPool,
+        encoder: &mut weng::wgpu::CommandEncoder,
+    ) {
+        let order = self.order();
+
+        pool.begin();
+
+        let pooled = self
+            .resources
+            .iter()
+            .map(|resource| match resource {
+                Resource::Buffer | Resource::Imported(_) => None,
+                Resource::Transient(desc) => Some(pool.acquire(graphics, *desc)),
+            })
+            .collect::<Vec<_>>();
+
+        {
+            let resources = Resources {
+                views: self
+                    .resources
+                    .iter()
+                    .zip(&pooled)
+                    .map(|(resource, index)| match resource {
+                        Resource::Buffer => None,
+                        Resource::Imported(view) => Some(*view),
+                        Resource::Transient(_) => Some(&pool.targets[index.unwrap()].view),
+                    })
+                    .collect(),
+            };
+
+            let mut passes = self.passes.into_iter().map(Some).collect::<Vec<_>>();
+
+            for index in order {
+                let pass = passes[index].take().unwrap();
+
+                encoder.push_debug_group(pass.name);
+                (pass.execute)(encoder, &resources);
+                encoder.pop_debug_group();
+            }
+        }
+
+        pool.end();
+    }
+
+    /// A target owned by someone else, like the surface.
+    pub fn import(&mut self, view: &'a weng::wgpu::TextureView) -> ResourceId {
+        self.resources.push(Resource::Imported(view));
+
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            resources: Vec::new(),
+        }
+    }
+
+    /// Sorts the passes topologically, keeping the order they were added in
+    /// where it is free to choose.
+    fn order(&self) -> Vec<usize> {
+        let writes =
+            |pass: usize, resource: &ResourceId| self.passes[pass].writes.contains(resource);
+        let depends_on = |pass: usize, other: usize| {
+            let reads_from = |resource: &ResourceId| {
+                writes(other, resource)
+                    && (other < pass || (0..pass).all(|earlier| !writes(earlier, resource)))
+            };
+
+            pass != other
+                && (self.passes[pass].reads.iter().any(reads_from)
+                    || (other < pass
+                        && self.passes[pass]
+                            .writes
+                            .iter()
+                            .any(|resource| writes(other, resource))))
+        };
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut remaining = (0..self.passes.len()).collect::<Vec<_>>();
+
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .position(|&pass| remaining.iter().all(|&other| !depends_on(pass, other)))
+                .unwrap_or_else(|| {
+                    let names = remaining
+                        .iter()
+                        .map(|&pass| self.passes[pass].name)
+                        .collect::<Vec<_>>();
+
+                    panic!("render passes {names:?} depend on each other")
+                });
+
+            order.push(remaining.remove(next));
+        }
+
+        order
+    }
+}
+
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut Graph<'a>,
+    name: &'static str,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    /// Adds the pass, which records its commands with `execute` once its
+    /// dependencies have.
+    pub fn execute(self, execute: impl FnOnce(&mut weng::wgpu::CommandEncoder, &Resources) + 'a) {
+        self.graph.passes.push(Pass {
+            execute: Box::new(execute),
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+        });
+    }
+
+    pub fn reads(mut self, resource: ResourceId) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    pub fn writes(mut self, resource: ResourceId) -> Self {
+        self.writes.push(resource);
+        self
+    }
+}
+
+/// The views of every target of the graph, for passes to draw with.
+pub struct Resources<'r> {
+    views: Vec<Option<&'r weng::wgpu::TextureView>>,
+}
+
+impl Resources<'_> {
+    pub fn view(&self, resource: ResourceId) -> &weng::wgpu::TextureView {
+        self.views[resource.0].expect("buffers have no view")
+    }
+}
+
+struct PooledTarget {
+    desc: TargetDesc,
+    used: bool,
+    view: weng::wgpu::TextureView,
+}
+
+/// Keeps the transient targets of the last frame, so a graph needing the same
+/// targets allocates nothing. Targets a frame did not need are freed.
+pub struct TargetPool {
+    targets: Vec<PooledTarget>,
+}
+
+impl TargetPool {
+    fn acquire(&mut self, graphics: &weng::graphics::Context, desc: TargetDesc) -> usize {
+        if let Some(index) = self
+            .targets
+            .iter()
+            .position(|target| !target.used && target.desc == desc)
+        {
+            self.targets[index].used = true;
+
+            return index;
+        }
+
+        let view = graphics
+            .device()
+            .create_texture(&weng::wgpu::TextureDescriptor {
+                label: None,
+                size: weng::wgpu::Extent3d {
+                    width: desc.width,
+                    height: desc.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: desc.sample_count,
+                dimension: weng::wgpu::TextureDimension::D2,
+                format: desc.format,
+                usage: weng::wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | weng::wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&weng::wgpu::TextureViewDescriptor::default());
+
+        self.targets.push(PooledTarget {
+            desc,
+            used: true,
+            view,
+        });
+
+        self.targets.len() - 1
+    }
+
+    fn begin(&mut self) {
+        for target in &mut self.targets {
+            target.used = false;
+        }
+    }
+
+    fn end(&mut self) {
+        self.targets.retain(|target| target.used);
+    }
+
+    pub fn new() -> Self {
+        Self {
+            targets: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(graph: &mut Graph) -> ResourceId {
+        graph.create(TargetDesc {
+            format: weng::wgpu::TextureFormat::Rgba8Unorm,
+            height: 1,
+            sample_count: 1,
+            width: 1,
+        })
+    }
+
+    fn names(graph: &Graph) -> Vec<&'static str> {
+        graph
+            .order()
+            .into_iter()
+            .map(|pass| graph.passes[pass].name)
+            .collect()
+    }
+
+    #[test]
+    fn passes_reading_and_writing_the_same_targets_keep_their_order() {
+        let mut graph = Graph::new();
+        let color = target(&mut graph);
+        let depth = target(&mut graph);
+
+        graph
+            .add_pass("opaque")
+            .writes(color)
+            .writes(depth)
+            .execute(|_, _| ());
+        graph
+            .add_pass("skybox")
+            .reads(depth)
+            .writes(color)
+            .execute(|_, _| ());
+        graph
+            .add_pass("transparent")
+            .reads(depth)
+            .writes(color)
+            .execute(|_, _| ());
+
+        assert_eq!(names(&graph), ["opaque", "skybox", "transparent"]);
+    }
+
+    #[test]
+    fn a_reader_added_before_every_writer_runs_after_them() {
+        let mut graph = Graph::new();
+        let color = target(&mut graph);
+        let visible = target(&mut graph);
+
+        graph
+            .add_pass("opaque")
+            .reads(visible)
+            .writes(color)
+            .execute(|_, _| ());
+        graph.add_pass("unrelated").execute(|_, _| ());
+        graph.add_pass("cull").writes(visible).execute(|_, _| ());
+
+        assert_eq!(names(&graph), ["unrelated", "cull", "opaque"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "depend on each other")]
+    fn passes_reading_what_the_other_writes_first_are_a_cycle() {
+        let mut graph = Graph::new();
+        let first = target(&mut graph);
+        let second = target(&mut graph);
+
+        graph
+            .add_pass("a")
+            .reads(second)
+            .writes(first)
+            .execute(|_, _| ());
+        graph
+            .add_pass("b")
+            .reads(first)
+            .writes(second)
+            .execute(|_, _| ());
+
+        graph.order();
+    }
+}
@@ -0,0 +1,451 @@
+pub mod batch;
+pub mod environment;
+#[cfg(feature = "gizmos")]
+pub mod gizmos;
+pub mod graph;
+pub mod passes;
+pub mod post;
+pub mod skybox;
+pub mod sort;
+pub mod sprites;
+pub mod text;
+pub mod ui;
+
+use crate::{
+    data::materials::AlphaMode,
+    settings::{self, View},
+};
+use weng::wgpu::util::DeviceExt;
+
+pub const DEPTH_FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Depth32Float;
+/// Scenes are lit and drawn in this format, then tone mapped to the surface by
+/// [`post::Post`].
+pub const HDR_FORMAT: weng::wgpu::TextureFormat = weng::wgpu::TextureFormat::Rgba16Float;
+
+/// The surface texture of a frame and the commands recorded for it. Nothing is
+/// shown until [`Frame::finish`] submits and presents it.
+pub struct Frame {
+    pub encoder: weng::wgpu::CommandEncoder,
+    output: weng::wgpu::SurfaceTexture,
+    pub view: weng::wgpu::TextureView,
+}
+
+impl Frame {
+    pub fn begin(graphics: &weng::graphics::Context) -> Result<Self, weng::wgpu::SurfaceError> {
+        let output = graphics.surface().get_current_texture()?;
+
+        Ok(Self {
+            encoder: graphics
+                .device()
+                .create_command_encoder(&weng::wgpu::CommandEncoderDescriptor { label: None }),
+            view: output
+                .texture
+                .create_view(&weng::wgpu::TextureViewDescriptor::default()),
+            output,
+        })
+    }
+
+    pub fn finish(self, graphics: &weng::graphics::Context) {
+        graphics.queue().submit(Some(self.encoder.finish()));
+        self.output.present();
+    }
+}
+
+/// The mesh pipelines for every alpha mode. Cutout materials discard in the
+/// shader, so they share the opaque pipeline.
+pub struct MeshPipelines {
+    blended: weng::wgpu::RenderPipeline,
+    opaque: weng::wgpu::RenderPipeline,
+    wireframe: Option<weng::wgpu::RenderPipeline>,
+}
+
+impl MeshPipelines {
+    pub fn get(&self, alpha_mode: AlphaMode) -> &weng::wgpu::RenderPipeline {
+        match alpha_mode {
+            AlphaMode::Opaque | AlphaMode::Cutout => &self.opaque,
+            AlphaMode::Blended => &self.blended,
+        }
+    }
+
+    /// Meshes show `debug.view`, and get a wireframe pipeline if
+    /// `debug.wireframe` is set and the device can draw polygons as lines.
+    pub fn new<V, I>(
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+        bind_group_layouts: &[&weng::wgpu::BindGroupLayout],
+        sample_count: u32,
+        debug: &settings::Debug,
+    ) -> Self
+    where
+        V: weng::graphics::vertices::Vertex,
+        I: weng::graphics::instances::Instance,
+    {
+        let layout =
+            graphics
+                .device()
+                .create_pipeline_layout(&weng::wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts,
+                    push_constant_ranges: &[],
+                });
+
+        let entry_point = match debug.view {
+            View::Lit => "fs_main",
+            View::Diffuse => "fs_diffuse",
+            View::Depth => "fs_depth",
+            View::NormalMap => "fs_normal_map",
+            View::Uvs => "fs_uvs",
+            View::WorldNormals => "fs_world_normals",
+        };
+
+        let lines_supported = graphics
+            .device()
+            .features()
+            .contains(weng::wgpu::Features::POLYGON_MODE_LINE);
+        if debug.wireframe && !lines_supported {
+            log::warn!("the wireframe is not supported by the device");
+        }
+
+        Self {
+            blended: create_mesh_pipeline::<V, I>(
+                graphics,
+                shader,
+                &layout,
+                sample_count,
+                AlphaMode::Blended,
+                entry_point,
+                weng::wgpu::PolygonMode::Fill,
+            ),
+            opaque: create_mesh_pipeline::<V, I>(
+                graphics,
+                shader,
+                &layout,
+                sample_count,
+                AlphaMode::Opaque,
+                entry_point,
+                weng::wgpu::PolygonMode::Fill,
+            ),
+            wireframe: (debug.wireframe && lines_supported).then(|| {
+                // Blended pipelines leave the depth as is.
+                create_mesh_pipeline::<V, I>(
+                    graphics,
+                    shader,
+                    &layout,
+                    sample_count,
+                    AlphaMode::Blended,
+                    "fs_wireframe",
+                    weng::wgpu::PolygonMode::Line,
+                )
+            }),
+        }
+    }
+
+    /// Draws over the meshes, when enabled.
+    pub fn wireframe(&self) -> Option<&weng::wgpu::RenderPipeline> {
+        self.wireframe.as_ref()
+    }
+}
+
+/// Where a scene pass is among the scene passes of a frame. The first clears
+/// the targets and the last resolves them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScenePass {
+    First,
+    Middle,
+    Last,
+}
+
+/// The targets the scene passes draw into. With MSAA the scene is drawn into a
+/// multisampled target, which the last scene pass resolves into the HDR one.
+#[derive(Clone, Copy, Debug)]
+pub struct SceneTargets {
+    color: graph::ResourceId,
+    depth: graph::ResourceId,
+    hdr: graph::ResourceId,
+}
+
+impl SceneTargets {
+    pub fn begin_pass<'r>(
+        &self,
+        encoder: &'r mut weng::wgpu::CommandEncoder,
+        resources: &'r graph::Resources,
+        scene_pass: ScenePass,
+    ) -> weng::wgpu::RenderPass<'r> {
+        let first = scene_pass == ScenePass::First;
+        let resolve = scene_pass == ScenePass::Last && self.color != self.hdr;
+
+        encoder.begin_render_pass(&weng::wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(weng::wgpu::RenderPassColorAttachment {
+                view: resources.view(self.color),
+                resolve_target: resolve.then(|| resources.view(self.hdr)),
+                ops: weng::wgpu::Operations {
+                    load: if first {
+                        weng::wgpu::LoadOp::Clear(weng::wgpu::Color::BLACK)
+                    } else {
+                        weng::wgpu::LoadOp::Load
+                    },
+                    // Only the resolved samples are needed after the last pass.
+                    store: !resolve,
+                },
+            })],
+            depth_stencil_attachment: Some(weng::wgpu::RenderPassDepthStencilAttachment {
+                view: resources.view(self.depth),
+                depth_ops: Some(weng::wgpu::Operations {
+                    load: if first {
+                        weng::wgpu::LoadOp::Clear(1.0)
+                    } else {
+                        weng::wgpu::LoadOp::Load
+                    },
+                    store: scene_pass != ScenePass::Last,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    /// What scene passes draw into, multisampled with MSAA.
+    pub fn color(&self) -> graph::ResourceId {
+        self.color
+    }
+
+    pub fn depth(&self) -> graph::ResourceId {
+        self.depth
+    }
+
+    /// Written by the last scene pass.
+    pub fn hdr(&self) -> graph::ResourceId {
+        self.hdr
+    }
+
+    pub fn new(
+        graph: &mut graph::Graph,
+        hdr: graph::ResourceId,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Self {
+        let desc = |format| graph::TargetDesc {
+            format,
+            height,
+            sample_count,
+            width,
+        };
+
+        Self {
+            color: if sample_count > 1 {
+                graph.create(desc(HDR_FORMAT))
+            } else {
+                hdr
+            },
+            depth: graph.create(desc(DEPTH_FORMAT)),
+            hdr,
+        }
+    }
+}
+
+/// Runs a future to completion on this thread, which is all the futures of
+/// native wgpu backends need.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    fn noop_raw_waker() -> std::task::RawWaker {
+        fn clone(_: *const ()) -> std::task::RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: std::task::RawWakerVTable =
+            std::task::RawWakerVTable::new(clone, noop, noop, noop);
+
+        std::task::RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    // SAFETY: the vtable functions do nothing with the data pointer.
+    let waker = unsafe { std::task::Waker::from_raw(noop_raw_waker()) };
+    let mut context = std::task::Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+pub fn create_buffer<T: bytemuck::Pod>(
+    graphics: &weng::graphics::Context,
+    contents: &[T],
+    usage: weng::wgpu::BufferUsages,
+) -> weng::wgpu::Buffer {
+    graphics
+        .device()
+        .create_buffer_init(&weng::wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(contents),
+            usage,
+        })
+}
+
+pub fn create_material_bind_group_layout(
+    graphics: &weng::graphics::Context,
+) -> weng::wgpu::BindGroupLayout {
+    let texture = |binding| weng::wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+        ty: weng::wgpu::BindingType::Texture {
+            sample_type: weng::wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: weng::wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    let sampler = |binding| weng::wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+        ty: weng::wgpu::BindingType::Sampler(weng::wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
+
+    graphics
+        .device()
+        .create_bind_group_layout(&weng::wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                texture(0),
+                sampler(1),
+                texture(2),
+                sampler(3),
+                weng::wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: weng::wgpu::ShaderStages::FRAGMENT,
+                    ty: weng::wgpu::BindingType::Buffer {
+                        ty: weng::wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture(5),
+                sampler(6),
+                texture(7),
+                sampler(8),
+                texture(9),
+                sampler(10),
+            ],
+        })
+}
+
+/// A pipeline for instanced meshes, drawn into the HDR target with depth
+/// testing. Blended meshes are tested against the depth of everything opaque
+/// but leave it as is. Lines pass the test on the triangles they outline.
+fn create_mesh_pipeline<V, I>(
+    graphics: &weng::graphics::Context,
+    shader: &weng::wgpu::ShaderModule,
+    layout: &weng::wgpu::PipelineLayout,
+    sample_count: u32,
+    alpha_mode: AlphaMode,
+    entry_point: &str,
+    polygon_mode: weng::wgpu::PolygonMode,
+) -> weng::wgpu::RenderPipeline
+where
+    V: weng::graphics::vertices::Vertex,
+    I: weng::graphics::instances::Instance,
+{
+    let blended = alpha_mode == AlphaMode::Blended;
+
+    graphics
+        .device()
+        .create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: weng::wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    weng::wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<V>() as weng::wgpu::BufferAddress,
+                        step_mode: weng::wgpu::VertexStepMode::Vertex,
+                        attributes: V::ATTRIBUTES,
+                    },
+                    weng::wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<I>() as weng::wgpu::BufferAddress,
+                        step_mode: weng::wgpu::VertexStepMode::Instance,
+                        attributes: I::ATTRIBUTES,
+                    },
+                ],
+            },
+            primitive: weng::wgpu::PrimitiveState {
+                polygon_mode,
+                ..Default::default()
+            },
+            depth_stencil: Some(weng::wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: !blended,
+                depth_compare: if polygon_mode == weng::wgpu::PolygonMode::Line {
+                    weng::wgpu::CompareFunction::LessEqual
+                } else {
+                    weng::wgpu::CompareFunction::Less
+                },
+                stencil: weng::wgpu::StencilState::default(),
+                bias: weng::wgpu::DepthBiasState::default(),
+            }),
+            multisample: weng::wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            fragment: Some(weng::wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[Some(weng::wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: blended.then_some(weng::wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: weng::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+/// The largest MSAA sample count up to `requested` that both scene
+/// attachments support.
+pub fn supported_sample_count(graphics: &weng::graphics::Context, requested: u32) -> u32 {
+    // Beyond the guaranteed counts, support depends on the adapter, and the
+    // device only allows it with the matching feature enabled.
+    let adapter_specific = graphics
+        .device()
+        .features()
+        .contains(weng::wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    let flags = |format: weng::wgpu::TextureFormat| {
+        if adapter_specific {
+            graphics.adapter().get_texture_format_features(format).flags
+        } else {
+            format.describe().guaranteed_format_features.flags
+        }
+    };
+    let (color, depth) = (flags(HDR_FORMAT), flags(DEPTH_FORMAT));
+
+    let supported = [8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| color.sample_count_supported(count) && depth.sample_count_supported(count))
+        .unwrap_or(1);
+
+    if supported != requested {
+        log::warn!("{requested}x MSAA is not supported, using {supported}x");
+    }
+
+    supported
+}
+
+/// Runs `build` inside a validation error scope, so that wgpu rejecting a
+/// shader or pipeline is returned here rather than aborting the game, and the
+/// caller can keep what it would have replaced.
+pub fn validated<T>(device: &weng::wgpu::Device, build: impl FnOnce() -> T) -> anyhow::Result<T> {
+    device.push_error_scope(weng::wgpu::ErrorFilter::Validation);
+
+    let built = build();
+
+    match block_on(device.pop_error_scope()) {
+        Some(error) => Err(anyhow::anyhow!("{error}")),
+        None => Ok(built),
+    }
+}
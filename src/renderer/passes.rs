@@ -0,0 +1,703 @@
+//! The passes of a frame, and the pipelines, uniforms and targets they use.
+//! The main loop hands over what it extracted from the world with
+//! [`Renderer::prepare`] and [`Renderer::prepare_overlay`], then records the
+//! frame with [`Renderer::render`].
+
+use super::{
+    batch::DrawList,
+    environment::{self, Environment},
+    graph::{Graph, TargetPool},
+    post::Post,
+    skybox::Skybox,
+    sprites::SpriteRenderer,
+    text::TextRenderer,
+    ui::UiRenderer,
+    validated, Frame, MeshPipelines, ScenePass, SceneTargets,
+};
+use crate::{
+    assets::{self, Assets, Handle},
+    camera::Camera,
+    canvas::{Canvas, Sprite},
+    data::{
+        self,
+        materials::AlphaMode,
+        shaders::basic::{CameraUniform, Instance, LightUniform},
+    },
+    settings::Settings,
+    states::game::Label,
+    systems::Extracted,
+    text::Fonts,
+    ui,
+};
+use std::path::Path;
+use weng_game::culling::{self, Culling};
+
+type Context = weng::graphics::Context;
+type Model = assets::Model<Context>;
+type Shader = Handle<weng::wgpu::ShaderModule>;
+
+/// The instances of a model that are culled on the GPU, with a [`Culling`]
+/// for each of its meshes.
+struct CulledModel {
+    cullings: Vec<Culling>,
+    instances: Vec<Instance>,
+    model: Handle<Model>,
+}
+
+impl CulledModel {
+    fn cull(
+        &self,
+        queue: &weng::wgpu::Queue,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        assets: &Assets<Context>,
+        view_projection: glam::Mat4,
+    ) {
+        for (mesh, culling) in assets.model(self.model).meshes.iter().zip(&self.cullings) {
+            culling.cull(
+                queue,
+                encoder,
+                view_projection,
+                mesh.buffers.bounding_sphere,
+                mesh.buffers.index_count,
+            );
+        }
+    }
+
+    /// Draws what the last cull kept, with the pipeline `pipeline` picks for
+    /// the alpha mode of each mesh.
+    fn draw<'a>(
+        &'a self,
+        pass: &mut weng::wgpu::RenderPass<'a>,
+        assets: &'a Assets<Context>,
+        pipeline: impl Fn(AlphaMode) -> &'a weng::wgpu::RenderPipeline,
+    ) {
+        for (mesh, culling) in assets.model(self.model).meshes.iter().zip(&self.cullings) {
+            let material = assets.material(mesh.material);
+
+            pass.set_pipeline(pipeline(material.properties.alpha_mode));
+            pass.set_bind_group(0, &material.bind_group, &[]);
+            pass.set_vertex_buffer(0, mesh.buffers.vertex_buffer.slice(..));
+            pass.set_index_buffer(
+                mesh.buffers.index_buffer.slice(..),
+                weng::wgpu::IndexFormat::Uint32,
+            );
+
+            culling.draw(pass);
+        }
+    }
+
+    fn new(
+        device: &weng::wgpu::Device,
+        shader: &weng::wgpu::ShaderModule,
+        assets: &Assets<Context>,
+        model: Handle<Model>,
+        instances: Vec<Instance>,
+    ) -> Self {
+        Self {
+            cullings: assets
+                .model(model)
+                .meshes
+                .iter()
+                .map(|_| Culling::new(device, shader, &instances))
+                .collect(),
+            instances,
+            model,
+        }
+    }
+}
+
+struct Shaders {
+    basic: Shader,
+    cull: Shader,
+    #[cfg(feature = "gizmos")]
+    gizmos: Shader,
+    ibl: Shader,
+    post: Shader,
+    skybox: Shader,
+    sprite: Shader,
+    text: Shader,
+    ui: Shader,
+}
+
+impl Shaders {
+    fn load(graphics: &Context, assets: &mut Assets<Context>) -> anyhow::Result<Self> {
+        let mut load =
+            |name: &str| assets.load_shader(graphics, &Path::new(data::shaders::DIR).join(name));
+
+        Ok(Self {
+            basic: load(data::shaders::basic::NAME)?,
+            cull: load(data::shaders::cull::NAME)?,
+            #[cfg(feature = "gizmos")]
+            gizmos: load(data::shaders::gizmos::NAME)?,
+            ibl: load(data::shaders::ibl::NAME)?,
+            post: load(data::shaders::post::NAME)?,
+            skybox: load(data::shaders::skybox::NAME)?,
+            sprite: load(data::shaders::sprite::NAME)?,
+            text: load(data::shaders::text::NAME)?,
+            ui: load(data::shaders::ui::NAME)?,
+        })
+    }
+}
+
+pub struct Renderer {
+    camera_bind_group: weng::wgpu::BindGroup,
+    camera_layout: weng::wgpu::BindGroupLayout,
+    camera_uniform_buffer: weng::graphics::uniforms::UniformBuffer<CameraUniform>,
+    /// The instances of [`Extracted::culled`], by model.
+    culled: Vec<CulledModel>,
+    draw_list: DrawList,
+    environment: Environment,
+    environment_layout: weng::wgpu::BindGroupLayout,
+    environment_source: data::environments::Source,
+    #[cfg(feature = "gizmos")]
+    gizmos: super::gizmos::GizmoRenderer,
+    /// Whether the adapter can run [`Culling`]. Without compute shaders or
+    /// indirect draws the culled instances are tested on the CPU instead.
+    gpu_culling: bool,
+    light_bind_group: weng::wgpu::BindGroup,
+    light_layout: weng::wgpu::BindGroupLayout,
+    light_uniform_buffer: weng::graphics::uniforms::UniformBuffer<LightUniform>,
+    mesh_pipelines: MeshPipelines,
+    post: Post,
+    sample_count: u32,
+    /// What the pipelines were built with.
+    settings: Settings,
+    shaders: Shaders,
+    skybox: Skybox,
+    sprites: SpriteRenderer,
+    targets: TargetPool,
+    text: TextRenderer,
+    ui: UiRenderer,
+    view_projection: glam::Mat4,
+}
+
+impl Renderer {
+    /// Loads a sprite for [`Canvas`] to draw.
+    pub fn load_sprite(&mut self, graphics: &Context, path: &Path) -> anyhow::Result<Sprite> {
+        self.sprites.load(graphics, path)
+    }
+
+    /// Builds everything the passes need.
+    pub fn new(
+        graphics: &Context,
+        assets: &mut Assets<Context>,
+        settings: &Settings,
+    ) -> anyhow::Result<Self> {
+        let shaders = Shaders::load(graphics, assets)?;
+
+        let camera_layout =
+            graphics.create_uniform_bind_group_layout(weng::wgpu::ShaderStages::VERTEX_FRAGMENT);
+        let light_layout =
+            graphics.create_uniform_bind_group_layout(weng::wgpu::ShaderStages::VERTEX_FRAGMENT);
+        let environment_layout = environment::create_bind_group_layout(graphics);
+
+        // Both are written by `prepare` before anything is drawn.
+        let camera_uniform_buffer = graphics.create_uniform_buffer(&[bytemuck::Zeroable::zeroed()]);
+        let light_uniform_buffer = graphics.create_uniform_buffer(&[bytemuck::Zeroable::zeroed()]);
+        let camera_bind_group =
+            graphics.create_uniform_bind_group(&camera_layout, &camera_uniform_buffer);
+        let light_bind_group =
+            graphics.create_uniform_bind_group(&light_layout, &light_uniform_buffer);
+
+        let sample_count = super::supported_sample_count(graphics, settings.antialiasing.samples);
+        let gpu_culling = graphics
+            .adapter()
+            .get_downlevel_capabilities()
+            .flags
+            .contains(
+                weng::wgpu::DownlevelFlags::COMPUTE_SHADERS
+                    | weng::wgpu::DownlevelFlags::INDIRECT_EXECUTION,
+            );
+
+        if !gpu_culling {
+            log::warn!("the adapter cannot cull on the GPU, culling on the CPU");
+        }
+
+        let environment_source =
+            data::environments::Source::find(Path::new(data::environments::DIR), "sky")?;
+        let environment = Environment::load(
+            graphics,
+            assets.shader(shaders.ibl),
+            &environment_source,
+            &environment_layout,
+        )?;
+
+        Ok(Self {
+            culled: Vec::new(),
+            draw_list: DrawList::new(graphics),
+            environment,
+            environment_source,
+            #[cfg(feature = "gizmos")]
+            gizmos: super::gizmos::GizmoRenderer::new(
+                graphics,
+                assets.shader(shaders.gizmos),
+                &camera_layout,
+            ),
+            gpu_culling,
+            mesh_pipelines: create_mesh_pipelines(
+                graphics,
+                assets,
+                shaders.basic,
+                &[&camera_layout, &light_layout, &environment_layout],
+                sample_count,
+                settings,
+            ),
+            post: Post::new(
+                graphics,
+                assets.shader(shaders.post),
+                graphics.surface_width(),
+                graphics.surface_height(),
+                settings,
+            ),
+            skybox: Skybox::new(
+                graphics,
+                assets.shader(shaders.skybox),
+                &environment_layout,
+                &camera_layout,
+                sample_count,
+            ),
+            sprites: SpriteRenderer::new(graphics, assets.shader(shaders.sprite)),
+            targets: TargetPool::new(),
+            text: TextRenderer::new(
+                graphics,
+                assets.shader(shaders.text),
+                settings.text.rasterization,
+            ),
+            ui: UiRenderer::new(graphics, assets.shader(shaders.ui)),
+            view_projection: glam::Mat4::IDENTITY,
+            camera_bind_group,
+            camera_layout,
+            camera_uniform_buffer,
+            environment_layout,
+            light_bind_group,
+            light_layout,
+            light_uniform_buffer,
+            sample_count,
+            settings: settings.clone(),
+            shaders,
+        })
+    }
+
+    /// Writes the uniforms, builds the draw list of the scene, and uploads
+    /// the instances culled on the GPU when they changed.
+    pub fn prepare(
+        &mut self,
+        graphics: &Context,
+        assets: &Assets<Context>,
+        extracted: &Extracted,
+        camera: &Camera,
+        light: &LightUniform,
+    ) {
+        self.camera_uniform_buffer
+            .set(graphics, &[camera_uniform(camera)]);
+        self.light_uniform_buffer.set(graphics, &[*light]);
+        self.view_projection = camera.build_matrix();
+
+        // Objects only show up once the background loader hands them over.
+        for &(model, instance) in &extracted.instances {
+            self.draw_list.push(assets, model, instance);
+        }
+
+        // Culled instances are drawn in the opaque pass, so blended models have
+        // to be sorted with the other blended draws instead.
+        let mut culled: Vec<(Handle<Model>, Vec<Instance>)> = Vec::new();
+        let planes = culling::frustum_planes(self.view_projection);
+
+        for &(model, instance) in &extracted.culled {
+            let meshes = &assets.model(model).meshes;
+            let blended = meshes.iter().any(|mesh| {
+                assets.material(mesh.material).properties.alpha_mode == AlphaMode::Blended
+            });
+
+            if !self.gpu_culling {
+                let matrix = glam::Mat4::from_cols_array(&instance.model);
+
+                if meshes.iter().any(|mesh| {
+                    culling::instance_visible(&planes, matrix, mesh.buffers.bounding_sphere)
+                }) {
+                    self.draw_list.push(assets, model, instance);
+                }
+            } else if blended {
+                self.draw_list.push(assets, model, instance);
+            } else if let Some((_, instances)) = culled.iter_mut().find(|(m, _)| *m == model) {
+                instances.push(instance);
+            } else {
+                culled.push((model, vec![instance]));
+            }
+        }
+
+        self.culled.retain(|previous| {
+            culled.iter().any(|(model, instances)| {
+                previous.model == *model
+                    && bytemuck::cast_slice::<_, u8>(&previous.instances)
+                        == bytemuck::cast_slice::<_, u8>(instances)
+                    && previous.cullings.len() == assets.model(*model).meshes.len()
+            })
+        });
+        for (model, instances) in culled {
+            if !self.culled.iter().any(|previous| previous.model == model) {
+                self.culled.push(CulledModel::new(
+                    graphics.device(),
+                    assets.shader(self.shaders.cull),
+                    assets,
+                    model,
+                    instances,
+                ));
+            }
+        }
+
+        self.draw_list.build(graphics, camera.position());
+    }
+
+    #[cfg(feature = "gizmos")]
+    pub fn prepare_gizmos(&mut self, graphics: &Context, gizmos: &crate::gizmos::Gizmos) {
+        self.gizmos.prepare(graphics, gizmos);
+    }
+
+    /// Uploads what is drawn over the scene: the sprites of `canvas`, the
+    /// `labels` and the debug UI.
+    pub fn prepare_overlay(
+        &mut self,
+        graphics: &Context,
+        canvas: &Canvas,
+        fonts: &Fonts,
+        labels: &[Label],
+        ui: &ui::Output,
+        framebuffer_size: glam::UVec2,
+    ) {
+        for label in labels {
+            self.text
+                .draw(fonts, &label.layout, label.position, label.color);
+        }
+
+        self.sprites.prepare(graphics, canvas, framebuffer_size);
+        self.text.prepare(graphics);
+        self.ui.prepare(
+            graphics,
+            &ui.textures_delta,
+            &ui.primitives,
+            ui.pixels_per_point,
+        );
+    }
+
+    /// Rebuilds whatever was built from `shader`, after
+    /// [`Assets::reload_shader`]. On failure the old pipelines are kept.
+    pub fn reload_shader(
+        &mut self,
+        graphics: &Context,
+        assets: &Assets<Context>,
+        shader: Shader,
+    ) -> anyhow::Result<()> {
+        let device = graphics.device();
+        let shaders = &self.shaders;
+
+        match shader {
+            handle if handle == shaders.basic => {
+                self.mesh_pipelines =
+                    validated(device, || self.create_mesh_pipelines(graphics, assets))?;
+            }
+            handle if handle == shaders.cull => {
+                self.culled = validated(device, || {
+                    self.culled
+                        .iter()
+                        .map(|culled| {
+                            CulledModel::new(
+                                device,
+                                assets.shader(shaders.cull),
+                                assets,
+                                culled.model,
+                                culled.instances.clone(),
+                            )
+                        })
+                        .collect()
+                })?;
+            }
+            #[cfg(feature = "gizmos")]
+            handle if handle == shaders.gizmos => {
+                self.gizmos = validated(device, || {
+                    super::gizmos::GizmoRenderer::new(
+                        graphics,
+                        assets.shader(shaders.gizmos),
+                        &self.camera_layout,
+                    )
+                })?;
+            }
+            handle if handle == shaders.ibl => {
+                self.environment = validated(device, || {
+                    Environment::load(
+                        graphics,
+                        assets.shader(shaders.ibl),
+                        &self.environment_source,
+                        &self.environment_layout,
+                    )
+                })??;
+            }
+            handle if handle == shaders.post => {
+                self.post = validated(device, || {
+                    Post::new(
+                        graphics,
+                        assets.shader(shaders.post),
+                        graphics.surface_width(),
+                        graphics.surface_height(),
+                        &self.settings,
+                    )
+                })?;
+            }
+            handle if handle == shaders.skybox => {
+                self.skybox = validated(device, || self.create_skybox(graphics, assets))?;
+            }
+            handle if handle == shaders.sprite => {
+                self.sprites
+                    .reload_shader(graphics, assets.shader(shaders.sprite))?;
+            }
+            handle if handle == shaders.text => {
+                self.text = validated(device, || {
+                    TextRenderer::new(
+                        graphics,
+                        assets.shader(shaders.text),
+                        self.settings.text.rasterization,
+                    )
+                })?;
+            }
+            handle if handle == shaders.ui => {
+                self.ui = validated(device, || {
+                    UiRenderer::new(graphics, assets.shader(shaders.ui))
+                })?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Records every pass of the frame into its encoder.
+    pub fn render(&mut self, graphics: &Context, assets: &Assets<Context>, frame: &mut Frame) {
+        let Self {
+            ref camera_bind_group,
+            ref culled,
+            ref draw_list,
+            ref environment,
+            #[cfg(feature = "gizmos")]
+            ref gizmos,
+            ref light_bind_group,
+            ref mesh_pipelines,
+            ref post,
+            sample_count,
+            ref skybox,
+            ref sprites,
+            ref mut targets,
+            ref text,
+            ref ui,
+            view_projection,
+            ..
+        } = *self;
+
+        let mut graph = Graph::new();
+
+        let hdr = graph.import(post.hdr_view());
+        let surface = graph.import(&frame.view);
+        let scene = SceneTargets::new(
+            &mut graph,
+            hdr,
+            graphics.surface_width(),
+            graphics.surface_height(),
+            sample_count,
+        );
+
+        // The visible culled instances and their indirect draws.
+        let visible = graph.buffer();
+
+        graph
+            .add_pass("cull")
+            .writes(visible)
+            .execute(|encoder, _| {
+                for culled in culled {
+                    culled.cull(graphics.queue(), encoder, assets, view_projection);
+                }
+            });
+
+        graph
+            .add_pass("opaque")
+            .reads(visible)
+            .writes(scene.color())
+            .writes(scene.depth())
+            .execute(|encoder, resources| {
+                let mut pass = scene.begin_pass(encoder, resources, ScenePass::First);
+
+                pass.set_bind_group(1, camera_bind_group, &[]);
+                pass.set_bind_group(2, light_bind_group, &[]);
+                pass.set_bind_group(3, &environment.bind_group, &[]);
+
+                draw_list.draw(
+                    &mut pass,
+                    assets,
+                    mesh_pipelines,
+                    &[AlphaMode::Opaque, AlphaMode::Cutout],
+                );
+
+                for culled in culled {
+                    culled.draw(&mut pass, assets, |alpha_mode| {
+                        mesh_pipelines.get(alpha_mode)
+                    });
+                }
+            });
+
+        graph
+            .add_pass("skybox")
+            .reads(scene.depth())
+            .writes(scene.color())
+            .execute(|encoder, resources| {
+                let mut pass = scene.begin_pass(encoder, resources, ScenePass::Middle);
+
+                skybox.draw(&mut pass, environment, camera_bind_group);
+            });
+
+        graph
+            .add_pass("transparent")
+            .reads(visible)
+            .reads(scene.depth())
+            .writes(scene.color())
+            .writes(scene.hdr())
+            .execute(|encoder, resources| {
+                let mut pass = scene.begin_pass(encoder, resources, ScenePass::Last);
+
+                pass.set_bind_group(1, camera_bind_group, &[]);
+                pass.set_bind_group(2, light_bind_group, &[]);
+                pass.set_bind_group(3, &environment.bind_group, &[]);
+
+                draw_list.draw(&mut pass, assets, mesh_pipelines, &[AlphaMode::Blended]);
+
+                if let Some(wireframe) = mesh_pipelines.wireframe() {
+                    draw_list.draw_all(&mut pass, assets, wireframe);
+
+                    for culled in culled {
+                        culled.draw(&mut pass, assets, |_| wireframe);
+                    }
+                }
+            });
+
+        graph
+            .add_pass("post")
+            .reads(hdr)
+            .writes(surface)
+            .execute(|encoder, resources| post.render(encoder, resources.view(surface)));
+
+        #[cfg(feature = "gizmos")]
+        graph
+            .add_pass("gizmos")
+            .writes(surface)
+            .execute(|encoder, resources| {
+                gizmos.render(encoder, resources.view(surface), camera_bind_group)
+            });
+
+        graph
+            .add_pass("sprites")
+            .writes(surface)
+            .execute(|encoder, resources| sprites.render(encoder, resources.view(surface)));
+
+        graph
+            .add_pass("text")
+            .writes(surface)
+            .execute(|encoder, resources| text.render(encoder, resources.view(surface)));
+
+        graph
+            .add_pass("ui")
+            .writes(surface)
+            .execute(|encoder, resources| ui.render(encoder, resources.view(surface)));
+
+        graph.execute(graphics, targets, &mut frame.encoder);
+    }
+
+    pub fn resize(&mut self, graphics: &Context, framebuffer_size: glam::UVec2) {
+        self.post
+            .resize(graphics, framebuffer_size.x, framebuffer_size.y);
+    }
+
+    /// Rebuilds the pipelines that `settings` changed.
+    pub fn set_settings(
+        &mut self,
+        graphics: &Context,
+        assets: &Assets<Context>,
+        settings: &Settings,
+    ) {
+        let sample_count = super::supported_sample_count(graphics, settings.antialiasing.samples);
+        let samples_changed = sample_count != self.sample_count;
+        // The sample count and the debug view are baked into the scene
+        // pipelines.
+        let rebuild = samples_changed || settings.debug != self.settings.debug;
+        // The atlas starts over, since its glyphs are rasterized either way.
+        let text_changed = settings.text != self.settings.text;
+
+        self.post.set_settings(graphics, settings);
+        self.sample_count = sample_count;
+        self.settings.clone_from(settings);
+
+        if rebuild {
+            self.mesh_pipelines = self.create_mesh_pipelines(graphics, assets);
+        }
+        if samples_changed {
+            self.skybox = self.create_skybox(graphics, assets);
+        }
+        if text_changed {
+            self.text = TextRenderer::new(
+                graphics,
+                assets.shader(self.shaders.text),
+                self.settings.text.rasterization,
+            );
+        }
+    }
+
+    fn create_mesh_pipelines(&self, graphics: &Context, assets: &Assets<Context>) -> MeshPipelines {
+        create_mesh_pipelines(
+            graphics,
+            assets,
+            self.shaders.basic,
+            &[
+                &self.camera_layout,
+                &self.light_layout,
+                &self.environment_layout,
+            ],
+            self.sample_count,
+            &self.settings,
+        )
+    }
+
+    fn create_skybox(&self, graphics: &Context, assets: &Assets<Context>) -> Skybox {
+        Skybox::new(
+            graphics,
+            assets.shader(self.shaders.skybox),
+            &self.environment_layout,
+            &self.camera_layout,
+            self.sample_count,
+        )
+    }
+}
+
+fn camera_uniform(camera: &Camera) -> CameraUniform {
+    CameraUniform {
+        pos: glam::Vec4::from((camera.position(), 0.0)).to_array(),
+        view_proj: camera.build_matrix().to_cols_array(),
+        sky_inv_view_proj: camera.build_sky_matrix().inverse().to_cols_array(),
+    }
+}
+
+/// With the material layout of `assets` first, then the camera, light and
+/// environment `layouts`.
+fn create_mesh_pipelines(
+    graphics: &Context,
+    assets: &Assets<Context>,
+    shader: Shader,
+    layouts: &[&weng::wgpu::BindGroupLayout; 3],
+    sample_count: u32,
+    settings: &Settings,
+) -> MeshPipelines {
+    let [camera, light, environment] = *layouts;
+
+    MeshPipelines::new::<data::models::Vertex, Instance>(
+        graphics,
+        assets.shader(shader),
+        &[assets.material_layout(), camera, light, environment],
+        sample_count,
+        &settings.debug,
+    )
+}
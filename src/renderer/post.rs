@@ -0,0 +1,447 @@
+use crate::{
+    data::shaders::post::Params,
+    settings::{Settings, Tonemapper, View},
+};
+
+/// Bloom levels below the half resolution first level.
+const BLOOM_LEVELS: u32 = 6;
+
+/// The HDR target the scene is drawn into, and the passes that turn it into
+/// the final image: bloom, exposure, tone mapping and FXAA.
+pub struct Post {
+    bloom_enabled: bool,
+    composite: weng::wgpu::RenderPipeline,
+    downsample: weng::wgpu::RenderPipeline,
+    fxaa: weng::wgpu::RenderPipeline,
+    fxaa_enabled: bool,
+    params: weng::wgpu::Buffer,
+    prefilter: weng::wgpu::RenderPipeline,
+    sampler: weng::wgpu::Sampler,
+    targets: Targets,
+    upsample: weng::wgpu::RenderPipeline,
+}
+
+/// Everything that depends on the size of the surface.
+struct Targets {
+    bloom_views: Vec<weng::wgpu::TextureView>,
+    composite: weng::wgpu::BindGroup,
+    downsample: Vec<weng::wgpu::BindGroup>,
+    fxaa: weng::wgpu::BindGroup,
+    hdr_view: weng::wgpu::TextureView,
+    /// Tone mapped, in the surface format, for FXAA to read.
+    ldr_view: weng::wgpu::TextureView,
+    prefilter: weng::wgpu::BindGroup,
+    upsample: Vec<weng::wgpu::BindGroup>,
+}
+
+impl Post {
+    pub fn hdr_view(&self) -> &weng::wgpu::TextureView {
+        &self.targets.hdr_view
+    }
+
+    pub fn new(
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+        width: u32,
+        height: u32,
+        settings: &Settings,
+    ) -> Self {
+        let additive = weng::wgpu::BlendComponent {
+            src_factor: weng::wgpu::BlendFactor::One,
+            dst_factor: weng::wgpu::BlendFactor::One,
+            operation: weng::wgpu::BlendOperation::Add,
+        };
+
+        let composite = create_pipeline(
+            graphics,
+            shader,
+            "fs_composite",
+            graphics.surface_format(),
+            None,
+        );
+        let downsample =
+            create_pipeline(graphics, shader, "fs_downsample", super::HDR_FORMAT, None);
+        let fxaa = create_pipeline(graphics, shader, "fs_fxaa", graphics.surface_format(), None);
+        let prefilter = create_pipeline(graphics, shader, "fs_prefilter", super::HDR_FORMAT, None);
+        let upsample = create_pipeline(
+            graphics,
+            shader,
+            "fs_upsample",
+            super::HDR_FORMAT,
+            Some(weng::wgpu::BlendState {
+                color: additive,
+                alpha: additive,
+            }),
+        );
+
+        let params = graphics
+            .device()
+            .create_buffer(&weng::wgpu::BufferDescriptor {
+                label: None,
+                size: std::mem::size_of::<Params>() as weng::wgpu::BufferAddress,
+                usage: weng::wgpu::BufferUsages::UNIFORM | weng::wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        let sampler = graphics
+            .device()
+            .create_sampler(&weng::wgpu::SamplerDescriptor {
+                address_mode_u: weng::wgpu::AddressMode::ClampToEdge,
+                address_mode_v: weng::wgpu::AddressMode::ClampToEdge,
+                mag_filter: weng::wgpu::FilterMode::Linear,
+                min_filter: weng::wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+        let targets = Targets::new(
+            graphics,
+            [&composite, &downsample, &fxaa, &prefilter, &upsample],
+            &params,
+            &sampler,
+            width,
+            height,
+        );
+
+        let mut post = Self {
+            bloom_enabled: settings.post.bloom.enabled,
+            composite,
+            downsample,
+            fxaa,
+            fxaa_enabled: settings.antialiasing.fxaa,
+            params,
+            prefilter,
+            sampler,
+            targets,
+            upsample,
+        };
+
+        post.set_settings(graphics, settings);
+
+        post
+    }
+
+    /// Records the post-processing passes, reading the HDR target and writing
+    /// `output`.
+    pub fn render(
+        &self,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        output: &weng::wgpu::TextureView,
+    ) {
+        let targets = &self.targets;
+
+        if self.bloom_enabled {
+            let levels = targets.bloom_views.len();
+
+            draw(
+                encoder,
+                &targets.bloom_views[0],
+                weng::wgpu::LoadOp::Clear(weng::wgpu::Color::BLACK),
+                &self.prefilter,
+                &targets.prefilter,
+            );
+
+            for level in 1..levels {
+                draw(
+                    encoder,
+                    &targets.bloom_views[level],
+                    weng::wgpu::LoadOp::Clear(weng::wgpu::Color::BLACK),
+                    &self.downsample,
+                    &targets.downsample[level - 1],
+                );
+            }
+
+            // Each level is blurred onto the next larger one, so the first
+            // level ends up with the sum of every level.
+            for level in (1..levels).rev() {
+                draw(
+                    encoder,
+                    &targets.bloom_views[level - 1],
+                    weng::wgpu::LoadOp::Load,
+                    &self.upsample,
+                    &targets.upsample[level - 1],
+                );
+            }
+        }
+
+        draw(
+            encoder,
+            if self.fxaa_enabled {
+                &targets.ldr_view
+            } else {
+                output
+            },
+            weng::wgpu::LoadOp::Clear(weng::wgpu::Color::BLACK),
+            &self.composite,
+            &targets.composite,
+        );
+
+        if self.fxaa_enabled {
+            draw(
+                encoder,
+                output,
+                weng::wgpu::LoadOp::Clear(weng::wgpu::Color::BLACK),
+                &self.fxaa,
+                &targets.fxaa,
+            );
+        }
+    }
+
+    pub fn resize(&mut self, graphics: &weng::graphics::Context, width: u32, height: u32) {
+        self.targets = Targets::new(
+            graphics,
+            [
+                &self.composite,
+                &self.downsample,
+                &self.fxaa,
+                &self.prefilter,
+                &self.upsample,
+            ],
+            &self.params,
+            &self.sampler,
+            width,
+            height,
+        );
+    }
+
+    pub fn set_settings(&mut self, graphics: &weng::graphics::Context, settings: &Settings) {
+        let (antialiasing, debug, settings) =
+            (&settings.antialiasing, &settings.debug, &settings.post);
+        // Debug views show their values as they are.
+        let lit = debug.view == View::Lit;
+
+        self.bloom_enabled = settings.bloom.enabled && lit;
+        self.fxaa_enabled = antialiasing.fxaa;
+
+        graphics.queue().write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&Params {
+                bloom_intensity: if self.bloom_enabled {
+                    settings.bloom.intensity
+                } else {
+                    0.0
+                },
+                bloom_threshold: settings.bloom.threshold,
+                bloom_knee: settings.bloom.knee,
+                exposure: if lit { settings.exposure } else { 0.0 },
+                tonemapper: if lit {
+                    settings.tonemapper
+                } else {
+                    Tonemapper::None
+                } as u32,
+                encode_srgb: !graphics.surface_format().describe().srgb as u32,
+                _padding: [0; 2],
+            }),
+        );
+    }
+}
+
+impl Targets {
+    fn new(
+        graphics: &weng::graphics::Context,
+        [composite, downsample, fxaa, prefilter, upsample]: [&weng::wgpu::RenderPipeline; 5],
+        params: &weng::wgpu::Buffer,
+        sampler: &weng::wgpu::Sampler,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let hdr_view = create_target(graphics, width, height, super::HDR_FORMAT, 1)
+            .create_view(&weng::wgpu::TextureViewDescriptor::default());
+        let ldr_view = create_target(graphics, width, height, graphics.surface_format(), 1)
+            .create_view(&weng::wgpu::TextureViewDescriptor::default());
+
+        let (bloom_width, bloom_height) = ((width / 2).max(1), (height / 2).max(1));
+        let bloom = create_target(
+            graphics,
+            bloom_width,
+            bloom_height,
+            super::HDR_FORMAT,
+            crate::data::textures::mip_level_count(bloom_width, bloom_height).min(BLOOM_LEVELS),
+        );
+        let bloom_views = (0..bloom.mip_level_count())
+            .map(|level| {
+                bloom.create_view(&weng::wgpu::TextureViewDescriptor {
+                    base_mip_level: level,
+                    mip_level_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = |pipeline: &weng::wgpu::RenderPipeline, entries: &[_]| {
+            graphics
+                .device()
+                .create_bind_group(&weng::wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &pipeline.get_bind_group_layout(0),
+                    entries,
+                })
+        };
+        // Downsampling and upsampling only read a single bloom level.
+        let level_bind_groups = |pipeline, levels: std::ops::Range<usize>| {
+            bloom_views[levels]
+                .iter()
+                .map(|view| {
+                    bind_group(
+                        pipeline,
+                        &[
+                            weng::wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: weng::wgpu::BindingResource::TextureView(view),
+                            },
+                            weng::wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: weng::wgpu::BindingResource::Sampler(sampler),
+                            },
+                        ],
+                    )
+                })
+                .collect()
+        };
+
+        Self {
+            composite: bind_group(
+                composite,
+                &[
+                    weng::wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: weng::wgpu::BindingResource::TextureView(&hdr_view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: weng::wgpu::BindingResource::Sampler(sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params.as_entire_binding(),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: weng::wgpu::BindingResource::TextureView(&bloom_views[0]),
+                    },
+                ],
+            ),
+            downsample: level_bind_groups(downsample, 0..bloom_views.len() - 1),
+            fxaa: bind_group(
+                fxaa,
+                &[
+                    weng::wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: weng::wgpu::BindingResource::TextureView(&ldr_view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: weng::wgpu::BindingResource::Sampler(sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params.as_entire_binding(),
+                    },
+                ],
+            ),
+            prefilter: bind_group(
+                prefilter,
+                &[
+                    weng::wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: weng::wgpu::BindingResource::TextureView(&hdr_view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: weng::wgpu::BindingResource::Sampler(sampler),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: params.as_entire_binding(),
+                    },
+                ],
+            ),
+            upsample: level_bind_groups(upsample, 1..bloom_views.len()),
+            bloom_views,
+            hdr_view,
+            ldr_view,
+        }
+    }
+}
+
+/// Every post-processing pipeline draws a full screen triangle and derives its
+/// layout from the bindings its fragment shader uses.
+fn create_pipeline(
+    graphics: &weng::graphics::Context,
+    shader: &weng::wgpu::ShaderModule,
+    entry_point: &str,
+    format: weng::wgpu::TextureFormat,
+    blend: Option<weng::wgpu::BlendState>,
+) -> weng::wgpu::RenderPipeline {
+    graphics
+        .device()
+        .create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            vertex: weng::wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: weng::wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: weng::wgpu::MultisampleState::default(),
+            fragment: Some(weng::wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[Some(weng::wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: weng::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+fn create_target(
+    graphics: &weng::graphics::Context,
+    width: u32,
+    height: u32,
+    format: weng::wgpu::TextureFormat,
+    mip_level_count: u32,
+) -> weng::wgpu::Texture {
+    graphics
+        .device()
+        .create_texture(&weng::wgpu::TextureDescriptor {
+            label: None,
+            size: weng::wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: weng::wgpu::TextureDimension::D2,
+            format,
+            usage: weng::wgpu::TextureUsages::RENDER_ATTACHMENT
+                | weng::wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+}
+
+fn draw(
+    encoder: &mut weng::wgpu::CommandEncoder,
+    target: &weng::wgpu::TextureView,
+    load: weng::wgpu::LoadOp<weng::wgpu::Color>,
+    pipeline: &weng::wgpu::RenderPipeline,
+    bind_group: &weng::wgpu::BindGroup,
+) {
+    let mut pass = encoder.begin_render_pass(&weng::wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(weng::wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: weng::wgpu::Operations { load, store: true },
+        })],
+        depth_stencil_attachment: None,
+    });
+
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
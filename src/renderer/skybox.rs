@@ -0,0 +1,73 @@
+use super::environment::Environment;
+
+/// Draws the environment behind everything else, rotating with the camera but
+/// never moving with it.
+pub struct Skybox {
+    pipeline: weng::wgpu::RenderPipeline,
+}
+
+impl Skybox {
+    /// Has to come after the opaque geometry of the pass, as it only fills the
+    /// pixels that are still at the far plane.
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut weng::wgpu::RenderPass<'a>,
+        environment: &'a Environment,
+        camera_bind_group: &'a weng::wgpu::BindGroup,
+    ) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &environment.bind_group, &[]);
+        pass.set_bind_group(1, camera_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn new(
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+        environment_layout: &weng::wgpu::BindGroupLayout,
+        camera_layout: &weng::wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> Self {
+        let layout =
+            graphics
+                .device()
+                .create_pipeline_layout(&weng::wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[environment_layout, camera_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let pipeline =
+            graphics
+                .device()
+                .create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(&layout),
+                    vertex: weng::wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    primitive: weng::wgpu::PrimitiveState::default(),
+                    depth_stencil: Some(weng::wgpu::DepthStencilState {
+                        format: super::DEPTH_FORMAT,
+                        depth_write_enabled: false,
+                        depth_compare: weng::wgpu::CompareFunction::LessEqual,
+                        stencil: weng::wgpu::StencilState::default(),
+                        bias: weng::wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: weng::wgpu::MultisampleState {
+                        count: sample_count,
+                        ..Default::default()
+                    },
+                    fragment: Some(weng::wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(super::HDR_FORMAT.into())],
+                    }),
+                    multiview: None,
+                });
+
+        Self { pipeline }
+    }
+}
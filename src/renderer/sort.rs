@@ -0,0 +1,46 @@
+/// Orders blended draws back to front, so every one is blended over what is
+/// behind it. Farther positions get smaller keys.
+pub fn back_to_front_key(camera_position: glam::Vec3, position: glam::Vec3) -> u32 {
+    // The bits of non-negative floats sort the same way as the floats.
+    !camera_position.distance_squared(position).to_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn sorting_by_key_orders_back_to_front() {
+        let camera = Vec3::new(0.0, 1.0, 0.0);
+        let mut positions = vec![
+            Vec3::new(0.0, 1.0, -2.0),
+            Vec3::new(10.0, 1.0, 0.0),
+            camera,
+            Vec3::new(0.0, -4.0, 0.0),
+        ];
+
+        positions.sort_by_key(|position| back_to_front_key(camera, *position));
+
+        assert_eq!(
+            positions,
+            [
+                Vec3::new(10.0, 1.0, 0.0),
+                Vec3::new(0.0, -4.0, 0.0),
+                Vec3::new(0.0, 1.0, -2.0),
+                camera,
+            ]
+        );
+    }
+
+    #[test]
+    fn equal_distances_get_equal_keys() {
+        let camera = Vec3::new(1.0, 2.0, 3.0);
+
+        assert_eq!(
+            back_to_front_key(camera, camera + Vec3::X),
+            back_to_front_key(camera, camera - Vec3::Z)
+        );
+        assert_eq!(back_to_front_key(camera, camera), u32::MAX);
+    }
+}
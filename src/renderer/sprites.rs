@@ -0,0 +1,278 @@
+use crate::{
+    canvas::{Canvas, Sprite, TextureId},
+    data::{
+        self,
+        shaders::sprite::{SpriteUniform, Vertex},
+    },
+};
+use std::path::Path;
+
+struct SpriteTexture {
+    bind_group: weng::wgpu::BindGroup,
+    /// Kept alive for the bind group.
+    _texture: data::textures::Texture,
+}
+
+/// Draws a [`Canvas`] over the final image with an orthographic projection of
+/// the framebuffer, one draw per batch.
+pub struct SpriteRenderer {
+    batches: Vec<crate::canvas::Batch>,
+    pipeline: weng::wgpu::RenderPipeline,
+    texture_layout: weng::wgpu::BindGroupLayout,
+    /// Indexed by [`TextureId`], starting with [`TextureId::WHITE`].
+    textures: Vec<SpriteTexture>,
+    uniform: weng::wgpu::Buffer,
+    uniform_bind_group: weng::wgpu::BindGroup,
+    uniform_layout: weng::wgpu::BindGroupLayout,
+    vertex_buffer: weng::wgpu::Buffer,
+}
+
+impl SpriteRenderer {
+    /// Loads an image with `data::textures::load`, as a sprite of the whole
+    /// of it.
+    pub fn load(
+        &mut self,
+        graphics: &weng::graphics::Context,
+        path: &Path,
+    ) -> anyhow::Result<Sprite> {
+        let texture = data::textures::load::<data::textures::Sprite>(graphics, path)?;
+
+        Ok(self.insert(graphics, texture))
+    }
+
+    pub fn new(graphics: &weng::graphics::Context, shader: &weng::wgpu::ShaderModule) -> Self {
+        let device = graphics.device();
+
+        let uniform_layout =
+            graphics.create_uniform_bind_group_layout(weng::wgpu::ShaderStages::VERTEX_FRAGMENT);
+        let texture_layout =
+            device.create_bind_group_layout(&weng::wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    weng::wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+                        ty: weng::wgpu::BindingType::Texture {
+                            sample_type: weng::wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: weng::wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    weng::wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+                        ty: weng::wgpu::BindingType::Sampler(
+                            weng::wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let pipeline = create_pipeline(graphics, shader, &uniform_layout, &texture_layout);
+
+        let uniform = device.create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<SpriteUniform>() as weng::wgpu::BufferAddress,
+            usage: weng::wgpu::BufferUsages::UNIFORM | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group = device.create_bind_group(&weng::wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &uniform_layout,
+            entries: &[weng::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+        });
+
+        let mut renderer = Self {
+            batches: Vec::new(),
+            pipeline,
+            texture_layout,
+            textures: Vec::new(),
+            uniform,
+            uniform_bind_group,
+            uniform_layout,
+            vertex_buffer: create_vertex_buffer(graphics, 0),
+        };
+
+        let white = data::textures::create(
+            graphics,
+            &data::textures::Image::Uncompressed(image::DynamicImage::ImageRgba8(
+                image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])),
+            )),
+            <data::textures::Sprite as data::textures::Type>::FORMAT,
+            <data::textures::Sprite as data::textures::Type>::SAMPLING,
+        )
+        .expect("an uncompressed image always converts");
+        renderer.insert(graphics, white);
+
+        renderer
+    }
+
+    /// Uploads the quads of `canvas`, which are drawn until the next call.
+    pub fn prepare(
+        &mut self,
+        graphics: &weng::graphics::Context,
+        canvas: &Canvas,
+        framebuffer_size: glam::UVec2,
+    ) {
+        let size = framebuffer_size.as_vec2();
+
+        graphics.queue().write_buffer(
+            &self.uniform,
+            0,
+            bytemuck::bytes_of(&SpriteUniform {
+                projection: glam::Mat4::orthographic_lh(0.0, size.x, size.y, 0.0, 0.0, 1.0)
+                    .to_cols_array(),
+                encode_srgb: !graphics.surface_format().describe().srgb as u32,
+                _padding: [0; 3],
+            }),
+        );
+
+        let bytes = bytemuck::cast_slice(canvas.vertices());
+
+        if bytes.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = create_vertex_buffer(graphics, bytes.len() as u64);
+        }
+        graphics.queue().write_buffer(&self.vertex_buffer, 0, bytes);
+
+        self.batches.clear();
+        self.batches.extend_from_slice(canvas.batches());
+    }
+
+    /// Rebuilds the pipeline from a changed shader, keeping the textures. On
+    /// failure the old pipeline is kept.
+    pub fn reload_shader(
+        &mut self,
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+    ) -> anyhow::Result<()> {
+        self.pipeline = super::validated(graphics.device(), || {
+            create_pipeline(graphics, shader, &self.uniform_layout, &self.texture_layout)
+        })?;
+
+        Ok(())
+    }
+
+    /// Draws over `output`, keeping what is already in it.
+    pub fn render(
+        &self,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        output: &weng::wgpu::TextureView,
+    ) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&weng::wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(weng::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: weng::wgpu::Operations {
+                    load: weng::wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        for batch in &self.batches {
+            pass.set_bind_group(1, &self.textures[batch.texture.0].bind_group, &[]);
+            pass.draw(batch.vertices.clone(), 0..1);
+        }
+    }
+
+    fn insert(
+        &mut self,
+        graphics: &weng::graphics::Context,
+        texture: data::textures::Texture,
+    ) -> Sprite {
+        let bind_group = graphics
+            .device()
+            .create_bind_group(&weng::wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.texture_layout,
+                entries: &[
+                    weng::wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: weng::wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    weng::wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: weng::wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+            });
+        let size = glam::UVec2::new(texture.texture.width(), texture.texture.height());
+
+        self.textures.push(SpriteTexture {
+            bind_group,
+            _texture: texture,
+        });
+
+        Sprite::whole(TextureId(self.textures.len() - 1), size)
+    }
+}
+
+fn create_pipeline(
+    graphics: &weng::graphics::Context,
+    shader: &weng::wgpu::ShaderModule,
+    uniform_layout: &weng::wgpu::BindGroupLayout,
+    texture_layout: &weng::wgpu::BindGroupLayout,
+) -> weng::wgpu::RenderPipeline {
+    let layout = graphics
+        .device()
+        .create_pipeline_layout(&weng::wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[uniform_layout, texture_layout],
+            push_constant_ranges: &[],
+        });
+
+    graphics
+        .device()
+        .create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: weng::wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[weng::wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as weng::wgpu::BufferAddress,
+                    step_mode: weng::wgpu::VertexStepMode::Vertex,
+                    attributes: <Vertex as weng::graphics::vertices::Vertex>::ATTRIBUTES,
+                }],
+            },
+            primitive: weng::wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: weng::wgpu::MultisampleState::default(),
+            fragment: Some(weng::wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(weng::wgpu::ColorTargetState {
+                    format: graphics.surface_format(),
+                    blend: Some(weng::wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: weng::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+}
+
+fn create_vertex_buffer(graphics: &weng::graphics::Context, size: u64) -> weng::wgpu::Buffer {
+    graphics
+        .device()
+        .create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: weng::wgpu::BufferUsages::VERTEX | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+}
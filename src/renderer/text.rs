@@ -0,0 +1,252 @@
+use crate::{
+    data::shaders::text::{TextUniform, Vertex},
+    text::{Atlas, Fonts, Layout, Rasterization},
+};
+
+const ATLAS_SIZE: u32 = 1024;
+
+/// Draws laid out text over the final image, as one batch of quads sampling
+/// a glyph [`Atlas`].
+pub struct TextRenderer {
+    atlas: Atlas,
+    atlas_texture: weng::wgpu::Texture,
+    bind_group: weng::wgpu::BindGroup,
+    pipeline: weng::wgpu::RenderPipeline,
+    uniform: weng::wgpu::Buffer,
+    vertex_buffer: weng::wgpu::Buffer,
+    vertex_count: u32,
+    vertices: Vec<Vertex>,
+}
+
+impl TextRenderer {
+    /// Queues `layout` with its top left corner at `position`, in framebuffer
+    /// pixels. `color` is linear, with straight alpha.
+    pub fn draw(
+        &mut self,
+        fonts: &Fonts,
+        layout: &Layout,
+        position: glam::Vec2,
+        color: glam::Vec4,
+    ) {
+        let atlas_size = self.atlas.size() as f32;
+        let color = color.to_array();
+
+        for glyph in &layout.glyphs {
+            let entry =
+                match self
+                    .atlas
+                    .glyph(fonts, layout.font, glyph.character, layout.text_size)
+                {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+
+            let scale = layout.text_size / entry.size;
+            let min = position + glyph.position + entry.offset * scale;
+            let max = min + entry.texels.as_vec2() * scale;
+            let uv_min = entry.origin.as_vec2() / atlas_size;
+            let uv_max = (entry.origin + entry.texels).as_vec2() / atlas_size;
+
+            let corner = |x: bool, y: bool| Vertex {
+                position: [if x { max.x } else { min.x }, if y { max.y } else { min.y }],
+                tex_coords: [
+                    if x { uv_max.x } else { uv_min.x },
+                    if y { uv_max.y } else { uv_min.y },
+                ],
+                color,
+            };
+
+            self.vertices.extend([
+                corner(false, false),
+                corner(false, true),
+                corner(true, false),
+                corner(true, false),
+                corner(false, true),
+                corner(true, true),
+            ]);
+        }
+    }
+
+    pub fn new(
+        graphics: &weng::graphics::Context,
+        shader: &weng::wgpu::ShaderModule,
+        rasterization: Rasterization,
+    ) -> Self {
+        let device = graphics.device();
+
+        let pipeline = device.create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: None,
+            vertex: weng::wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[weng::wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vertex>() as weng::wgpu::BufferAddress,
+                    step_mode: weng::wgpu::VertexStepMode::Vertex,
+                    attributes: <Vertex as weng::graphics::vertices::Vertex>::ATTRIBUTES,
+                }],
+            },
+            primitive: weng::wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: weng::wgpu::MultisampleState::default(),
+            fragment: Some(weng::wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(weng::wgpu::ColorTargetState {
+                    format: graphics.surface_format(),
+                    blend: Some(weng::wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: weng::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let atlas_texture = device.create_texture(&weng::wgpu::TextureDescriptor {
+            label: None,
+            size: weng::wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: weng::wgpu::TextureDimension::D2,
+            format: weng::wgpu::TextureFormat::R8Unorm,
+            usage: weng::wgpu::TextureUsages::TEXTURE_BINDING | weng::wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let atlas_view = atlas_texture.create_view(&weng::wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&weng::wgpu::SamplerDescriptor {
+            address_mode_u: weng::wgpu::AddressMode::ClampToEdge,
+            address_mode_v: weng::wgpu::AddressMode::ClampToEdge,
+            mag_filter: weng::wgpu::FilterMode::Linear,
+            min_filter: weng::wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform = device.create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<TextUniform>() as weng::wgpu::BufferAddress,
+            usage: weng::wgpu::BufferUsages::UNIFORM | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&weng::wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                weng::wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                weng::wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: weng::wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                weng::wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: weng::wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Self {
+            atlas: Atlas::new(ATLAS_SIZE, rasterization),
+            atlas_texture,
+            bind_group,
+            pipeline,
+            uniform,
+            vertex_buffer: create_vertex_buffer(graphics, 0),
+            vertex_count: 0,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Uploads the text queued since the last call, which is drawn until the
+    /// next one, along with any new glyphs.
+    pub fn prepare(&mut self, graphics: &weng::graphics::Context) {
+        if self.atlas.take_dirty() {
+            graphics.queue().write_texture(
+                weng::wgpu::ImageCopyTexture {
+                    texture: &self.atlas_texture,
+                    mip_level: 0,
+                    origin: weng::wgpu::Origin3d::ZERO,
+                    aspect: weng::wgpu::TextureAspect::All,
+                },
+                self.atlas.pixels(),
+                weng::wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.atlas.size()),
+                    rows_per_image: None,
+                },
+                weng::wgpu::Extent3d {
+                    width: self.atlas.size(),
+                    height: self.atlas.size(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        graphics.queue().write_buffer(
+            &self.uniform,
+            0,
+            bytemuck::bytes_of(&TextUniform {
+                size: [
+                    graphics.surface_width() as f32,
+                    graphics.surface_height() as f32,
+                ],
+                encode_srgb: !graphics.surface_format().describe().srgb as u32,
+                sdf: (self.atlas.rasterization() == Rasterization::Sdf) as u32,
+            }),
+        );
+
+        let bytes = bytemuck::cast_slice(&self.vertices);
+
+        if bytes.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = create_vertex_buffer(graphics, bytes.len() as u64);
+        }
+        graphics.queue().write_buffer(&self.vertex_buffer, 0, bytes);
+
+        self.vertex_count = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    /// Draws over `output`, keeping what is already in it.
+    pub fn render(
+        &self,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        output: &weng::wgpu::TextureView,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&weng::wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(weng::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: weng::wgpu::Operations {
+                    load: weng::wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+fn create_vertex_buffer(graphics: &weng::graphics::Context, size: u64) -> weng::wgpu::Buffer {
+    graphics
+        .device()
+        .create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: weng::wgpu::BufferUsages::VERTEX | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+}
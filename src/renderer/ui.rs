@@ -0,0 +1,393 @@
+use crate::data::shaders::ui::ScreenUniform;
+use std::collections::HashMap;
+
+/// The meshes of one UI frame that use the same texture and clip rectangle.
+struct Draw {
+    base_vertex: i32,
+    /// In framebuffer pixels: x, y, width and height.
+    clip: [u32; 4],
+    indices: std::ops::Range<u32>,
+    texture: egui::TextureId,
+}
+
+struct UiTexture {
+    bind_group: weng::wgpu::BindGroup,
+    texture: weng::wgpu::Texture,
+}
+
+/// Paints what egui tessellated over the final image.
+pub struct UiRenderer {
+    draws: Vec<Draw>,
+    freed: Vec<egui::TextureId>,
+    index_buffer: weng::wgpu::Buffer,
+    pipeline: weng::wgpu::RenderPipeline,
+    sampler: weng::wgpu::Sampler,
+    screen: weng::wgpu::Buffer,
+    screen_bind_group: weng::wgpu::BindGroup,
+    texture_layout: weng::wgpu::BindGroupLayout,
+    textures: HashMap<egui::TextureId, UiTexture>,
+    vertex_buffer: weng::wgpu::Buffer,
+}
+
+impl UiRenderer {
+    pub fn new(graphics: &weng::graphics::Context, shader: &weng::wgpu::ShaderModule) -> Self {
+        let device = graphics.device();
+
+        let screen_layout =
+            graphics.create_uniform_bind_group_layout(weng::wgpu::ShaderStages::VERTEX_FRAGMENT);
+        let texture_layout =
+            device.create_bind_group_layout(&weng::wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    weng::wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+                        ty: weng::wgpu::BindingType::Texture {
+                            sample_type: weng::wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: weng::wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    weng::wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: weng::wgpu::ShaderStages::FRAGMENT,
+                        ty: weng::wgpu::BindingType::Sampler(
+                            weng::wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+            });
+
+        let layout = device.create_pipeline_layout(&weng::wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&screen_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        // egui colors have premultiplied alpha.
+        let premultiplied = weng::wgpu::BlendComponent {
+            src_factor: weng::wgpu::BlendFactor::One,
+            dst_factor: weng::wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: weng::wgpu::BlendOperation::Add,
+        };
+
+        let pipeline = device.create_render_pipeline(&weng::wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: weng::wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[weng::wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<egui::epaint::Vertex>()
+                        as weng::wgpu::BufferAddress,
+                    step_mode: weng::wgpu::VertexStepMode::Vertex,
+                    attributes: &weng::wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Unorm8x4,
+                    ],
+                }],
+            },
+            primitive: weng::wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: weng::wgpu::MultisampleState::default(),
+            fragment: Some(weng::wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(weng::wgpu::ColorTargetState {
+                    format: graphics.surface_format(),
+                    blend: Some(weng::wgpu::BlendState {
+                        color: premultiplied,
+                        alpha: premultiplied,
+                    }),
+                    write_mask: weng::wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let screen = device.create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<ScreenUniform>() as weng::wgpu::BufferAddress,
+            usage: weng::wgpu::BufferUsages::UNIFORM | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group = device.create_bind_group(&weng::wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &screen_layout,
+            entries: &[weng::wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen.as_entire_binding(),
+            }],
+        });
+
+        let sampler = device.create_sampler(&weng::wgpu::SamplerDescriptor {
+            address_mode_u: weng::wgpu::AddressMode::ClampToEdge,
+            address_mode_v: weng::wgpu::AddressMode::ClampToEdge,
+            mag_filter: weng::wgpu::FilterMode::Linear,
+            min_filter: weng::wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            draws: Vec::new(),
+            freed: Vec::new(),
+            index_buffer: create_buffer(graphics, 0, weng::wgpu::BufferUsages::INDEX),
+            pipeline,
+            sampler,
+            screen,
+            screen_bind_group,
+            texture_layout,
+            textures: HashMap::new(),
+            vertex_buffer: create_buffer(graphics, 0, weng::wgpu::BufferUsages::VERTEX),
+        }
+    }
+
+    /// Updates the textures and uploads the meshes of a UI frame, which is
+    /// drawn until the next call.
+    pub fn prepare(
+        &mut self,
+        graphics: &weng::graphics::Context,
+        textures_delta: &egui::TexturesDelta,
+        primitives: &[egui::ClippedPrimitive],
+        pixels_per_point: f32,
+    ) {
+        // egui frees textures once the frame that last used them is drawn.
+        for id in self.freed.drain(..) {
+            self.textures.remove(&id);
+        }
+        self.freed.extend_from_slice(&textures_delta.free);
+
+        for (id, delta) in &textures_delta.set {
+            self.set_texture(graphics, *id, delta);
+        }
+
+        let (width, height) = (graphics.surface_width(), graphics.surface_height());
+
+        graphics.queue().write_buffer(
+            &self.screen,
+            0,
+            bytemuck::bytes_of(&ScreenUniform {
+                size: [
+                    width as f32 / pixels_per_point,
+                    height as f32 / pixels_per_point,
+                ],
+                encode_srgb: !graphics.surface_format().describe().srgb as u32,
+                _padding: 0,
+            }),
+        );
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        self.draws.clear();
+
+        for primitive in primitives {
+            // Paint callbacks are not supported.
+            let mesh = match &primitive.primitive {
+                egui::epaint::Primitive::Mesh(mesh) => mesh,
+                egui::epaint::Primitive::Callback(_) => continue,
+            };
+
+            let rect = primitive.clip_rect;
+            let min = (glam::Vec2::new(rect.min.x, rect.min.y) * pixels_per_point)
+                .round()
+                .clamp(
+                    glam::Vec2::ZERO,
+                    glam::Vec2::new(width as f32, height as f32),
+                );
+            let max = (glam::Vec2::new(rect.max.x, rect.max.y) * pixels_per_point)
+                .round()
+                .clamp(min, glam::Vec2::new(width as f32, height as f32));
+
+            if mesh.indices.is_empty() || min.x == max.x || min.y == max.y {
+                continue;
+            }
+
+            self.draws.push(Draw {
+                base_vertex: vertices.len() as i32,
+                clip: [
+                    min.x as u32,
+                    min.y as u32,
+                    (max.x - min.x) as u32,
+                    (max.y - min.y) as u32,
+                ],
+                indices: indices.len() as u32..(indices.len() + mesh.indices.len()) as u32,
+                texture: mesh.texture_id,
+            });
+
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        for (buffer, bytes, usage) in [
+            (
+                &mut self.vertex_buffer,
+                bytemuck::cast_slice(&vertices),
+                weng::wgpu::BufferUsages::VERTEX,
+            ),
+            (
+                &mut self.index_buffer,
+                bytemuck::cast_slice(&indices),
+                weng::wgpu::BufferUsages::INDEX,
+            ),
+        ] {
+            if bytes.len() as u64 > buffer.size() {
+                *buffer = create_buffer(graphics, bytes.len() as u64, usage);
+            }
+            graphics.queue().write_buffer(buffer, 0, bytes);
+        }
+    }
+
+    /// Draws over `output`, keeping what is already in it.
+    pub fn render(
+        &self,
+        encoder: &mut weng::wgpu::CommandEncoder,
+        output: &weng::wgpu::TextureView,
+    ) {
+        if self.draws.is_empty() {
+            return;
+        }
+
+        let mut pass = encoder.begin_render_pass(&weng::wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(weng::wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: weng::wgpu::Operations {
+                    load: weng::wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), weng::wgpu::IndexFormat::Uint32);
+
+        for draw in &self.draws {
+            let texture = match self.textures.get(&draw.texture) {
+                Some(texture) => texture,
+                None => continue,
+            };
+            let [x, y, width, height] = draw.clip;
+
+            pass.set_scissor_rect(x, y, width, height);
+            pass.set_bind_group(1, &texture.bind_group, &[]);
+            pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+        }
+    }
+
+    fn set_texture(
+        &mut self,
+        graphics: &weng::graphics::Context,
+        id: egui::TextureId,
+        delta: &egui::epaint::ImageDelta,
+    ) {
+        let [width, height] = delta.image.size();
+        let pixels = match &delta.image {
+            egui::ImageData::Color(image) => image.pixels.clone(),
+            egui::ImageData::Font(image) => image.srgba_pixels(None).collect(),
+        };
+
+        // Without a position, the delta is a whole new texture.
+        if delta.pos.is_none() {
+            let texture = graphics
+                .device()
+                .create_texture(&weng::wgpu::TextureDescriptor {
+                    label: None,
+                    size: weng::wgpu::Extent3d {
+                        width: width as u32,
+                        height: height as u32,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: weng::wgpu::TextureDimension::D2,
+                    format: weng::wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: weng::wgpu::TextureUsages::TEXTURE_BINDING
+                        | weng::wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+            let view = texture.create_view(&weng::wgpu::TextureViewDescriptor::default());
+            let bind_group =
+                graphics
+                    .device()
+                    .create_bind_group(&weng::wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout: &self.texture_layout,
+                        entries: &[
+                            weng::wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: weng::wgpu::BindingResource::TextureView(&view),
+                            },
+                            weng::wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: weng::wgpu::BindingResource::Sampler(&self.sampler),
+                            },
+                        ],
+                    });
+
+            self.textures.insert(
+                id,
+                UiTexture {
+                    bind_group,
+                    texture,
+                },
+            );
+        }
+
+        let texture = match self.textures.get(&id) {
+            Some(texture) => texture,
+            None => {
+                log::warn!("UI texture {id:?} updated before it was created");
+
+                return;
+            }
+        };
+        let [x, y] = delta.pos.unwrap_or([0, 0]);
+
+        graphics.queue().write_texture(
+            weng::wgpu::ImageCopyTexture {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: weng::wgpu::Origin3d {
+                    x: x as u32,
+                    y: y as u32,
+                    z: 0,
+                },
+                aspect: weng::wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&pixels),
+            weng::wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(width as u32 * 4),
+                rows_per_image: None,
+            },
+            weng::wgpu::Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+fn create_buffer(
+    graphics: &weng::graphics::Context,
+    size: u64,
+    usage: weng::wgpu::BufferUsages,
+) -> weng::wgpu::Buffer {
+    graphics
+        .device()
+        .create_buffer(&weng::wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: usage | weng::wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+}
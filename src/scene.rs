@@ -0,0 +1,179 @@
+//! The game objects the game starts with. New kinds of objects are spawned
+//! here with their components, and the systems take it from there.
+
+use crate::{
+    character::CharacterController,
+    components::{Camera, GpuCulled, Interpolated, Light, MeshRenderer, Transform, Velocity},
+    data,
+    ecs::World,
+    physics::{Collider, RigidBody, Shape},
+    systems::ActiveCamera,
+};
+use rand::Rng;
+use std::path::Path;
+
+/// Spawns the player, a spectator camera, the light, the first models, the cube field and the
+/// physics test.
+pub fn setup(world: &mut World, random: &mut impl Rng, camera: Camera) {
+    // On the floor of the physics test, facing the falling cubes.
+    let mut spectator = camera.clone();
+    let player = world
+        .spawn()
+        .with(camera)
+        .with(CharacterController::new())
+        .with(Transform::from_translation(glam::Vec3::new(0.0, -3.5, 6.0)))
+        .id();
+
+    world.insert_resource(ActiveCamera(player));
+
+    // Behind and above the player, for looking at its frustum.
+    spectator.set_position(glam::Vec3::new(0.0, 2.0, -6.0));
+    world.spawn().with(spectator);
+    world.spawn().with(Transform::IDENTITY).with(Light {
+        color: glam::Vec3::ONE,
+    });
+
+    spawn_model(
+        world,
+        random,
+        &Path::new(data::models::DIR).join("glass.obj"),
+    );
+    spawn_cube_field(world, random);
+    spawn_physics_test(world, random);
+}
+
+/// Twenty thousand cubes scattered far around the scene, which the GPU culls.
+pub fn spawn_cube_field(world: &mut World, random: &mut impl Rng) {
+    let cube_path = Path::new(data::models::DIR).join("cube.obj");
+
+    for _ in 0..20_000 {
+        world
+            .spawn()
+            .with(random_transform(
+                random,
+                glam::Vec3::new(400.0, 40.0, 400.0),
+            ))
+            .with(MeshRenderer::new(&cube_path))
+            .with(GpuCulled);
+    }
+}
+
+/// Twenty instances of the model at `path`, scattered around the origin and
+/// slowly turning.
+pub fn spawn_model(world: &mut World, random: &mut impl Rng, path: &Path) {
+    for _ in 0..20 {
+        let axis = glam::Vec3::new(
+            random.gen_range(-1.0..1.0),
+            random.gen_range(-1.0..1.0),
+            random.gen_range(-1.0..1.0),
+        )
+        .normalize_or_zero();
+
+        world
+            .spawn()
+            .with(random_transform(random, glam::Vec3::new(50.0, 10.0, 50.0)))
+            .with(Velocity {
+                angular: axis * random.gen_range(0.1..0.5),
+                linear: glam::Vec3::ZERO,
+            })
+            .with(Interpolated::default())
+            .with(MeshRenderer::new(path));
+    }
+}
+
+/// Cubes, balls and capsules falling onto a floor in front of the camera,
+/// where a kinematic bar sweeps them around. No model fits the balls and
+/// capsules, so only the collider gizmos show them.
+pub fn spawn_physics_test(world: &mut World, random: &mut impl Rng) {
+    let cube_path = Path::new(data::models::DIR).join("cube.obj");
+    // The cube model reaches 1 out from its center.
+    let floor = glam::Vec3::new(15.0, 0.5, 15.0);
+
+    world
+        .spawn()
+        .with(Transform {
+            scale: floor,
+            ..Transform::from_translation(glam::Vec3::new(0.0, -5.0, 20.0))
+        })
+        .with(MeshRenderer::new(&cube_path))
+        .with(RigidBody::fixed())
+        .with(Collider::new(Shape::Box {
+            half_extents: floor,
+        }));
+
+    // A bar turning on the floor, which sweeps the bodies around and
+    // sometimes off the edge.
+    let sweeper = glam::Vec3::new(6.0, 0.5, 0.25);
+
+    world
+        .spawn()
+        .with(Transform {
+            scale: sweeper,
+            ..Transform::from_translation(glam::Vec3::new(0.0, -4.0, 20.0))
+        })
+        .with(Velocity {
+            angular: glam::Vec3::Y * 0.5,
+            linear: glam::Vec3::ZERO,
+        })
+        .with(Interpolated::default())
+        .with(MeshRenderer::new(&cube_path))
+        .with(RigidBody::kinematic())
+        .with(Collider::new(Shape::Box {
+            half_extents: sweeper,
+        }));
+
+    for index in 0..100 {
+        let translation = glam::Vec3::new(
+            random.gen_range(-8.0..8.0),
+            index as f32 * 0.75,
+            20.0 + random.gen_range(-8.0..8.0),
+        );
+        let transform = Transform {
+            rotation: random.gen(),
+            ..Transform::from_translation(translation)
+        };
+        let shape = match index % 10 {
+            0 => Shape::Sphere { radius: 0.5 },
+            1 => Shape::Capsule {
+                half_height: 0.5,
+                radius: 0.3,
+            },
+            _ => Shape::Box {
+                half_extents: glam::Vec3::splat(0.5),
+            },
+        };
+
+        let builder = world
+            .spawn()
+            .with(RigidBody::dynamic())
+            .with(Collider::new(shape))
+            .with(Interpolated::default());
+
+        match shape {
+            Shape::Box { half_extents } => {
+                builder
+                    .with(Transform {
+                        scale: half_extents,
+                        ..transform
+                    })
+                    .with(MeshRenderer::new(&cube_path));
+            }
+            _ => {
+                builder.with(transform);
+            }
+        }
+    }
+}
+
+/// Somewhere within `extent` of the origin on every axis.
+pub fn random_transform(random: &mut impl Rng, extent: glam::Vec3) -> Transform {
+    Transform {
+        rotation: random.gen(),
+        scale: glam::Vec3::splat(random.gen_range(0.25..4.0)),
+        translation: glam::Vec3::new(
+            random.gen_range(-extent.x..extent.x),
+            random.gen_range(-extent.y..extent.y),
+            random.gen_range(-extent.z..extent.z),
+        ),
+    }
+}
@@ -0,0 +1,136 @@
+use crate::text::Rasterization;
+use anyhow::Context;
+use std::path::Path;
+
+pub static PATH: &str = "settings.toml";
+
+/// User settings, read from [`PATH`]. Missing keys, or a missing file, fall
+/// back to the defaults.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub antialiasing: Antialiasing,
+    pub debug: Debug,
+    pub post: Post,
+    pub text: Text,
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => {
+                toml::from_str(&source).with_context(|| format!("invalid {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Antialiasing {
+    /// Smooths edges after tone mapping, cheaper than MSAA but blurrier.
+    pub fxaa: bool,
+    /// MSAA samples per pixel, 1 to disable it. Lowered to what the adapter
+    /// supports.
+    pub samples: u32,
+}
+
+impl Default for Antialiasing {
+    fn default() -> Self {
+        Self {
+            fxaa: false,
+            samples: 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bloom {
+    pub enabled: bool,
+    pub intensity: f32,
+    /// Width of the soft transition around the threshold.
+    pub knee: f32,
+    /// Brightness above which pixels bloom, before exposure is applied.
+    pub threshold: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 0.1,
+            knee: 0.5,
+            threshold: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Debug {
+    /// Draws the normal, tangent and bitangent of every vertex with the
+    /// gizmos, so only in builds that have them.
+    pub vectors: bool,
+    pub view: View,
+    /// Draws the edges of every triangle over the scene, when the adapter
+    /// supports it.
+    pub wireframe: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Post {
+    pub bloom: Bloom,
+    /// In stops, so every step of 1 doubles the brightness.
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+}
+
+impl Default for Post {
+    fn default() -> Self {
+        Self {
+            bloom: Bloom::default(),
+            exposure: 0.0,
+            tonemapper: Tonemapper::Aces,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Text {
+    pub rasterization: Rasterization,
+}
+
+/// The values match the constants of `post.wgsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u32)]
+pub enum Tonemapper {
+    Aces = 0,
+    Reinhard = 1,
+    /// Clamps to the displayable range, mostly for debug views.
+    None = 2,
+}
+
+/// What meshes show instead of their lit color. Anything but [`View::Lit`]
+/// skips bloom, exposure and tone mapping, so the values come out unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, strum::EnumIter)]
+#[serde(rename_all = "snake_case")]
+pub enum View {
+    #[default]
+    Lit,
+    /// The diffuse texture, unlit.
+    Diffuse,
+    /// The distance to the camera, white at the far plane.
+    Depth,
+    /// The normal map as sampled, in tangent space.
+    NormalMap,
+    /// The texture coordinates, wrapped to 0 to 1.
+    Uvs,
+    /// The normals used for lighting, after normal mapping.
+    WorldNormals,
+}
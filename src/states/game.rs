@@ -0,0 +1,425 @@
+//! The states of the game, drawing their menus onto the HUD.
+
+use super::{State, Transition};
+use crate::{
+    assets::Progress,
+    camera,
+    canvas::{Canvas, SpriteSheet},
+    input::Actions,
+    settings::{Settings, Tonemapper},
+    text::{self, FontId, Fonts, Layout},
+};
+
+const DIM: glam::Vec4 = glam::Vec4::new(0.0, 0.0, 0.0, 0.6);
+const ITEM_COLOR: glam::Vec4 = glam::Vec4::new(0.8, 0.8, 0.8, 1.0);
+const ITEM_SIZE: f32 = 28.0;
+const SELECTED_COLOR: glam::Vec4 = glam::Vec4::new(1.0, 0.8, 0.2, 1.0);
+const TITLE_SIZE: f32 = 48.0;
+
+/// What the states read and write, shared with the main loop.
+pub struct Context {
+    /// One icon per [`camera::Type`], in the order of its values.
+    pub camera_icons: SpriteSheet,
+    pub camera_type: camera::Type,
+    pub canvas: Canvas,
+    pub font: FontId,
+    pub fonts: Fonts,
+    pub framebuffer_size: glam::UVec2,
+    /// Text to draw this frame, after the canvas.
+    pub labels: Vec<Label>,
+    pub loading: Progress,
+    /// A copy of the settings, which the main loop applies when a state
+    /// changes it.
+    pub settings: Settings,
+    /// Whether the simulation ticks this frame. Cleared before every update,
+    /// so it only stays set while a state that runs the game is on top.
+    pub simulate: bool,
+}
+
+impl Context {
+    /// Queues `text` centered on `center`.
+    pub fn label(&mut self, text: &str, size: f32, center: glam::Vec2, color: glam::Vec4) {
+        let layout = text::layout(
+            &self.fonts,
+            text,
+            &text::Style {
+                align: text::Align::Center,
+                ..text::Style::new(self.font, size)
+            },
+        );
+
+        self.labels.push(Label {
+            color,
+            position: center - layout.size / 2.0,
+            layout,
+        });
+    }
+}
+
+pub struct Label {
+    pub color: glam::Vec4,
+    pub layout: Layout,
+    /// The top left corner, in framebuffer pixels.
+    pub position: glam::Vec2,
+}
+
+/// Waits for the assets requested at startup, then shows the main menu.
+pub struct Loading;
+
+impl State<Context> for Loading {
+    fn name(&self) -> &'static str {
+        "loading"
+    }
+
+    fn render(&mut self, context: &mut Context) {
+        let size = context.framebuffer_size.as_vec2();
+        let bar_size = glam::Vec2::new(size.x / 3.0, 8.0);
+
+        context
+            .canvas
+            .rect(glam::Vec2::ZERO, size, glam::Vec4::new(0.0, 0.0, 0.0, 1.0));
+        context.label(
+            "Loading",
+            ITEM_SIZE,
+            size / 2.0 - glam::Vec2::Y * ITEM_SIZE,
+            ITEM_COLOR,
+        );
+        context.canvas.bar(
+            (size - bar_size) / 2.0,
+            bar_size,
+            context.loading.fraction(),
+            SELECTED_COLOR,
+            glam::Vec4::new(0.2, 0.2, 0.2, 1.0),
+        );
+    }
+
+    fn update(&mut self, context: &mut Context, _actions: Actions) -> Transition<Context> {
+        if context.loading.done() {
+            Transition::Replace(Box::new(MainMenu::new()))
+        } else {
+            Transition::None
+        }
+    }
+}
+
+pub struct MainMenu {
+    menu: Menu,
+}
+
+impl MainMenu {
+    const ITEMS: [&'static str; 3] = ["Play", "Settings", "Quit"];
+
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::new(Self::ITEMS.len()),
+        }
+    }
+}
+
+impl State<Context> for MainMenu {
+    fn name(&self) -> &'static str {
+        "main menu"
+    }
+
+    fn render(&mut self, context: &mut Context) {
+        self.menu.render(context, "weng", &Self::ITEMS);
+    }
+
+    fn update(&mut self, _context: &mut Context, actions: Actions) -> Transition<Context> {
+        match self.menu.navigate(actions) {
+            Some(0) => Transition::Replace(Box::new(Playing)),
+            Some(1) => Transition::Push(Box::new(SettingsMenu::new())),
+            Some(_) => Transition::Quit,
+            None => Transition::None,
+        }
+    }
+}
+
+/// Runs the simulation, until paused.
+pub struct Playing;
+
+impl State<Context> for Playing {
+    fn captures_cursor(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> &'static str {
+        "playing"
+    }
+
+    fn render(&mut self, context: &mut Context) {
+        let icon_size = glam::Vec2::splat(32.0);
+
+        context.canvas.sprite(
+            &context.camera_icons.sprite(context.camera_type as u32),
+            glam::Vec2::new(16.0, context.framebuffer_size.y as f32 - icon_size.y - 16.0),
+            icon_size,
+            ITEM_COLOR,
+        );
+
+        // Only the FPS camera aims from the center of the screen.
+        if matches!(context.camera_type, camera::Type::Fps) {
+            context.canvas.crosshair(
+                context.framebuffer_size.as_vec2() / 2.0,
+                8.0,
+                4.0,
+                2.0,
+                glam::Vec4::ONE,
+            );
+        }
+    }
+
+    fn update(&mut self, context: &mut Context, actions: Actions) -> Transition<Context> {
+        if actions.contains(Actions::BACK) {
+            return Transition::Push(Box::new(Paused::new()));
+        }
+
+        context.simulate = true;
+
+        Transition::None
+    }
+}
+
+/// Shown over the frozen game.
+pub struct Paused {
+    menu: Menu,
+}
+
+impl Paused {
+    const ITEMS: [&'static str; 4] = ["Resume", "Settings", "Main menu", "Quit"];
+
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::new(Self::ITEMS.len()),
+        }
+    }
+}
+
+impl State<Context> for Paused {
+    fn name(&self) -> &'static str {
+        "paused"
+    }
+
+    fn overlay(&self) -> bool {
+        true
+    }
+
+    fn render(&mut self, context: &mut Context) {
+        self.menu.render(context, "Paused", &Self::ITEMS);
+    }
+
+    fn update(&mut self, _context: &mut Context, actions: Actions) -> Transition<Context> {
+        if actions.contains(Actions::BACK) {
+            return Transition::Pop;
+        }
+
+        match self.menu.navigate(actions) {
+            Some(0) => Transition::Pop,
+            Some(1) => Transition::Push(Box::new(SettingsMenu::new())),
+            Some(2) => Transition::Reset(Box::new(MainMenu::new())),
+            Some(_) => Transition::Quit,
+            None => Transition::None,
+        }
+    }
+}
+
+/// Toggles the post-processing settings, which apply right away.
+pub struct SettingsMenu {
+    menu: Menu,
+}
+
+impl SettingsMenu {
+    const LEN: usize = 4;
+
+    pub fn new() -> Self {
+        Self {
+            menu: Menu::new(Self::LEN),
+        }
+    }
+}
+
+impl State<Context> for SettingsMenu {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn render(&mut self, context: &mut Context) {
+        let on_off = |enabled| if enabled { "on" } else { "off" };
+        let items = [
+            format!("FXAA: {}", on_off(context.settings.antialiasing.fxaa)),
+            format!("Bloom: {}", on_off(context.settings.post.bloom.enabled)),
+            format!("Tone mapping: {:?}", context.settings.post.tonemapper),
+            "Back".to_owned(),
+        ];
+
+        self.menu.render(context, "Settings", &items);
+    }
+
+    fn update(&mut self, context: &mut Context, actions: Actions) -> Transition<Context> {
+        if actions.contains(Actions::BACK) {
+            return Transition::Pop;
+        }
+
+        let settings = &mut context.settings;
+
+        match self.menu.navigate(actions) {
+            Some(0) => settings.antialiasing.fxaa = !settings.antialiasing.fxaa,
+            Some(1) => settings.post.bloom.enabled = !settings.post.bloom.enabled,
+            Some(2) => {
+                settings.post.tonemapper = match settings.post.tonemapper {
+                    Tonemapper::Aces => Tonemapper::Reinhard,
+                    Tonemapper::Reinhard => Tonemapper::None,
+                    Tonemapper::None => Tonemapper::Aces,
+                }
+            }
+            Some(_) => return Transition::Pop,
+            None => (),
+        }
+
+        Transition::None
+    }
+}
+
+/// A vertical list of items, one of them selected.
+struct Menu {
+    len: usize,
+    selected: usize,
+}
+
+impl Menu {
+    fn new(len: usize) -> Self {
+        Self { len, selected: 0 }
+    }
+
+    /// Moves the selection, wrapping around, and returns the selected item
+    /// when it is confirmed.
+    fn navigate(&mut self, actions: Actions) -> Option<usize> {
+        if actions.contains(Actions::UP) {
+            self.selected = (self.selected + self.len - 1) % self.len;
+        }
+        if actions.contains(Actions::DOWN) {
+            self.selected = (self.selected + 1) % self.len;
+        }
+
+        actions.contains(Actions::CONFIRM).then_some(self.selected)
+    }
+
+    /// Dims whatever is below and lists `items` under `title`, in the middle
+    /// of the screen.
+    fn render<S: AsRef<str>>(&self, context: &mut Context, title: &str, items: &[S]) {
+        let size = context.framebuffer_size.as_vec2();
+        let line_height = ITEM_SIZE * 1.5;
+        let top = size.y / 2.0 - (TITLE_SIZE + items.len() as f32 * line_height) / 2.0;
+
+        context.canvas.rect(glam::Vec2::ZERO, size, DIM);
+        context.label(
+            title,
+            TITLE_SIZE,
+            glam::Vec2::new(size.x / 2.0, top),
+            glam::Vec4::ONE,
+        );
+
+        for (index, item) in items.iter().enumerate() {
+            let color = if index == self.selected {
+                SELECTED_COLOR
+            } else {
+                ITEM_COLOR
+            };
+            let y = top + TITLE_SIZE + (index as f32 + 0.5) * line_height;
+
+            context.label(
+                item.as_ref(),
+                ITEM_SIZE,
+                glam::Vec2::new(size.x / 2.0, y),
+                color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        canvas::{Sprite, TextureId},
+        states::StateStack,
+        time::Time,
+    };
+
+    fn context() -> Context {
+        let mut fonts = Fonts::new();
+        let font = fonts
+            .load(&std::path::Path::new(crate::data::fonts::DIR).join("DejaVuSans.ttf"))
+            .unwrap();
+
+        Context {
+            camera_icons: SpriteSheet {
+                cell: glam::UVec2::splat(16),
+                texture: Sprite::whole(TextureId::WHITE, glam::UVec2::new(32, 16)),
+            },
+            camera_type: camera::Type::Fps,
+            canvas: Canvas::new(),
+            font,
+            fonts,
+            framebuffer_size: glam::UVec2::new(800, 600),
+            labels: Vec::new(),
+            loading: Progress {
+                finished: 0,
+                requested: 0,
+            },
+            settings: Settings::default(),
+            simulate: false,
+        }
+    }
+
+    /// Updates like the main loop, which clears `simulate` first.
+    fn update(stack: &mut StateStack<Context>, context: &mut Context, actions: Actions) -> bool {
+        context.simulate = false;
+        stack.update(context, actions);
+
+        context.simulate
+    }
+
+    #[test]
+    fn pausing_freezes_the_simulation() {
+        let mut context = context();
+        let mut stack = StateStack::new(&mut context, Box::new(Playing));
+
+        assert!(update(&mut stack, &mut context, Actions::empty()));
+
+        // Pausing takes the frame the key is pressed on.
+        assert!(!update(&mut stack, &mut context, Actions::BACK));
+        assert_eq!(stack.names().collect::<Vec<_>>(), ["playing", "paused"]);
+        assert!(!stack.captures_cursor());
+        assert!(!update(&mut stack, &mut context, Actions::empty()));
+        assert!(!update(&mut stack, &mut context, Actions::DOWN));
+
+        assert!(!update(&mut stack, &mut context, Actions::BACK));
+        assert_eq!(stack.names().collect::<Vec<_>>(), ["playing"]);
+        assert!(stack.captures_cursor());
+        assert!(update(&mut stack, &mut context, Actions::empty()));
+    }
+
+    #[test]
+    fn paused_frames_run_no_ticks() {
+        let mut context = context();
+        let mut stack = StateStack::new(&mut context, Box::new(Playing));
+        let mut time = Time::new();
+        // Like the main loop, which drops the time owed to ticks when the
+        // states did not simulate.
+        let mut frame = |stack: &mut StateStack<Context>, actions| {
+            std::thread::sleep(Time::TICK_RATE);
+            time.begin_loop();
+            if !update(stack, &mut context, actions) {
+                time.skip_ticks();
+            }
+
+            time.should_update()
+        };
+
+        assert!(!frame(&mut stack, Actions::BACK));
+        assert!(!frame(&mut stack, Actions::empty()));
+        assert!(!frame(&mut stack, Actions::BACK));
+        assert!(frame(&mut stack, Actions::empty()));
+    }
+}
@@ -0,0 +1,269 @@
+//! A stack of game states. Only the state on top updates, so a state pushed
+//! over another, like the pause menu over the game, freezes the one below it
+//! while still letting it draw.
+//!
+//! The stack is generic over what the states work on, so transitions can be
+//! driven without a window or a GPU.
+
+pub mod game;
+
+use crate::input::Actions;
+
+pub trait State<C> {
+    /// Whether the cursor is captured while this state is on top.
+    fn captures_cursor(&self) -> bool {
+        false
+    }
+
+    /// Runs when the state is pushed.
+    fn enter(&mut self, _context: &mut C) {}
+
+    /// Runs when the state is popped, replaced or cleared.
+    fn exit(&mut self, _context: &mut C) {}
+
+    /// For logging transitions.
+    fn name(&self) -> &'static str;
+
+    /// Whether the states below are drawn before this one.
+    fn overlay(&self) -> bool {
+        false
+    }
+
+    /// Runs every frame the state is visible, from the bottom up.
+    fn render(&mut self, _context: &mut C) {}
+
+    /// Runs every frame the state is on top.
+    fn update(&mut self, context: &mut C, actions: Actions) -> Transition<C>;
+}
+
+pub enum Transition<C> {
+    None,
+    /// Covers the current state, which is kept but no longer updated.
+    Push(Box<dyn State<C>>),
+    /// Leaves the current state, uncovering the one below.
+    Pop,
+    Replace(Box<dyn State<C>>),
+    /// Leaves every state for a new one, like going back to the main menu.
+    Reset(Box<dyn State<C>>),
+    /// Leaves every state, which ends the game.
+    Quit,
+}
+
+pub struct StateStack<C> {
+    states: Vec<Box<dyn State<C>>>,
+}
+
+impl<C> StateStack<C> {
+    pub fn apply(&mut self, context: &mut C, transition: Transition<C>) {
+        match transition {
+            Transition::None => (),
+            Transition::Push(state) => self.push(context, state),
+            Transition::Pop => self.pop(context),
+            Transition::Replace(state) => {
+                self.pop(context);
+                self.push(context, state);
+            }
+            Transition::Reset(state) => {
+                self.clear(context);
+                self.push(context, state);
+            }
+            Transition::Quit => self.clear(context),
+        }
+    }
+
+    pub fn captures_cursor(&self) -> bool {
+        matches!(self.states.last(), Some(state) if state.captures_cursor())
+    }
+
+    /// Once every state has left, the game is over.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// From the bottom of the stack up.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.states.iter().map(|state| state.name())
+    }
+
+    pub fn new(context: &mut C, initial: Box<dyn State<C>>) -> Self {
+        let mut stack = Self { states: Vec::new() };
+
+        stack.push(context, initial);
+
+        stack
+    }
+
+    /// Draws the top state, over the states below it for as long as they are
+    /// overlays.
+    pub fn render(&mut self, context: &mut C) {
+        let bottom = self
+            .states
+            .iter()
+            .rposition(|state| !state.overlay())
+            .unwrap_or(0);
+
+        for state in &mut self.states[bottom..] {
+            state.render(context);
+        }
+    }
+
+    /// Updates the top state and applies the transition it returns.
+    pub fn update(&mut self, context: &mut C, actions: Actions) {
+        if let Some(state) = self.states.last_mut() {
+            let transition = state.update(context, actions);
+
+            self.apply(context, transition);
+        }
+    }
+
+    fn clear(&mut self, context: &mut C) {
+        while !self.states.is_empty() {
+            self.pop(context);
+        }
+    }
+
+    fn pop(&mut self, context: &mut C) {
+        if let Some(mut state) = self.states.pop() {
+            log::info!("leaving {}", state.name());
+
+            state.exit(context);
+        }
+    }
+
+    fn push(&mut self, context: &mut C, mut state: Box<dyn State<C>>) {
+        log::info!("entering {}", state.name());
+
+        state.enter(context);
+        self.states.push(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Logs what happens to it, and does whatever the test queued up next.
+    struct Recorder {
+        name: &'static str,
+        next: Option<Transition<Log>>,
+        overlay: bool,
+    }
+
+    #[derive(Default)]
+    struct Log {
+        events: Vec<String>,
+    }
+
+    impl Log {
+        fn take(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.events)
+        }
+    }
+
+    fn state(name: &'static str) -> Box<Recorder> {
+        Box::new(Recorder {
+            name,
+            next: None,
+            overlay: false,
+        })
+    }
+
+    fn then(name: &'static str, next: Transition<Log>) -> Box<Recorder> {
+        Box::new(Recorder {
+            next: Some(next),
+            ..*state(name)
+        })
+    }
+
+    impl State<Log> for Recorder {
+        fn enter(&mut self, log: &mut Log) {
+            log.events.push(format!("enter {}", self.name));
+        }
+
+        fn exit(&mut self, log: &mut Log) {
+            log.events.push(format!("exit {}", self.name));
+        }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn overlay(&self) -> bool {
+            self.overlay
+        }
+
+        fn render(&mut self, log: &mut Log) {
+            log.events.push(format!("render {}", self.name));
+        }
+
+        fn update(&mut self, log: &mut Log, _: Actions) -> Transition<Log> {
+            log.events.push(format!("update {}", self.name));
+
+            self.next.take().unwrap_or(Transition::None)
+        }
+    }
+
+    #[test]
+    fn transitions_enter_and_exit_in_order() {
+        let mut log = Log::default();
+        let mut stack = StateStack::new(&mut log, state("game"));
+
+        assert_eq!(log.take(), ["enter game"]);
+
+        stack.apply(&mut log, Transition::Push(state("pause")));
+        assert_eq!(log.take(), ["enter pause"]);
+        assert_eq!(stack.names().collect::<Vec<_>>(), ["game", "pause"]);
+
+        stack.apply(&mut log, Transition::Replace(state("settings")));
+        assert_eq!(log.take(), ["exit pause", "enter settings"]);
+
+        stack.apply(&mut log, Transition::Pop);
+        assert_eq!(log.take(), ["exit settings"]);
+        assert_eq!(stack.names().collect::<Vec<_>>(), ["game"]);
+
+        stack.apply(&mut log, Transition::Push(state("pause")));
+        stack.apply(&mut log, Transition::Reset(state("menu")));
+        assert_eq!(
+            log.take(),
+            ["enter pause", "exit pause", "exit game", "enter menu"]
+        );
+
+        stack.apply(&mut log, Transition::Quit);
+        assert_eq!(log.take(), ["exit menu"]);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn only_the_top_state_updates() {
+        let mut log = Log::default();
+        let mut stack = StateStack::new(&mut log, state("game"));
+
+        stack.apply(&mut log, Transition::Push(then("pause", Transition::Pop)));
+        log.take();
+
+        stack.update(&mut log, Actions::empty());
+        assert_eq!(log.take(), ["update pause", "exit pause"]);
+
+        stack.update(&mut log, Actions::empty());
+        assert_eq!(log.take(), ["update game"]);
+    }
+
+    #[test]
+    fn overlays_render_over_the_states_below() {
+        let mut log = Log::default();
+        let mut stack = StateStack::new(&mut log, state("menu"));
+
+        stack.apply(&mut log, Transition::Push(state("game")));
+        stack.apply(
+            &mut log,
+            Transition::Push(Box::new(Recorder {
+                overlay: true,
+                ..*state("pause")
+            })),
+        );
+        log.take();
+
+        stack.render(&mut log);
+        assert_eq!(log.take(), ["render game", "render pause"]);
+    }
+}
@@ -0,0 +1,678 @@
+//! The systems run by the [`Schedule`] of the main loop, and the resources
+//! they share through the world.
+
+use crate::{
+    assets::{self, Assets, Backend, Handle, Loader},
+    camera,
+    character::{self, CharacterController},
+    components::{Camera, GpuCulled, Interpolated, Light, MeshRenderer, Transform, Velocity},
+    data::shaders::basic::{Instance, LightUniform},
+    ecs::{Components, Entity, Schedule, World},
+    gizmos::{self, Gizmos},
+    input::Input,
+    physics::{self, Physics},
+    raycast::{self, Ray},
+    time::{Blend, Time},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use weng_game::{
+    bvh::{Aabb, Bvh},
+    culling,
+};
+
+type Model = assets::Model<weng::graphics::Context>;
+
+/// Bounds and vertex vectors are only drawn for the objects this close to
+/// the camera, since the far ones would bury it in lines.
+const GIZMO_RANGE: f32 = 50.0;
+/// The boxes the [`Bvh`] holds for those objects, margin included.
+const BVH_COLOR: glam::Vec4 = glam::Vec4::new(0.0, 1.0, 1.0, 0.5);
+
+/// The camera the frame is drawn from, which [`scene::setup`] picks.
+///
+/// [`scene::setup`]: crate::scene::setup
+pub struct ActiveCamera(pub Entity);
+
+/// What the renderer needs from the world, gathered once a frame by
+/// [`extract`].
+#[derive(Default)]
+pub struct Extracted {
+    /// The [`ActiveCamera`].
+    pub camera: Option<Camera>,
+    /// The instances of the [`GpuCulled`] renderers.
+    pub culled: Vec<(Handle<Model>, Instance)>,
+    /// The instances of the other renderers whose box in the [`Bvh`] is in
+    /// the frustum of the camera, or all of them without a camera.
+    pub instances: Vec<(Handle<Model>, Instance)>,
+    /// The first light.
+    pub light: Option<LightUniform>,
+}
+
+/// Keeps the [`Bvh`] in step with the entities that have a loaded model, for
+/// the scene queries.
+pub fn bounds(world: &mut World) {
+    let mut bvh = world.resource_mut::<Bvh<Entity>>();
+    let renderers = world.components::<MeshRenderer>();
+    let transforms = world.components::<Transform>();
+
+    let gone: Vec<_> = bvh
+        .keys()
+        .filter(|&entity| {
+            !matches!(
+                (renderers.get(entity), transforms.get(entity)),
+                (Some(renderer), Some(_)) if renderer.model.is_some()
+            )
+        })
+        .collect();
+    for entity in gone {
+        bvh.remove(entity);
+    }
+
+    for (entity, renderer) in renderers.iter() {
+        if let (Some(_), Some(transform)) = (renderer.model, transforms.get(entity)) {
+            bvh.insert(entity, renderer.bounds.transformed(transform.matrix()));
+        }
+    }
+}
+
+/// Every model that was asked for, and its handle once it loaded.
+pub struct Models<B: Backend = weng::graphics::Context> {
+    /// Models that failed to load since the last [`resolve_models`].
+    failed: Vec<PathBuf>,
+    handles: HashMap<PathBuf, Option<Handle<assets::Model<B>>>>,
+}
+
+impl<B: Backend> Default for Models<B> {
+    fn default() -> Self {
+        Self {
+            failed: Vec::new(),
+            handles: HashMap::new(),
+        }
+    }
+}
+
+impl<B: Backend> Models<B> {
+    /// Gives up on a model the [`Loader`] could not load. Its renderers are
+    /// removed, and spawning new ones asks for it again.
+    pub fn fail(&mut self, path: PathBuf) {
+        self.handles.remove(&path);
+        self.failed.push(path);
+    }
+
+    /// Whether `path` was asked for, and its handle if it loaded.
+    pub fn get(&self, path: &Path) -> Option<Option<Handle<assets::Model<B>>>> {
+        self.handles.get(path).copied()
+    }
+
+    /// Hands over a model the [`Loader`] finished.
+    pub fn insert(&mut self, path: PathBuf, model: Handle<assets::Model<B>>) {
+        self.handles.insert(path, Some(model));
+    }
+
+    /// Releases the loaded models that none of `renderers` draws anymore,
+    /// such as those of despawned entities, and unloads them. Drawing one
+    /// again asks the loader for it again.
+    pub fn release_unused(&mut self, assets: &mut Assets<B>, renderers: &Components<MeshRenderer>) {
+        let used: HashSet<&Path> = renderers
+            .iter()
+            .map(|(_, renderer)| renderer.path.as_path())
+            .collect();
+        let mut released = false;
+
+        self.handles.retain(|path, handle| match *handle {
+            Some(handle) if !used.contains(path.as_path()) => {
+                assets.release_model(handle);
+                released = true;
+
+                false
+            }
+            _ => true,
+        });
+
+        if released {
+            assets.unload_unused();
+        }
+    }
+
+    /// Has the loader decode `path`, unless it was asked for already.
+    pub fn request(&mut self, loader: &mut Loader, path: &Path) {
+        if !self.handles.contains_key(path) {
+            loader.load_model(path);
+            self.handles.insert(path.to_path_buf(), None);
+        }
+    }
+}
+
+/// Copies the drawn state of the world into [`Extracted`], with the
+/// [`Interpolated`] entities as far between their last two ticks as the
+/// [`Blend`] says.
+pub fn extract(world: &mut World) {
+    let mut extracted = world.resource_mut::<Extracted>();
+    let blend = world.resource::<Blend>().0;
+    let culled = world.components::<GpuCulled>();
+    let interpolated = world.components::<Interpolated>();
+    let transforms = world.components::<Transform>();
+
+    extracted.camera = world
+        .components::<Camera>()
+        .get(world.resource::<ActiveCamera>().0)
+        .cloned();
+
+    // By their box in the tree, while the GPU culls the crowds mesh by mesh.
+    let visible = extracted.camera.as_ref().map(|camera| {
+        let mut visible = HashSet::new();
+
+        world.resource::<Bvh<Entity>>().query_frustum(
+            &culling::frustum_planes(camera.build_matrix()),
+            |entity| {
+                visible.insert(entity);
+            },
+        );
+
+        visible
+    });
+
+    extracted.culled.clear();
+    extracted.instances.clear();
+    for (entity, renderer) in world.components::<MeshRenderer>().iter() {
+        if let (Some(model), Some(transform)) = (renderer.model, transforms.get(entity)) {
+            let instances = if culled.contains(entity) {
+                &mut extracted.culled
+            } else if matches!(&visible, Some(visible) if !visible.contains(&entity)) {
+                continue;
+            } else {
+                &mut extracted.instances
+            };
+
+            let instance = match interpolated
+                .get(entity)
+                .and_then(|interpolated| interpolated.previous)
+            {
+                Some(previous) => previous.lerp(transform, blend).instance(),
+                None => transform.instance(),
+            };
+
+            instances.push((model, instance));
+        }
+    }
+
+    extracted.light = world
+        .components::<Light>()
+        .iter()
+        .find_map(|(entity, light)| {
+            transforms.get(entity).map(|transform| LightUniform {
+                position: transform.translation.to_array(),
+                _padding: 0,
+                color: light.color.to_array(),
+                _padding2: 0,
+            })
+        });
+}
+
+/// Draws the light, the world axes, the bounds of the objects, the colliders,
+/// the characters, the frustums of the other cameras, and where what the
+/// player aims at is hit, while the gizmos are on.
+pub fn gizmos(world: &mut World) {
+    let mut gizmos = world.resource_mut::<Gizmos>();
+
+    if world.resource::<Input>().toggle_gizmos() {
+        let enabled = gizmos.enabled();
+
+        gizmos.set_enabled(!enabled);
+    }
+    if !gizmos.enabled() {
+        return;
+    }
+
+    let active = world.resource::<ActiveCamera>().0;
+    let camera = match world.components::<Camera>().get(active) {
+        Some(camera) => camera.clone(),
+        None => return,
+    };
+    let assets = world.resource::<Assets<weng::graphics::Context>>();
+
+    gizmos.begin(&camera);
+    gizmos.grid(
+        glam::Vec3::ZERO,
+        20,
+        5.0,
+        glam::Vec4::new(1.0, 1.0, 1.0, 0.25),
+    );
+    gizmos.axes(glam::Mat4::IDENTITY, 5.0);
+
+    let transforms = world.components::<Transform>();
+
+    for (entity, _) in world.components::<Light>().iter() {
+        if let Some(transform) = transforms.get(entity) {
+            gizmos.sphere(transform.translation, 0.5, gizmos::YELLOW);
+            gizmos.text(
+                transform.translation + glam::Vec3::Y,
+                "light",
+                gizmos::YELLOW,
+            );
+        }
+    }
+
+    // What the other cameras see, to check the culling from outside.
+    for (entity, other) in world.components::<Camera>().iter() {
+        if entity != active {
+            gizmos.frustum(other.build_matrix(), gizmos::WHITE);
+        }
+    }
+
+    let renderers = world.components::<MeshRenderer>();
+    let bvh = world.resource::<Bvh<Entity>>();
+
+    bvh.query_sphere(camera.position(), GIZMO_RANGE, |entity| {
+        if let (Some(model), Some(transform)) = (
+            renderers.get(entity).and_then(|renderer| renderer.model),
+            transforms.get(entity),
+        ) {
+            mesh_gizmos(&mut gizmos, &assets, model, transform);
+        }
+        if let Some(aabb) = bvh.aabb(entity) {
+            gizmos.aabb(aabb.min, aabb.max, BVH_COLOR);
+        }
+    });
+
+    physics::draw_gizmos(world, &mut gizmos);
+    character::draw_gizmos(world, &mut gizmos);
+
+    let ray = Ray::from_input(&camera, &world.resource::<Input>());
+
+    if let Some(hit) = raycast::cast(world, &assets, &ray, 100.0) {
+        gizmos.sphere(hit.point, 0.1, gizmos::RED);
+        gizmos.line(hit.point, hit.point + hit.normal, gizmos::RED);
+
+        // What the box of the aimed object overlaps.
+        if let Some(aabb) = bvh.aabb(hit.entity) {
+            bvh.query_aabb(&aabb, |entity| match bvh.aabb(entity) {
+                Some(other) if entity != hit.entity => {
+                    gizmos.aabb(other.min, other.max, gizmos::RED)
+                }
+                _ => (),
+            });
+        }
+    }
+}
+
+/// Switches to the next camera on request, then turns the active camera with
+/// the input of the tick and moves it unless a character carries it.
+pub fn input(world: &mut World) {
+    let mut input = world.resource_mut::<Input>();
+    let mut active = world.resource_mut::<ActiveCamera>();
+    let characters = world.components::<CharacterController>();
+    let mut cameras = world.components_mut::<Camera>();
+
+    input.tick();
+
+    if input.switch_camera() {
+        let entities = cameras.iter().map(|(entity, _)| entity).collect::<Vec<_>>();
+
+        if let Some(index) = entities.iter().position(|&entity| entity == active.0) {
+            active.0 = entities[(index + 1) % entities.len()];
+        }
+    }
+
+    if let Some(camera) = cameras.get_mut(active.0) {
+        if characters.contains(active.0) && matches!(camera.type_(), camera::Type::Fps) {
+            camera.look(&input);
+        } else {
+            camera.update(&input);
+        }
+    }
+}
+
+/// The bounding spheres of the meshes of `model`, and the normals and
+/// tangents of their vertices with [`Gizmos::vectors`].
+fn mesh_gizmos(
+    gizmos: &mut Gizmos,
+    assets: &Assets<weng::graphics::Context>,
+    model: Handle<Model>,
+    transform: &Transform,
+) {
+    let matrix = transform.matrix();
+    let normal_matrix = glam::Mat3::from_mat4(matrix).inverse().transpose();
+    let scale = transform.scale.abs().max_element();
+
+    for mesh in &assets.model(model).meshes {
+        let sphere = mesh.buffers.bounding_sphere;
+
+        gizmos.sphere(
+            matrix.transform_point3(sphere.truncate()),
+            sphere.w * scale,
+            gizmos::GREEN,
+        );
+
+        if !gizmos.vectors() {
+            continue;
+        }
+
+        for vertex in mesh.buffers.vertices() {
+            let position = matrix.transform_point3(vertex.position.into());
+
+            for (vector, color) in [
+                (vertex.normal, gizmos::BLUE),
+                (vertex.tangent, gizmos::RED),
+                (vertex.bitangent, gizmos::GREEN),
+            ] {
+                let direction = normal_matrix * glam::Vec3::from(vector);
+
+                gizmos.line(
+                    position,
+                    position + direction.normalize_or_zero() * 0.25,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+/// Keeps the transforms of the [`Interpolated`] entities from before the tick.
+pub fn remember_transforms(world: &mut World) {
+    let transforms = world.components::<Transform>();
+
+    for (entity, interpolated) in world.components_mut::<Interpolated>().iter_mut() {
+        interpolated.previous = transforms.get(entity).copied();
+    }
+}
+
+pub fn movement(world: &mut World) {
+    let delta = Time::TICK_RATE.as_secs_f32();
+    let mut transforms = world.components_mut::<Transform>();
+
+    for (entity, velocity) in world.components::<Velocity>().iter() {
+        if let Some(transform) = transforms.get_mut(entity) {
+            transform.translation += velocity.linear * delta;
+            transform.rotation = (glam::Quat::from_scaled_axis(velocity.angular * delta)
+                * transform.rotation)
+                .normalize();
+        }
+    }
+}
+
+/// Registers the components and resources the systems use.
+pub fn register(world: &mut World) {
+    world.register::<Camera>();
+    world.register::<CharacterController>();
+    world.register::<physics::Collider>();
+    world.register::<GpuCulled>();
+    world.register::<Interpolated>();
+    world.register::<Light>();
+    world.register::<MeshRenderer>();
+    world.register::<physics::RigidBody>();
+    world.register::<Transform>();
+    world.register::<Velocity>();
+
+    world.insert_resource(Blend(1.0));
+    world.insert_resource(Bvh::<Entity>::new(0.5));
+    world.insert_resource(Extracted::default());
+    world.insert_resource(Gizmos::new());
+    world.insert_resource(Input::new());
+    world.insert_resource(Loader::new());
+    world.insert_resource(Models::<weng::graphics::Context>::default());
+    world.insert_resource(Physics::new());
+}
+
+/// Hands loaded models, and their bounds, to the mesh renderers waiting for
+/// them, requests the models of new ones, removes the renderers whose model
+/// failed to load, and unloads the models nothing draws anymore.
+pub fn resolve_models(world: &mut World) {
+    let failed = std::mem::take(&mut world.resource_mut::<Models>().failed);
+
+    if !failed.is_empty() {
+        let entities: Vec<_> = world
+            .components::<MeshRenderer>()
+            .iter()
+            .filter(|(_, renderer)| failed.contains(&renderer.path))
+            .map(|(entity, _)| entity)
+            .collect();
+
+        for entity in entities {
+            world.remove::<MeshRenderer>(entity);
+        }
+    }
+
+    let mut assets = world.resource_mut::<Assets<weng::graphics::Context>>();
+    let mut loader = world.resource_mut::<Loader>();
+    let mut models = world.resource_mut::<Models>();
+    let mut renderers = world.components_mut::<MeshRenderer>();
+
+    for (_, renderer) in renderers.iter_mut() {
+        if renderer.model.is_some() {
+            continue;
+        }
+
+        match models.get(&renderer.path) {
+            Some(Some(model)) => {
+                renderer.bounds = Aabb::from_points(
+                    assets
+                        .model(model)
+                        .meshes
+                        .iter()
+                        .flat_map(|mesh| mesh.buffers.positions.iter().copied()),
+                );
+                renderer.model = Some(model);
+            }
+            Some(None) => (),
+            None => models.request(&mut loader, &renderer.path),
+        }
+    }
+
+    models.release_unused(&mut assets, &renderers);
+}
+
+pub fn schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+
+    schedule
+        .add_tick_system(remember_transforms)
+        .add_tick_system(input)
+        .add_tick_system(character::step)
+        .add_tick_system(movement)
+        .add_tick_system(physics::step)
+        .add_tick_system(physics::despawn_fallen)
+        .add_frame_system(resolve_models)
+        .add_frame_system(bounds)
+        .add_frame_system(extract)
+        .add_frame_system(gizmos);
+
+    schedule
+}
+
+/// Writes `light`, as edited outside the world, back to the first light.
+pub fn set_light(world: &World, light: &LightUniform) {
+    let mut transforms = world.components_mut::<Transform>();
+
+    for (entity, component) in world.components_mut::<Light>().iter_mut() {
+        if let Some(transform) = transforms.get_mut(entity) {
+            transform.translation = light.position.into();
+            component.color = light.color.into();
+
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::tests::{load_model, Files, Recorder};
+
+    #[test]
+    fn gpu_culled_renderers_are_extracted_apart() {
+        let mut world = World::new();
+
+        register(&mut world);
+
+        let nobody = world.spawn().id();
+
+        world.insert_resource(ActiveCamera(nobody));
+
+        for culled in [false, true, true] {
+            let mut renderer = MeshRenderer::new("cube.obj");
+
+            renderer.model = Some(Handle::test(1));
+
+            let builder = world.spawn().with(renderer).with(Transform::IDENTITY);
+
+            if culled {
+                builder.with(GpuCulled);
+            }
+        }
+        // Not drawn until its model loaded.
+        world
+            .spawn()
+            .with(MeshRenderer::new("cube.obj"))
+            .with(Transform::IDENTITY)
+            .with(GpuCulled);
+
+        extract(&mut world);
+
+        let extracted = world.resource::<Extracted>();
+
+        assert!(extracted.camera.is_none());
+        assert_eq!(extracted.instances.len(), 1);
+        assert_eq!(extracted.culled.len(), 2);
+    }
+
+    #[test]
+    fn renderers_outside_the_frustum_are_not_extracted() {
+        let mut world = World::new();
+
+        register(&mut world);
+
+        // Looking down +z from the origin.
+        let camera = world.spawn().with(Camera::new(800, 600)).id();
+
+        world.insert_resource(ActiveCamera(camera));
+
+        let spawn = |world: &mut World, z: f32, culled: bool| {
+            let mut renderer = MeshRenderer::new("cube.obj");
+
+            renderer.bounds = Aabb {
+                max: glam::Vec3::ONE,
+                min: -glam::Vec3::ONE,
+            };
+            renderer.model = Some(Handle::test(1));
+
+            let builder = world
+                .spawn()
+                .with(renderer)
+                .with(Transform::from_translation(glam::Vec3::Z * z));
+
+            if culled {
+                builder.with(GpuCulled);
+            }
+        };
+
+        spawn(&mut world, 10.0, false);
+        spawn(&mut world, -10.0, false);
+        // Beyond the far plane, but the GPU culls those.
+        spawn(&mut world, 200.0, true);
+
+        bounds(&mut world);
+        extract(&mut world);
+
+        let extracted = world.resource::<Extracted>();
+        let z = |instance: &Instance| instance.model[14];
+
+        assert!(extracted.camera.is_some());
+        assert_eq!(
+            extracted
+                .instances
+                .iter()
+                .map(|(_, instance)| z(instance))
+                .collect::<Vec<_>>(),
+            [10.0]
+        );
+        assert_eq!(extracted.culled.len(), 1);
+    }
+
+    #[test]
+    fn interpolated_renderers_are_drawn_between_ticks() {
+        let mut world = World::new();
+
+        register(&mut world);
+
+        let nobody = world.spawn().id();
+
+        world.insert_resource(ActiveCamera(nobody));
+
+        let mut renderer = MeshRenderer::new("cube.obj");
+
+        renderer.model = Some(Handle::test(1));
+
+        let entity = world
+            .spawn()
+            .with(renderer)
+            .with(Transform::IDENTITY)
+            .with(Velocity {
+                angular: glam::Vec3::ZERO,
+                linear: glam::Vec3::X / Time::TICK_RATE.as_secs_f32(),
+            })
+            .with(Interpolated::default())
+            .id();
+        let translation = |world: &World| {
+            glam::Mat4::from_cols_array(&world.resource::<Extracted>().instances[0].1.model)
+                .w_axis
+                .truncate()
+        };
+
+        // Before its first tick there is nothing to blend with.
+        world.insert_resource(Blend(0.5));
+        extract(&mut world);
+        assert_eq!(translation(&world), glam::Vec3::ZERO);
+
+        remember_transforms(&mut world);
+        movement(&mut world);
+
+        for (blend, x) in [(0.0, 0.0), (0.25, 0.25), (1.0, 1.0)] {
+            world.insert_resource(Blend(blend));
+            extract(&mut world);
+            assert!(translation(&world).abs_diff_eq(glam::Vec3::X * x, 1e-5));
+        }
+
+        // The transform itself stays where the tick left it.
+        assert!(world
+            .components::<Transform>()
+            .get(entity)
+            .unwrap()
+            .translation
+            .abs_diff_eq(glam::Vec3::X, 1e-5));
+    }
+
+    #[test]
+    fn despawning_the_last_renderer_unloads_its_model() {
+        let files = Files::new("models");
+        let backend = Recorder::default();
+        let mut assets = Assets::new(());
+        let mut models = Models::default();
+        let mut world = World::new();
+        let path = files.path("first.obj");
+
+        world.register::<MeshRenderer>();
+
+        let first = world.spawn().with(MeshRenderer::new(&path)).id();
+        let second = world.spawn().with(MeshRenderer::new(&path)).id();
+
+        models.insert(
+            path.clone(),
+            load_model(&mut assets, &backend, &path).unwrap(),
+        );
+
+        world.despawn(first);
+        models.release_unused(&mut assets, &world.components());
+        assert!(models.get(&path).is_some());
+        assert!(!assets.dependencies().is_empty());
+
+        world.despawn(second);
+        models.release_unused(&mut assets, &world.components());
+        assert_eq!(models.get(&path), None);
+        assert!(assets.dependencies().is_empty());
+        assert_eq!(assets.memory().total(), 0);
+    }
+}
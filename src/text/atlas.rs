@@ -0,0 +1,254 @@
+use super::{FontId, Fonts};
+use std::collections::HashMap;
+
+/// The pixel size glyphs are rasterized at with [`Rasterization::Sdf`].
+pub const SDF_SIZE: f32 = 48.0;
+/// How far from the edge of a glyph distances are stored, in pixels at
+/// [`SDF_SIZE`]. Also the room left around each glyph for them.
+const SDF_SPREAD: i32 = 6;
+/// Empty texels between glyphs, so that filtering does not bleed.
+const PADDING: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rasterization {
+    /// Coverage at the size the text is drawn at. The sharpest, but every
+    /// size takes its own glyphs.
+    Bitmap,
+    /// Signed distances to the outline at [`SDF_SIZE`], scaled to any size
+    /// when drawn, with slightly rounder corners.
+    #[default]
+    Sdf,
+}
+
+/// Where a glyph is in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    /// From the pen position to the top left corner of the glyph, in pixels
+    /// at `size`.
+    pub offset: glam::Vec2,
+    /// The top left corner, in texels.
+    pub origin: glam::UVec2,
+    /// The pixel size it was rasterized at. The glyph scales by the drawn size
+    /// over it.
+    pub size: f32,
+    /// In texels.
+    pub texels: glam::UVec2,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    character: char,
+    font: FontId,
+    /// The bits of the pixel size, which is the same for every glyph with
+    /// [`Rasterization::Sdf`].
+    size: u32,
+}
+
+/// A row of glyphs, as tall as the tallest of them.
+struct Shelf {
+    height: u32,
+    x: u32,
+    y: u32,
+}
+
+/// A single channel texture of the glyphs drawn so far. Glyphs are added when
+/// first asked for and never removed.
+pub struct Atlas {
+    dirty: bool,
+    /// `None` for characters without pixels, like spaces.
+    entries: HashMap<Key, Option<Entry>>,
+    full: bool,
+    pixels: Vec<u8>,
+    rasterization: Rasterization,
+    shelves: Vec<Shelf>,
+    size: u32,
+}
+
+impl Atlas {
+    /// The glyph of `character` for text of `size` pixels, rasterized if it is
+    /// not in the atlas yet. `None` when it has no pixels or does not fit.
+    pub fn glyph(
+        &mut self,
+        fonts: &Fonts,
+        font: FontId,
+        character: char,
+        size: f32,
+    ) -> Option<Entry> {
+        let size = match self.rasterization {
+            Rasterization::Bitmap => size,
+            Rasterization::Sdf => SDF_SIZE,
+        };
+        let key = Key {
+            character,
+            font,
+            size: size.to_bits(),
+        };
+
+        if let Some(&entry) = self.entries.get(&key) {
+            return entry;
+        }
+
+        let (metrics, coverage) = fonts.get(font).rasterize(character, size);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+
+        let (pixels, margin) = match self.rasterization {
+            Rasterization::Bitmap => (coverage, 0),
+            Rasterization::Sdf => (
+                signed_distances(&coverage, width, height),
+                SDF_SPREAD as u32,
+            ),
+        };
+        let texels = glam::UVec2::new(width, height) + margin * 2;
+
+        let entry = if width == 0 || height == 0 {
+            None
+        } else if let Some(origin) = self.allocate(texels) {
+            for row in 0..texels.y {
+                let start = ((origin.y + row) * self.size + origin.x) as usize;
+                let source = (row * texels.x) as usize;
+
+                self.pixels[start..start + texels.x as usize]
+                    .copy_from_slice(&pixels[source..source + texels.x as usize]);
+            }
+            self.dirty = true;
+
+            Some(Entry {
+                offset: glam::Vec2::new(
+                    metrics.xmin as f32 - margin as f32,
+                    -(metrics.ymin as f32 + height as f32) - margin as f32,
+                ),
+                origin,
+                size,
+                texels,
+            })
+        } else {
+            if !self.full {
+                log::warn!("the {0}x{0} glyph atlas is full", self.size);
+            }
+            self.full = true;
+
+            None
+        };
+
+        // A glyph that did not fit is not retried.
+        self.entries.insert(key, entry);
+
+        entry
+    }
+
+    pub fn new(size: u32, rasterization: Rasterization) -> Self {
+        Self {
+            dirty: true,
+            entries: HashMap::new(),
+            full: false,
+            pixels: vec![0; (size * size) as usize],
+            rasterization,
+            shelves: Vec::new(),
+            size,
+        }
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn rasterization(&self) -> Rasterization {
+        self.rasterization
+    }
+
+    /// The width and height, in texels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Whether glyphs were added since the last call, so the pixels need to
+    /// be uploaded again.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// Finds room on the lowest shelf that fits, or starts a new one.
+    fn allocate(&mut self, texels: glam::UVec2) -> Option<glam::UVec2> {
+        let (width, height) = (texels.x + PADDING, texels.y + PADDING);
+        let size = self.size;
+
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .find(|shelf| height <= shelf.height && shelf.x + width <= size)
+        {
+            let origin = glam::UVec2::new(shelf.x, shelf.y);
+            shelf.x += width;
+
+            return Some(origin);
+        }
+
+        // The last shelf can grow, as there is nothing below it yet.
+        if let Some(shelf) = self.shelves.last_mut() {
+            if shelf.x + width <= size && shelf.y + height <= size {
+                let origin = glam::UVec2::new(shelf.x, shelf.y);
+                shelf.height = height;
+                shelf.x += width;
+
+                return Some(origin);
+            }
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+
+        if y + height > self.size || width > self.size {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            height,
+            x: width,
+            y,
+        });
+
+        Some(glam::UVec2::new(0, y))
+    }
+}
+
+/// Turns the coverage of a glyph into distances to its outline, 0.5 on the
+/// edge and rising inside, with [`SDF_SPREAD`] texels of room on every side.
+fn signed_distances(coverage: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as i32, height as i32);
+    let inside = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && x < width && y < height && coverage[(y * width + x) as usize] >= 128
+    };
+
+    let (padded_width, padded_height) = (width + SDF_SPREAD * 2, height + SDF_SPREAD * 2);
+    let mut distances = Vec::with_capacity((padded_width * padded_height) as usize);
+
+    for y in -SDF_SPREAD..height + SDF_SPREAD {
+        for x in -SDF_SPREAD..width + SDF_SPREAD {
+            let center = inside(x, y);
+
+            // The closest texel on the other side of the edge, searched for
+            // within the spread only.
+            let mut closest = (SDF_SPREAD * SDF_SPREAD) as f32;
+            for dy in -SDF_SPREAD..=SDF_SPREAD {
+                for dx in -SDF_SPREAD..=SDF_SPREAD {
+                    if inside(x + dx, y + dy) != center {
+                        closest = closest.min((dx * dx + dy * dy) as f32);
+                    }
+                }
+            }
+
+            // The edge lies between two texels, half a texel from each.
+            let distance = (closest.sqrt() - 0.5).max(0.0);
+            let signed = if center { distance } else { -distance };
+
+            distances.push(
+                ((0.5 + signed / (SDF_SPREAD as f32 * 2.0)).clamp(0.0, 1.0) * 255.0).round() as u8,
+            );
+        }
+    }
+
+    distances
+}
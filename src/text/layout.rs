@@ -0,0 +1,339 @@
+use super::{FontId, Fonts};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    /// Within the width of the layout, which is `max_width` when it is set or
+    /// the longest line otherwise.
+    pub align: Align,
+    pub font: FontId,
+    /// Lines that would be longer break between words, or within a word that
+    /// does not fit on a line by itself.
+    pub max_width: Option<f32>,
+    /// The size of the em square in pixels.
+    pub size: f32,
+}
+
+impl Style {
+    pub fn new(font: FontId, size: f32) -> Self {
+        Self {
+            align: Align::Left,
+            font,
+            max_width: None,
+            size,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub character: char,
+    /// The pen position on the baseline, from the top left corner of the
+    /// layout. The glyph is drawn to the right and mostly above it.
+    pub position: glam::Vec2,
+}
+
+/// Text placed in lines, in pixels with y going down.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub font: FontId,
+    /// Whitespace takes up room but has no glyphs.
+    pub glyphs: Vec<Glyph>,
+    pub lines: usize,
+    pub size: glam::Vec2,
+    /// The pixel size the glyphs are drawn at.
+    pub text_size: f32,
+}
+
+/// What layout needs from a font, in pixels at `size`.
+trait Metrics {
+    fn advance(&self, character: char, size: f32) -> f32;
+    fn kern(&self, left: char, right: char, size: f32) -> f32;
+    /// The ascent and the distance between baselines.
+    fn line_metrics(&self, size: f32) -> (f32, f32);
+}
+
+impl Metrics for fontdue::Font {
+    fn advance(&self, character: char, size: f32) -> f32 {
+        self.metrics(character, size).advance_width
+    }
+
+    fn kern(&self, left: char, right: char, size: f32) -> f32 {
+        self.horizontal_kern(left, right, size).unwrap_or(0.0)
+    }
+
+    fn line_metrics(&self, size: f32) -> (f32, f32) {
+        match self.horizontal_line_metrics(size) {
+            Some(metrics) => (metrics.ascent, metrics.new_line_size),
+            None => (size, size),
+        }
+    }
+}
+
+/// A character of a line being laid out, with where it starts and its
+/// advance.
+#[derive(Clone, Copy)]
+struct Placed {
+    advance: f32,
+    character: char,
+    x: f32,
+}
+
+/// Places `text` in lines, breaking at `\n` and at the width of `style`.
+pub fn layout(fonts: &Fonts, text: &str, style: &Style) -> Layout {
+    layout_with(fonts.get(style.font), text, style)
+}
+
+fn layout_with(font: &impl Metrics, text: &str, style: &Style) -> Layout {
+    let (ascent, line_height) = font.line_metrics(style.size);
+
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line: Vec<Placed> = Vec::new();
+        // The index after the last whitespace, where the line can break.
+        let mut break_at = None;
+
+        for character in paragraph.chars() {
+            let mut placed = place(font, style.size, line.last(), character);
+
+            let overflows = matches!(style.max_width, Some(max) if placed.x + placed.advance > max);
+
+            if overflows && !character.is_whitespace() && !line.is_empty() {
+                // Only the word being written moves to the next line, unless
+                // it is the only one on this line.
+                let rest = line.split_off(break_at.unwrap_or(line.len()));
+                lines.push(line);
+
+                line = Vec::new();
+                for placed in rest {
+                    let placed = place(font, style.size, line.last(), placed.character);
+                    line.push(placed);
+                }
+                break_at = None;
+
+                placed = place(font, style.size, line.last(), character);
+            }
+
+            line.push(placed);
+
+            if character.is_whitespace() {
+                break_at = Some(line.len());
+            }
+        }
+
+        lines.push(line);
+    }
+
+    let widths = lines
+        .iter()
+        .map(|line| line_width(line))
+        .collect::<Vec<_>>();
+    let width = style
+        .max_width
+        .unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+
+    let mut glyphs = Vec::new();
+
+    for (index, (line, line_width)) in lines.iter().zip(&widths).enumerate() {
+        let offset = match style.align {
+            Align::Left => 0.0,
+            Align::Center => (width - line_width) / 2.0,
+            Align::Right => width - line_width,
+        };
+        let baseline = ascent + index as f32 * line_height;
+
+        glyphs.extend(
+            line.iter()
+                .filter(|placed| !placed.character.is_whitespace())
+                .map(|placed| Glyph {
+                    character: placed.character,
+                    position: glam::Vec2::new(offset + placed.x, baseline),
+                }),
+        );
+    }
+
+    Layout {
+        font: style.font,
+        glyphs,
+        lines: lines.len(),
+        size: glam::Vec2::new(width, lines.len() as f32 * line_height),
+        text_size: style.size,
+    }
+}
+
+/// Where `character` goes after `previous`, kerned.
+fn place(font: &impl Metrics, size: f32, previous: Option<&Placed>, character: char) -> Placed {
+    let x = match previous {
+        Some(previous) => {
+            previous.x + previous.advance + font.kern(previous.character, character, size)
+        }
+        None => 0.0,
+    };
+
+    Placed {
+        advance: font.advance(character, size),
+        character,
+        x,
+    }
+}
+
+/// Up to the end of the last glyph, so trailing whitespace does not count.
+fn line_width(line: &[Placed]) -> f32 {
+    line.iter()
+        .rev()
+        .find(|placed| !placed.character.is_whitespace())
+        .map_or(0.0, |placed| placed.x + placed.advance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character is a tenth of the size wide, lines are the size apart
+    /// with baselines 80% down, and "AV" is kerned one pixel closer.
+    struct Fixed;
+
+    impl Metrics for Fixed {
+        fn advance(&self, _: char, size: f32) -> f32 {
+            size / 10.0
+        }
+
+        fn kern(&self, left: char, right: char, _: f32) -> f32 {
+            if (left, right) == ('A', 'V') {
+                -1.0
+            } else {
+                0.0
+            }
+        }
+
+        fn line_metrics(&self, size: f32) -> (f32, f32) {
+            (size * 0.8, size)
+        }
+    }
+
+    fn style(max_width: Option<f32>, align: Align) -> Style {
+        Style {
+            align,
+            max_width,
+            ..Style::new(FontId(0), 100.0)
+        }
+    }
+
+    /// The characters of every line, and where each line starts.
+    fn lines(layout: &Layout) -> Vec<(String, glam::Vec2)> {
+        let mut lines: Vec<(String, glam::Vec2)> = Vec::new();
+
+        for glyph in &layout.glyphs {
+            match lines.last_mut() {
+                Some((line, start)) if start.y == glyph.position.y => line.push(glyph.character),
+                _ => lines.push((glyph.character.to_string(), glyph.position)),
+            }
+        }
+
+        lines
+    }
+
+    #[test]
+    fn characters_advance_along_one_baseline() {
+        let layout = layout_with(&Fixed, "abc", &style(None, Align::Left));
+        let positions = layout
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.position)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            positions,
+            [
+                glam::Vec2::new(0.0, 80.0),
+                glam::Vec2::new(10.0, 80.0),
+                glam::Vec2::new(20.0, 80.0),
+            ]
+        );
+        assert_eq!(layout.lines, 1);
+        assert_eq!(layout.size, glam::Vec2::new(30.0, 100.0));
+    }
+
+    #[test]
+    fn newlines_start_lines_and_whitespace_has_no_glyphs() {
+        let layout = layout_with(&Fixed, "ab\n\n c", &style(None, Align::Left));
+
+        assert_eq!(
+            lines(&layout),
+            [
+                ("ab".to_string(), glam::Vec2::new(0.0, 80.0)),
+                ("c".to_string(), glam::Vec2::new(10.0, 280.0)),
+            ]
+        );
+        assert_eq!(layout.lines, 3);
+        assert_eq!(layout.size, glam::Vec2::new(20.0, 300.0));
+    }
+
+    #[test]
+    fn long_lines_wrap_between_words() {
+        let layout = layout_with(&Fixed, "ab cd ef", &style(Some(55.0), Align::Left));
+
+        assert_eq!(
+            lines(&layout),
+            [
+                ("abcd".to_string(), glam::Vec2::new(0.0, 80.0)),
+                ("ef".to_string(), glam::Vec2::new(0.0, 180.0)),
+            ]
+        );
+        assert_eq!(layout.size, glam::Vec2::new(55.0, 200.0));
+    }
+
+    #[test]
+    fn words_longer_than_a_line_break_within_themselves() {
+        let layout = layout_with(&Fixed, "abcdefg", &style(Some(30.0), Align::Left));
+
+        assert_eq!(
+            lines(&layout)
+                .into_iter()
+                .map(|(line, _)| line)
+                .collect::<Vec<_>>(),
+            ["abc", "def", "g"]
+        );
+    }
+
+    #[test]
+    fn lines_align_within_the_width() {
+        let text = "abcd\nab";
+
+        for (align, x) in [
+            (Align::Left, 0.0),
+            (Align::Center, 10.0),
+            (Align::Right, 20.0),
+        ] {
+            let layout = layout_with(&Fixed, text, &style(None, align));
+
+            assert_eq!(lines(&layout)[0].1.x, 0.0);
+            assert_eq!(lines(&layout)[1].1.x, x, "{align:?}");
+        }
+
+        let layout = layout_with(&Fixed, "ab", &style(Some(100.0), Align::Right));
+
+        assert_eq!(layout.glyphs[0].position.x, 80.0);
+    }
+
+    #[test]
+    fn kerning_moves_pairs_closer() {
+        let layout = layout_with(&Fixed, "AVA", &style(None, Align::Left));
+        let xs = layout
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.position.x)
+            .collect::<Vec<_>>();
+
+        assert_eq!(xs, [0.0, 9.0, 19.0]);
+        assert_eq!(layout.size.x, 29.0);
+    }
+}
@@ -0,0 +1,38 @@
+//! Text from TTF and OTF fonts. [`layout`] places glyphs and [`Atlas`]
+//! rasterizes them, both on the CPU, and `renderer::text` draws the result.
+
+pub mod atlas;
+pub mod layout;
+
+pub use atlas::{Atlas, Rasterization};
+pub use layout::{layout, Align, Layout, Style};
+
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+/// The loaded fonts, which text refers to by [`FontId`].
+pub struct Fonts {
+    fonts: Vec<fontdue::Font>,
+}
+
+impl Fonts {
+    pub fn get(&self, id: FontId) -> &fontdue::Font {
+        &self.fonts[id.0]
+    }
+
+    pub fn insert(&mut self, font: fontdue::Font) -> FontId {
+        self.fonts.push(font);
+
+        FontId(self.fonts.len() - 1)
+    }
+
+    pub fn load(&mut self, path: &Path) -> anyhow::Result<FontId> {
+        Ok(self.insert(crate::data::fonts::load(path)?))
+    }
+
+    pub fn new() -> Self {
+        Self { fonts: Vec::new() }
+    }
+}
@@ -1,7 +1,15 @@
-#![allow(unused)]
+/// How far the frame is between the last tick and the next, from 0 to 1, for
+/// the frame systems. [`Schedule::run`] updates it.
+///
+/// [`Schedule::run`]: crate::ecs::Schedule::run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blend(pub f32);
 
 pub struct Time {
     accumulator: std::time::Duration,
+    /// Smoothed over the last frames, so that it can be read.
+    average_frame_time: f64,
+    frame_ticks: u32,
     last_time: std::time::Instant,
 }
 
@@ -10,26 +18,53 @@ impl Time {
 
     pub fn begin_loop(&mut self) {
         let time = std::time::Instant::now();
-        self.accumulator += time - self.last_time;
+        let frame_time = time - self.last_time;
+        self.accumulator += frame_time;
         self.last_time = time;
+
+        self.average_frame_time += (frame_time.as_secs_f64() - self.average_frame_time) * 0.05;
+        self.frame_ticks = 0;
     }
 
     pub fn blend_factor(&self) -> f64 {
         self.accumulator.as_secs_f64() / Self::TICK_RATE.as_secs_f64()
     }
 
+    pub fn fps(&self) -> f64 {
+        1.0 / self.average_frame_time.max(f64::EPSILON)
+    }
+
+    /// The average time between frames, in seconds.
+    pub fn frame_time(&self) -> f64 {
+        self.average_frame_time
+    }
+
+    /// How many ticks ran since the frame began.
+    pub fn frame_ticks(&self) -> u32 {
+        self.frame_ticks
+    }
+
     pub fn new() -> Self {
         Self {
             accumulator: std::time::Duration::ZERO,
+            average_frame_time: Self::TICK_RATE.as_secs_f64(),
+            frame_ticks: 0,
             last_time: std::time::Instant::now(),
         }
     }
 
+    /// Drops the time owed to ticks, so a paused simulation does not catch up
+    /// once it resumes.
+    pub fn skip_ticks(&mut self) {
+        self.accumulator = std::time::Duration::ZERO;
+    }
+
     pub fn should_update(&self) -> bool {
         self.accumulator >= Self::TICK_RATE
     }
 
     pub fn update(&mut self) {
         self.accumulator -= Self::TICK_RATE;
+        self.frame_ticks += 1;
     }
 }
@@ -0,0 +1,289 @@
+//! The debug overlay, an egui window toggled with F1. While it is open the
+//! cursor is released from the camera, and typing into a text field keeps
+//! keys from moving it.
+
+use crate::{
+    camera::Camera,
+    data::shaders::basic::LightUniform,
+    gizmos::Gizmos,
+    settings::{self, View},
+    time::Time,
+    window::Window,
+};
+use std::path::PathBuf;
+use strum::IntoEnumIterator;
+
+/// Roughly the height of a line of text, which is what a scroll step moves.
+const SCROLL_STEP: f32 = 20.0;
+
+pub struct DebugUi {
+    context: egui::Context,
+    model_path: String,
+    modifiers: egui::Modifiers,
+    open: bool,
+    start: std::time::Instant,
+}
+
+/// What the overlay shows without editing.
+pub struct Status<'a> {
+    pub camera: &'a Camera,
+    /// The names of the game states, from the bottom up.
+    pub states: Vec<&'static str>,
+    pub time: &'a Time,
+}
+
+/// What the overlay changed or asked for in a frame, and what to paint.
+#[derive(Default)]
+pub struct Output {
+    pub debug_changed: bool,
+    pub light_changed: bool,
+    /// A model to load and place around the origin.
+    pub load_model: Option<PathBuf>,
+    pub pixels_per_point: f32,
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub textures_delta: egui::TexturesDelta,
+}
+
+impl DebugUi {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn new() -> Self {
+        Self {
+            context: egui::Context::default(),
+            model_path: String::new(),
+            modifiers: egui::Modifiers::default(),
+            open: false,
+            start: std::time::Instant::now(),
+        }
+    }
+
+    /// Runs the overlay on the events of the last [`Window::events`], editing
+    /// what it shows in place.
+    pub fn run(
+        &mut self,
+        window: &mut Window,
+        status: &Status,
+        light: &mut LightUniform,
+        debug: &mut settings::Debug,
+        gizmos: &mut Gizmos,
+    ) -> Output {
+        let toggled = window.polled_events().iter().any(|event| {
+            matches!(
+                event,
+                glfw::WindowEvent::Key(glfw::Key::F1, _, glfw::Action::Press, _)
+            )
+        });
+
+        if toggled {
+            self.open = !self.open;
+        }
+
+        let mut output = Output {
+            pixels_per_point: 1.0,
+            ..Default::default()
+        };
+
+        if !self.open {
+            return output;
+        }
+
+        let input = self.raw_input(window);
+        let full_output = self.context.run(input, |context| {
+            egui::Window::new("Debug").show(context, |ui| {
+                ui.heading("Frame");
+                ui.label(format!(
+                    "{:.0} fps, {:.2} ms",
+                    status.time.fps(),
+                    status.time.frame_time() * 1000.0
+                ));
+                ui.label(format!("{} ticks this frame", status.time.frame_ticks()));
+                ui.label(status.states.join(" > "));
+
+                ui.separator();
+                ui.heading("Camera");
+                let position = status.camera.position();
+                ui.label(format!(
+                    "{:.2}, {:.2}, {:.2}",
+                    position.x, position.y, position.z
+                ));
+                ui.label(format!("{:?} mode", status.camera.type_()));
+
+                ui.separator();
+                ui.heading("Light");
+                ui.horizontal(|ui| {
+                    ui.label("Position");
+                    for value in &mut light.position {
+                        output.light_changed |=
+                            ui.add(egui::DragValue::new(value).speed(0.1)).changed();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Color");
+                    output.light_changed |= ui.color_edit_button_rgb(&mut light.color).changed();
+                });
+
+                ui.separator();
+                ui.heading("Debug");
+                egui::ComboBox::from_label("View")
+                    .selected_text(format!("{:?}", debug.view))
+                    .show_ui(ui, |ui| {
+                        for view in View::iter() {
+                            output.debug_changed |= ui
+                                .selectable_value(&mut debug.view, view, format!("{view:?}"))
+                                .changed();
+                        }
+                    });
+                output.debug_changed |= ui.checkbox(&mut debug.wireframe, "Wireframe").changed();
+                output.debug_changed |= ui.checkbox(&mut debug.vectors, "Vectors").changed();
+
+                let mut enabled = gizmos.enabled();
+                if ui.checkbox(&mut enabled, "Gizmos").changed() {
+                    gizmos.set_enabled(enabled);
+                }
+
+                ui.separator();
+                ui.heading("Models");
+                ui.horizontal(|ui| {
+                    let field = ui.text_edit_singleline(&mut self.model_path);
+                    let submitted =
+                        field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+
+                    if (ui.button("Load").clicked() || submitted) && !self.model_path.is_empty() {
+                        output.load_model = Some(PathBuf::from(&self.model_path));
+                    }
+                });
+            });
+        });
+
+        if !full_output.platform_output.copied_text.is_empty() {
+            window.set_clipboard(&full_output.platform_output.copied_text);
+        }
+
+        output.pixels_per_point = self.context.pixels_per_point();
+        output.primitives = self.context.tessellate(full_output.shapes);
+        output.textures_delta = full_output.textures_delta;
+
+        output
+    }
+
+    /// Whether the overlay takes the keys, so they should not also move the
+    /// camera.
+    pub fn wants_keyboard(&self) -> bool {
+        self.open && self.context.wants_keyboard_input()
+    }
+
+    fn raw_input(&mut self, window: &Window) -> egui::RawInput {
+        let size = window.get_framebuffer_size().as_vec2();
+        let cursor = window.get_cursor_position();
+        let pointer = egui::pos2(cursor.x, cursor.y);
+
+        let mut events = vec![egui::Event::PointerMoved(pointer)];
+
+        for event in window.polled_events() {
+            match *event {
+                glfw::WindowEvent::Char(character) => {
+                    events.push(egui::Event::Text(character.to_string()));
+                }
+                glfw::WindowEvent::Key(key, _, action, modifiers) => {
+                    self.modifiers = translate_modifiers(modifiers);
+
+                    let pressed = action != glfw::Action::Release;
+
+                    if pressed && self.modifiers.command {
+                        match key {
+                            glfw::Key::C => events.push(egui::Event::Copy),
+                            glfw::Key::X => events.push(egui::Event::Cut),
+                            glfw::Key::V => {
+                                if let Some(text) = window.clipboard() {
+                                    events.push(egui::Event::Paste(text));
+                                }
+                            }
+                            _ => (),
+                        }
+                    }
+
+                    if let Some(key) = translate_key(key) {
+                        events.push(egui::Event::Key {
+                            key,
+                            pressed,
+                            repeat: action == glfw::Action::Repeat,
+                            modifiers: self.modifiers,
+                        });
+                    }
+                }
+                glfw::WindowEvent::MouseButton(button, action, modifiers) => {
+                    self.modifiers = translate_modifiers(modifiers);
+
+                    let button = match button {
+                        glfw::MouseButtonLeft => egui::PointerButton::Primary,
+                        glfw::MouseButtonRight => egui::PointerButton::Secondary,
+                        glfw::MouseButtonMiddle => egui::PointerButton::Middle,
+                        _ => continue,
+                    };
+
+                    events.push(egui::Event::PointerButton {
+                        pos: pointer,
+                        button,
+                        pressed: action == glfw::Action::Press,
+                        modifiers: self.modifiers,
+                    });
+                }
+                glfw::WindowEvent::Scroll(x, y) => {
+                    events.push(egui::Event::Scroll(
+                        egui::vec2(x as f32, y as f32) * SCROLL_STEP,
+                    ));
+                }
+                _ => (),
+            }
+        }
+
+        egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(size.x, size.y),
+            )),
+            // The cursor and the screen are both in framebuffer pixels.
+            pixels_per_point: Some(1.0),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events,
+            ..Default::default()
+        }
+    }
+}
+
+fn translate_key(key: glfw::Key) -> Option<egui::Key> {
+    Some(match key {
+        glfw::Key::A => egui::Key::A,
+        glfw::Key::Backspace => egui::Key::Backspace,
+        glfw::Key::C => egui::Key::C,
+        glfw::Key::Delete => egui::Key::Delete,
+        glfw::Key::Down => egui::Key::ArrowDown,
+        glfw::Key::End => egui::Key::End,
+        glfw::Key::Enter => egui::Key::Enter,
+        glfw::Key::Escape => egui::Key::Escape,
+        glfw::Key::Home => egui::Key::Home,
+        glfw::Key::Left => egui::Key::ArrowLeft,
+        glfw::Key::Right => egui::Key::ArrowRight,
+        glfw::Key::Tab => egui::Key::Tab,
+        glfw::Key::Up => egui::Key::ArrowUp,
+        glfw::Key::V => egui::Key::V,
+        glfw::Key::X => egui::Key::X,
+        glfw::Key::Z => egui::Key::Z,
+        _ => return None,
+    })
+}
+
+fn translate_modifiers(modifiers: glfw::Modifiers) -> egui::Modifiers {
+    let ctrl = modifiers.contains(glfw::Modifiers::Control);
+
+    egui::Modifiers {
+        alt: modifiers.contains(glfw::Modifiers::Alt),
+        ctrl,
+        shift: modifiers.contains(glfw::Modifiers::Shift),
+        mac_cmd: false,
+        command: ctrl,
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Polls the modification times of watched files. Polling is throttled so that
+/// calling [`Watcher::changed`] every frame stays cheap.
+pub struct Watcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < Self::POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        self.files
+            .iter_mut()
+            .filter_map(|(path, modified)| {
+                let new_modified = Self::modified(path);
+
+                (new_modified != *modified).then(|| {
+                    *modified = new_modified;
+                    path.clone()
+                })
+            })
+            .collect()
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let modified = Self::modified(&path);
+
+        self.files.insert(path, modified);
+    }
+}
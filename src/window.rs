@@ -1,15 +1,39 @@
 use anyhow::anyhow;
 use glfw::Glfw;
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use std::sync::mpsc::Receiver;
 
 pub struct Window {
     context: Glfw,
+    events: Vec<glfw::WindowEvent>,
+    receiver: Receiver<(f64, glfw::WindowEvent)>,
     window: glfw::Window,
 }
 
 impl Window {
+    pub fn clipboard(&self) -> Option<String> {
+        self.window.get_clipboard_string()
+    }
+    /// Whether the cursor is hidden and moves the camera, rather than free to
+    /// use the UI.
+    pub fn cursor_captured(&self) -> bool {
+        self.window.get_cursor_mode() == glfw::CursorMode::Disabled
+    }
     pub fn events(&mut self) {
         self.context.poll_events();
+
+        self.events = glfw::flush_messages(&self.receiver)
+            .map(|(_, event)| event)
+            .collect();
+    }
+    /// The cursor position in framebuffer pixels, from the top left corner.
+    pub fn get_cursor_position(&self) -> glam::Vec2 {
+        let (x, y) = self.window.get_cursor_pos();
+        let (width, height) = self.window.get_size();
+        let framebuffer_size = self.get_framebuffer_size().as_vec2();
+
+        glam::Vec2::new(x as f32, y as f32) * framebuffer_size
+            / glam::Vec2::new(width as f32, height as f32).max(glam::Vec2::ONE)
     }
     pub fn get_relative_mouse_position(&self) -> glam::DVec2 {
         let (x, y) = self.window.get_cursor_pos();
@@ -33,21 +57,45 @@ impl Window {
 
         context.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::NoApi));
 
-        let (mut window, _) = context
+        let (mut window, receiver) = context
             .create_window(width, height, title, glfw::WindowMode::Windowed)
             .unwrap();
 
         window.set_cursor_mode(glfw::CursorMode::Disabled);
+        // For the UI, which needs every key and character in order.
+        window.set_char_polling(true);
+        window.set_key_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_scroll_polling(true);
 
         context
             .supports_raw_motion()
             .then(|| {
                 window.set_raw_mouse_motion(true);
 
-                Self { context, window }
+                Self {
+                    context,
+                    events: Vec::new(),
+                    receiver,
+                    window,
+                }
             })
             .ok_or_else(|| anyhow!("Mouse raw motion unsupported"))
     }
+    /// The events received by the last call to [`Window::events`].
+    pub fn polled_events(&self) -> &[glfw::WindowEvent] {
+        &self.events
+    }
+    pub fn set_clipboard(&mut self, text: &str) {
+        self.window.set_clipboard_string(text);
+    }
+    pub fn set_cursor_captured(&mut self, captured: bool) {
+        self.window.set_cursor_mode(if captured {
+            glfw::CursorMode::Disabled
+        } else {
+            glfw::CursorMode::Normal
+        });
+    }
     pub fn should_close(&self) -> bool {
         self.window.should_close()
     }